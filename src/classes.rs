@@ -0,0 +1,152 @@
+//! Registry of the tag classes that have a known struct, keyed by the reference class of their entry
+
+use std::fmt::Debug;
+use std::ops::Range;
+
+use binrw::{BinRead, BinResult, Endian};
+use destiny_pkg::TagHash;
+use serde::Serialize;
+
+use crate::entity::{Unk808073a5, Unk80809c0f, Unk80809c36};
+use crate::map::{Unk8080714f, Unk80807164, Unk80807dae, Unk80808a54, Unk808091e0, Unk808099d6};
+use crate::map_resources::{Unk80806df3, Unk80806e68, Unk80807268, Unk80809162, Unk80809802};
+use crate::material::{Unk80806cb1, Unk808071e8};
+use crate::statics::{Unk80807194, Unk808071a7, Unk8080966d};
+use crate::structure::{read_shared_traced, read_tag_shared, TagBuffer};
+use crate::text::StringSetHeader;
+use crate::texture::TexturePlateSet;
+use crate::unknown::Unk80804f72;
+
+/// Byte ranges read while parsing a tag, and the result of the parse
+pub type Regions = (Vec<Range<u64>>, BinResult<()>);
+
+pub struct TagClass {
+    /// Reference class of the tag entry (`entry.reference`)
+    pub reference: u32,
+    pub name: &'static str,
+    /// Struct the tag is parsed as
+    pub type_name: &'static str,
+    /// Name used by older tools, eg. `D2Class_1E898080`
+    pub legacy_name: Option<&'static str>,
+
+    parse: fn(TagHash) -> anyhow::Result<Box<dyn Debug>>,
+    parse_value: fn(TagHash) -> anyhow::Result<serde_yaml::Value>,
+    regions: fn(&TagBuffer) -> Regions,
+}
+
+impl TagClass {
+    pub fn parse(&self, tag: TagHash) -> anyhow::Result<Box<dyn Debug>> {
+        (self.parse)(tag)
+    }
+
+    /// Parses the tag into a tree of its fields. Lazy tables and tags are read as they're
+    /// serialized, so nested data is included
+    pub fn parse_value(&self, tag: TagHash) -> anyhow::Result<serde_yaml::Value> {
+        (self.parse_value)(tag)
+    }
+
+    /// Byte ranges of `data` read when parsing it as this class, one per field. If parsing fails
+    /// the ranges read up to that point are still returned
+    pub fn regions(&self, data: &TagBuffer) -> Regions {
+        (self.regions)(data)
+    }
+}
+
+fn parse<T>(tag: TagHash) -> anyhow::Result<Box<dyn Debug>>
+where
+    T: BinRead + Debug + 'static,
+    for<'a> T::Args<'a>: Default,
+{
+    Ok(Box::new(read_tag_shared::<T>(tag)?))
+}
+
+fn parse_value<T>(tag: TagHash) -> anyhow::Result<serde_yaml::Value>
+where
+    T: BinRead + Serialize,
+    for<'a> T::Args<'a>: Default,
+{
+    Ok(serde_yaml::to_value(read_tag_shared::<T>(tag)?)?)
+}
+
+fn regions<T>(data: &TagBuffer) -> Regions
+where
+    T: BinRead,
+    for<'a> T::Args<'a>: Default,
+{
+    let (result, reads) = read_shared_traced::<T>(data, Endian::Little);
+    (reads, result.map(|_| ()))
+}
+
+macro_rules! tag_classes {
+    ($($reference:literal => $ty:ident, $name:literal $(, $legacy:literal)?;)*) => {
+        &[$(TagClass {
+            reference: $reference,
+            name: $name,
+            type_name: stringify!($ty),
+            legacy_name: tag_classes!(@legacy $($legacy)?),
+            parse: parse::<$ty>,
+            parse_value: parse_value::<$ty>,
+            regions: regions::<$ty>,
+        },)*]
+    };
+    (@legacy) => { None };
+    (@legacy $legacy:literal) => { Some($legacy) };
+}
+
+static CLASSES: &[TagClass] = tag_classes! {
+    0x80807dae => Unk80807dae, "Map", "D2Class_1E898080";
+    0x808091e0 => Unk808091e0, "Map resources", "D2Class_01878080";
+    0x80808a54 => Unk80808a54, "Map resource", "D2Class_07878080";
+    0x808099d6 => Unk808099d6, "Map data table", "D2Class_83988080";
+    0x8080966d => Unk8080966d, "Placement group";
+    0x8080714f => Unk8080714f, "Terrain";
+    0x80807164 => Unk80807164, "Resource bounds";
+    0x808071a7 => Unk808071a7, "Static model";
+    0x80807194 => Unk80807194, "Static mesh data";
+    0x80809c0f => Unk80809c0f, "Entity";
+    0x80809c36 => Unk80809c36, "Entity resource";
+    0x808073a5 => Unk808073a5, "Entity model";
+    0x808071e8 => Unk808071e8, "Material";
+    0x80806cb1 => Unk80806cb1, "Material set";
+    0x80806e68 => Unk80806e68, "Decal collection";
+    0x80806df3 => Unk80806df3, "Mesh instances";
+    0x80807268 => Unk80807268, "Octree (unknown)";
+    0x80809162 => Unk80809162, "Respawn points";
+    0x80809802 => Unk80809802, "Audio";
+    0x80809a88 => StringSetHeader, "String set";
+    0x808072d2 => TexturePlateSet, "Texture plate set";
+    0x80804f72 => Unk80804f72, "Unknown hash table";
+};
+
+pub fn classes() -> &'static [TagClass] {
+    CLASSES
+}
+
+/// Looks up the class of a tag entry by its reference class
+pub fn class(reference: u32) -> Option<&'static TagClass> {
+    CLASSES.iter().find(|c| c.reference == reference)
+}
+
+/// Looks up a class by its reference class in hex (`808071e8`, `0x808071e8`), struct name,
+/// legacy name or human name
+pub fn find_class(query: &str) -> Option<&'static TagClass> {
+    let query = query.trim();
+    let hex = query.trim_start_matches("0x").trim_start_matches("0X");
+    if let Ok(reference) = u32::from_str_radix(hex, 16) {
+        if let Some(class) = class(reference) {
+            return Some(class);
+        }
+    }
+
+    CLASSES.iter().find(|c| {
+        c.type_name.eq_ignore_ascii_case(query)
+            || c.name.eq_ignore_ascii_case(query)
+            || c.legacy_name.is_some_and(|n| n.eq_ignore_ascii_case(query))
+    })
+}
+
+/// Class of `tag`, if it's registered
+pub fn class_of(tag: TagHash) -> Option<&'static TagClass> {
+    let entry = crate::packages::package_manager().get_entry(tag).ok()?;
+    class(entry.reference)
+}
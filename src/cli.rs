@@ -0,0 +1,773 @@
+//! Command line interface. Everything except `view` runs headless, without creating a window or a
+//! D3D device, and builds on any platform. `view` and `disasm-shader` need Direct3D, so on other
+//! platforms they fail with an error instead
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use anyhow::Context;
+use binrw::BinReaderExt;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use destiny_pkg::TagHash;
+use itertools::Itertools;
+use nohash_hasher::IntSet;
+
+use crate::classes;
+use crate::commands::{parse_class, parse_split_tag, parse_tag, parse_tag64};
+use crate::dxbc::DxbcHeader;
+use crate::entity::read_entity_model;
+use crate::export::batch::{self, BatchOptions, BatchProgress, BatchSelection};
+use crate::export::dump::DumpFormat;
+use crate::map::{Unk80806ef4, Unk8080714f, Unk80807dae, Unk80808a54, Unk808099d8};
+use crate::map_resources::Unk8080714b;
+use crate::material::Unk808071e8;
+use crate::packages::{self, package_manager};
+use crate::references::{self, IndexProgress};
+use crate::render::bytecode::opcodes::TfxBytecodeOp;
+use crate::render::shader;
+use crate::search::{self, SearchProgress, SearchQuery};
+use crate::statics::Unk808071a7;
+use crate::text::load_global_strings;
+use crate::texture::load_texture_data;
+use crate::version;
+use crate::{dds, export, mesh};
+
+#[derive(Parser)]
+#[command(name = "alkahest", about = "Destiny 2 map viewer and extraction tool")]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Package to view, same as `alkahest view <package>`
+    package: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Cli {
+    pub fn into_command(self) -> anyhow::Result<Command> {
+        match (self.command, self.package) {
+            (Some(command), _) => Ok(command),
+            (None, Some(package)) => Ok(Command::View(PackageArgs { package })),
+            (None, None) => anyhow::bail!("No package file was given!"),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct PackageArgs {
+    /// Path to a package file. All packages in the same directory are loaded
+    pub package: String,
+}
+
+#[derive(Args)]
+pub struct TagArgs {
+    #[command(flatten)]
+    pub package: PackageArgs,
+    /// Tag hash as displayed in the viewer (eg. 'EFBE7980')
+    #[arg(value_parser = parse_tag)]
+    pub tag: TagHash,
+    /// Output file
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("selection").args(["file_type", "class", "recursive"])))]
+pub struct BatchArgs {
+    #[command(flatten)]
+    pub package: PackageArgs,
+    /// Dump all tags with this file type, from all packages
+    #[arg(long = "type")]
+    pub file_type: Option<u8>,
+    /// Only dump tags with this file subtype (requires --type)
+    #[arg(long = "subtype", requires = "file_type")]
+    pub file_subtype: Option<u8>,
+    /// Dump all tags of a class, by reference (eg. '808071e8') or name (see `classes`)
+    #[arg(long, value_parser = parse_class)]
+    pub class: Option<u32>,
+    /// Dump a tag and all tags it references
+    #[arg(long, value_parser = parse_tag)]
+    pub recursive: Option<TagHash>,
+    /// Maximum reference depth for --recursive
+    #[arg(long, default_value_t = 4, requires = "recursive")]
+    pub depth: usize,
+    /// Also write a structured dump of every tag
+    #[arg(short, long, value_enum)]
+    pub format: Option<DumpFormat>,
+    #[arg(short, long, default_value = "dumps")]
+    pub output: PathBuf,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("query").required(true).args([
+    "tag", "pkg_id", "tag64", "class", "file_type", "bytes", "float", "hash", "string"
+])))]
+pub struct SearchArgs {
+    #[command(flatten)]
+    pub package: PackageArgs,
+    /// Tag hash as displayed in the viewer (eg. 'EFBE7980')
+    #[arg(long, value_parser = parse_tag)]
+    pub tag: Option<TagHash>,
+    /// Package ID (hex) of a split tag hash
+    #[arg(long, requires = "entry")]
+    pub pkg_id: Option<String>,
+    /// Entry index of a split tag hash
+    #[arg(long, requires = "pkg_id")]
+    pub entry: Option<String>,
+    /// TagHash64 in hex
+    #[arg(long, value_parser = parse_tag64)]
+    pub tag64: Option<u64>,
+    /// Tags of a class, by reference (eg. '808071e8') or name (see `classes`)
+    #[arg(long, value_parser = parse_class)]
+    pub class: Option<u32>,
+    /// Tags with this file type
+    #[arg(long = "type")]
+    pub file_type: Option<u8>,
+    /// Only tags with this file subtype (requires --type)
+    #[arg(long = "subtype", requires = "file_type")]
+    pub file_subtype: Option<u8>,
+    /// Tags containing a byte pattern (eg. 'de ad be ef')
+    #[arg(long)]
+    pub bytes: Option<String>,
+    /// Tags containing an f32 close to this value
+    #[arg(long, allow_hyphen_values = true)]
+    pub float: Option<f32>,
+    /// Maximum difference for --float
+    #[arg(long, default_value_t = 0.0001, requires = "float")]
+    pub tolerance: f32,
+    /// Tags containing a DestinyHash (decimal, or hex prefixed with 0x)
+    #[arg(long, value_parser = search::parse_u32)]
+    pub hash: Option<u32>,
+    /// Tags containing the DestinyHash of a string from the global string tables
+    #[arg(long)]
+    pub string: Option<String>,
+}
+
+impl SearchArgs {
+    fn query(self) -> anyhow::Result<SearchQuery> {
+        Ok(if let Some(tag) = self.tag {
+            SearchQuery::Tag(tag)
+        } else if let (Some(pkg_id), Some(entry)) = (&self.pkg_id, &self.entry) {
+            SearchQuery::Tag(parse_split_tag(pkg_id, entry)?)
+        } else if let Some(tag64) = self.tag64 {
+            SearchQuery::Tag64(tag64)
+        } else if let Some(reference) = self.class {
+            SearchQuery::Class(reference)
+        } else if let Some(file_type) = self.file_type {
+            SearchQuery::FileType {
+                file_type,
+                file_subtype: self.file_subtype,
+            }
+        } else if let Some(bytes) = &self.bytes {
+            SearchQuery::Bytes(search::parse_bytes(bytes)?)
+        } else if let Some(value) = self.float {
+            SearchQuery::Float {
+                value,
+                tolerance: self.tolerance,
+            }
+        } else if let Some(hash) = self.hash {
+            SearchQuery::Hashes(vec![hash])
+        } else if let Some(name) = &self.string {
+            let hashes = search::string_hashes(&load_global_strings()?, name);
+            anyhow::ensure!(!hashes.is_empty(), "No strings matching '{name}'");
+            SearchQuery::Hashes(hashes)
+        } else {
+            anyhow::bail!("No search query was given")
+        })
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Opens the maps in a package in the viewer (Windows only)
+    View(PackageArgs),
+    /// Lists all maps in all packages
+    ListMaps(PackageArgs),
+    /// Writes the raw data of a tag to a file (default: tags/<tag>_<file type>_<file subtype>.bin)
+    DumpTag(TagArgs),
+    /// Writes the fields of a tag, or a heuristic view of unknown classes (default: tags/<tag>.<format>)
+    DumpStructured {
+        #[command(flatten)]
+        tag: TagArgs,
+        #[arg(short, long, value_enum, default_value = "yaml")]
+        format: DumpFormat,
+    },
+    /// Dumps many tags at once to <output>/<package>/<class>/<tag>.bin, with a manifest.csv.
+    /// Dumps every tag in the given package when no selection is given
+    DumpBatch(BatchArgs),
+    /// Exports a static, entity or terrain tag to an OBJ file (default: exports/<tag>.obj)
+    ExportModel(TagArgs),
+    /// Exports a texture to a DDS file (default: exports/<tag>.dds)
+    ExportTexture(TagArgs),
+    /// Writes all global strings to a file, one '<hash>: <string>' per line
+    ExportStrings {
+        #[command(flatten)]
+        package: PackageArgs,
+        #[arg(short, long, default_value = "strings.txt")]
+        output: PathBuf,
+    },
+    /// Disassembles a shader, either a DXBC tag or a shader referenced by a material (Windows only)
+    DisasmShader(TagArgs),
+    /// Parses the TFX bytecode of every material and prints opcode statistics
+    TfxStats(PackageArgs),
+    /// Loads every map in a package without rendering, and reports all tags that fail to load
+    Validate(PackageArgs),
+    /// Lists the registered tag classes, and how many tags of each class there are
+    Classes(PackageArgs),
+    /// Finds tags by hash, class or file type, or tags containing a value
+    Search(SearchArgs),
+    /// Lists the tags that reference a tag, the tags it references and the maps using it.
+    /// The reference index is built on first use and cached in reference_index.bin
+    References {
+        #[command(flatten)]
+        package: PackageArgs,
+        /// Tag hash as displayed in the viewer (eg. 'EFBE7980')
+        #[arg(value_parser = parse_tag)]
+        tag: TagHash,
+        /// Rebuild the index, even if the cached one is up to date
+        #[arg(long)]
+        rebuild: bool,
+    },
+}
+
+impl Command {
+    pub fn package_path(&self) -> &str {
+        match self {
+            Command::View(p)
+            | Command::ListMaps(p)
+            | Command::TfxStats(p)
+            | Command::Validate(p)
+            | Command::Classes(p) => &p.package,
+            Command::DumpTag(t)
+            | Command::ExportModel(t)
+            | Command::ExportTexture(t)
+            | Command::DisasmShader(t) => &t.package.package,
+            Command::DumpStructured { tag, .. } => &tag.package.package,
+            Command::DumpBatch(b) => &b.package.package,
+            Command::Search(s) => &s.package.package,
+            Command::ExportStrings { package, .. } | Command::References { package, .. } => {
+                &package.package
+            }
+        }
+    }
+}
+
+/// Runs a headless command. The package version has to be selected beforehand
+pub fn run(command: Command) -> anyhow::Result<()> {
+    packages::initialize(command.package_path())?;
+
+    match command {
+        Command::View(_) => anyhow::bail!("The viewer can't be run headless"),
+        Command::ListMaps(_) => list_maps(),
+        Command::DumpTag(t) => {
+            let output = match t.output {
+                Some(output) => output,
+                None => export::dump::raw_dump_path(t.tag)?,
+            };
+            let data = package_manager().read_tag(t.tag)?;
+            write_file(&output, &data)?;
+            println!("Wrote {} bytes to {}", data.len(), output.display());
+            Ok(())
+        }
+        Command::DumpStructured { tag: t, format } => {
+            let output = t
+                .output
+                .unwrap_or_else(|| export::dump::structured_dump_path(t.tag, format));
+            export::dump::write_dump(t.tag, format, &output)?;
+            println!("Dumped {} to {}", t.tag, output.display());
+            Ok(())
+        }
+        Command::DumpBatch(b) => dump_batch(b),
+        Command::ExportModel(t) => {
+            let output = t
+                .output
+                .unwrap_or_else(|| PathBuf::from(format!("exports/{}.obj", t.tag)));
+            create_parent_dir(&output)?;
+            export::obj::export_tag(t.tag, &output)?;
+            println!("Exported {} to {}", t.tag, output.display());
+            Ok(())
+        }
+        Command::ExportTexture(t) => {
+            let output = t
+                .output
+                .unwrap_or_else(|| PathBuf::from(format!("exports/{}.dds", t.tag)));
+            let (header, data, mips) = load_texture_data(t.tag)?;
+            let mut out = Vec::new();
+            dds::dump_to_dds(&mut out, &header, &data, mips)?;
+            write_file(&output, &out)?;
+            println!(
+                "Exported {}x{}x{} {:?} texture to {}",
+                header.width,
+                header.height,
+                header.depth,
+                header.format,
+                output.display()
+            );
+            Ok(())
+        }
+        Command::ExportStrings { output, .. } => {
+            let strings = load_global_strings()?;
+            create_parent_dir(&output)?;
+            let mut f = BufWriter::new(File::create(&output)?);
+            for (hash, s) in strings.iter().sorted_by_key(|(h, _)| **h) {
+                writeln!(f, "{hash:08X}: {}", s.replace('\n', "\\n"))?;
+            }
+            println!("Wrote {} strings to {}", strings.len(), output.display());
+            Ok(())
+        }
+        Command::DisasmShader(t) => {
+            let mut data = package_manager().read_tag(t.tag)?;
+            if !data.starts_with(b"DXBC") {
+                data = shader::read_shader_data(t.tag)?;
+            }
+            anyhow::ensure!(data.starts_with(b"DXBC"), "{} is not a shader", t.tag);
+
+            let disassembly = shader::disassemble(&data)?;
+            match t.output {
+                Some(output) => write_file(&output, disassembly.as_bytes())?,
+                None => println!("{disassembly}"),
+            }
+            Ok(())
+        }
+        Command::TfxStats(_) => tfx_stats(),
+        Command::Validate(p) => validate(&p.package),
+        Command::Search(s) => search_tags(s),
+        Command::References { tag, rebuild, .. } => list_references(tag, rebuild),
+        Command::Classes(_) => {
+            for class in classes::classes() {
+                println!(
+                    "{:08x}  {:<16} {:<20} {:<18} {} tags",
+                    class.reference,
+                    class.type_name,
+                    class.name,
+                    class.legacy_name.unwrap_or(""),
+                    package_manager()
+                        .get_all_by_reference(class.reference)
+                        .len()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+fn create_parent_dir(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+
+    Ok(())
+}
+
+fn write_file(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    create_parent_dir(path)?;
+    std::fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn dump_batch(args: BatchArgs) -> anyhow::Result<()> {
+    let selection = if let Some(file_type) = args.file_type {
+        BatchSelection::FileType {
+            file_type,
+            file_subtype: args.file_subtype,
+        }
+    } else if let Some(reference) = args.class {
+        BatchSelection::Reference(reference)
+    } else if let Some(root) = args.recursive {
+        BatchSelection::Recursive {
+            root,
+            max_depth: args.depth,
+        }
+    } else {
+        let package = version::current()
+            .package_version()
+            .open(&args.package.package)
+            .context("Failed to open package")?;
+        BatchSelection::Package(package.pkg_id())
+    };
+
+    let options = BatchOptions {
+        selection,
+        output: args.output,
+        structured: args.format,
+    };
+    let progress = BatchProgress::default();
+    let summary = std::thread::scope(|s| {
+        let handle = s.spawn(|| batch::dump_batch(&options, &progress));
+        while !handle.is_finished() {
+            std::thread::sleep(Duration::from_millis(500));
+            eprint!(
+                "\r{}/{} tags ({} failed)",
+                progress.done.load(Ordering::Relaxed),
+                progress.total.load(Ordering::Relaxed),
+                progress.failed.load(Ordering::Relaxed)
+            );
+        }
+        eprintln!();
+
+        handle.join().expect("Batch dump panicked")
+    })?;
+
+    println!(
+        "Dumped {} tags ({} failed), manifest written to {}",
+        summary.dumped,
+        summary.failed,
+        summary.manifest.display()
+    );
+
+    Ok(())
+}
+
+fn search_tags(args: SearchArgs) -> anyhow::Result<()> {
+    let query = args.query()?;
+    let progress = SearchProgress::default();
+    let results = std::thread::scope(|s| {
+        let handle = s.spawn(|| search::search(&query, &progress));
+        while !handle.is_finished() {
+            std::thread::sleep(Duration::from_millis(500));
+            if progress.total.load(Ordering::Relaxed) != 0 {
+                eprint!(
+                    "\rSearched {}/{} tags",
+                    progress.done.load(Ordering::Relaxed),
+                    progress.total.load(Ordering::Relaxed)
+                );
+            }
+        }
+        if query.is_content() {
+            eprintln!();
+        }
+
+        handle.join().expect("Search panicked")
+    })?;
+
+    for r in &results {
+        let (reference, class) = match package_manager().get_entry(r.tag) {
+            Ok(e) => (
+                e.reference,
+                classes::class(e.reference).map_or("", |c| c.name),
+            ),
+            Err(_) => (0, ""),
+        };
+        print!("{} {reference:08x} {class}", r.tag);
+        if !r.offsets.is_empty() {
+            print!(
+                " ({} matches) {}",
+                r.match_count,
+                r.offsets.iter().map(|o| format!("0x{o:x}")).join(" ")
+            );
+        }
+        println!();
+    }
+    println!("{} tags found", results.len());
+
+    Ok(())
+}
+
+fn list_references(tag: TagHash, rebuild: bool) -> anyhow::Result<()> {
+    let progress = IndexProgress::default();
+    let index = std::thread::scope(|s| {
+        let handle = s.spawn(|| {
+            if rebuild {
+                references::rebuild(&progress)
+            } else {
+                references::load_or_build(&progress)
+            }
+        });
+        while !handle.is_finished() {
+            std::thread::sleep(Duration::from_millis(500));
+            if progress.total.load(Ordering::Relaxed) != 0 {
+                eprint!(
+                    "\rIndexing {}/{} tags",
+                    progress.done.load(Ordering::Relaxed),
+                    progress.total.load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        handle.join().expect("Reference indexer panicked")
+    })?;
+
+    let print_tags = |label: &str, tags: &[TagHash]| {
+        println!("{label} ({}):", tags.len());
+        for t in tags {
+            let class = classes::class_of(*t).map_or("", |c| c.name);
+            let reference = package_manager()
+                .get_entry(*t)
+                .map(|e| e.reference)
+                .unwrap_or_default();
+            println!("  {t} {reference:08x} {class}");
+        }
+    };
+
+    println!();
+    print_tags("Referenced by", index.referenced_by(tag));
+    print_tags("References", index.references(tag));
+    print_tags("Used by maps", &index.maps_using(tag));
+
+    Ok(())
+}
+
+fn list_maps() -> anyhow::Result<()> {
+    let strings = load_global_strings()?;
+    for (tag, _) in package_manager().get_all_by_reference(0x80807dae) {
+        match package_manager().read_tag_struct::<Unk80807dae>(tag) {
+            Ok(map) => {
+                let name = strings
+                    .get(&map.map_name.0)
+                    .cloned()
+                    .unwrap_or(format!("[MissingString_{:08x}]", map.map_name.0));
+                println!("{tag} {:04x} {name}", tag.pkg_id());
+            }
+            Err(e) => error!("Failed to read map {tag}: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn opcode_name(op: &TfxBytecodeOp) -> String {
+    let name = format!("{op:?}");
+    name.split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn tfx_stats() -> anyhow::Result<()> {
+    let mut material_count = 0;
+    let mut programs = 0;
+    let mut failed = 0;
+    let mut opcodes: BTreeMap<String, usize> = BTreeMap::new();
+    let mut externs: BTreeMap<String, usize> = BTreeMap::new();
+
+    for (tag, _) in package_manager().get_all_by_reference(0x808071e8) {
+        let mat: Unk808071e8 = match package_manager().read_tag_struct(tag) {
+            Ok(m) => m,
+            Err(e) => {
+                error!("Failed to read material {tag}: {e}");
+                continue;
+            }
+        };
+        material_count += 1;
+
+        for (stage, bytecode) in [("VS", &mat.vs_bytecode), ("PS", &mat.ps_bytecode)] {
+            if bytecode.is_empty() {
+                continue;
+            }
+
+            programs += 1;
+            match TfxBytecodeOp::parse_all(bytecode, binrw::Endian::Little) {
+                Ok(ops) => {
+                    for op in &ops {
+                        *opcodes.entry(opcode_name(op)).or_default() += 1;
+                        if let TfxBytecodeOp::LoadExtern { extern_, .. } = op {
+                            *externs.entry(format!("{extern_:?}")).or_default() += 1;
+                        }
+                    }
+                }
+                Err(e) => {
+                    failed += 1;
+                    warn!("Failed to parse {stage} bytecode for material {tag}: {e}");
+                }
+            }
+        }
+    }
+
+    println!("{material_count} materials, {programs} bytecode programs, {failed} failed to parse");
+    println!("\nOpcodes:");
+    for (name, count) in opcodes
+        .iter()
+        .sorted_by_key(|(_, c)| std::cmp::Reverse(**c))
+    {
+        println!("{count:>10} {name}");
+    }
+    println!("\nExterns:");
+    for (name, count) in externs
+        .iter()
+        .sorted_by_key(|(_, c)| std::cmp::Reverse(**c))
+    {
+        println!("{count:>10} {name}");
+    }
+
+    Ok(())
+}
+
+/// Loads all tags used by maps, without creating any GPU resources
+#[derive(Default)]
+struct Validator {
+    checked: IntSet<TagHash>,
+    checked_count: usize,
+    failures: Vec<(TagHash, &'static str, String)>,
+}
+
+impl Validator {
+    /// Runs `f` once for every tag, recording the error if it fails
+    fn check(
+        &mut self,
+        tag: TagHash,
+        kind: &'static str,
+        f: impl FnOnce(&mut Self) -> anyhow::Result<()>,
+    ) {
+        if !tag.is_valid() || !self.checked.insert(tag) {
+            return;
+        }
+
+        self.checked_count += 1;
+        if let Err(e) = f(self) {
+            error!("{kind} {tag}: {e:#}");
+            self.failures.push((tag, kind, format!("{e:#}")));
+        }
+    }
+
+    fn map(&mut self, tag: TagHash) {
+        self.check(tag, "Map", |v| {
+            let map: Unk80807dae = package_manager().read_tag_struct(tag)?;
+            for res in &map.child_map.map_resources {
+                let resource: Unk80808a54 = if res.is_hash32 != 0 {
+                    package_manager().read_tag_struct(res.hash32)?
+                } else {
+                    package_manager().read_tag64_struct(res.hash64.0)?
+                };
+
+                for table in &resource.data_tables {
+                    let entries = table.load()?.data_entries.to_vec()?;
+                    v.data_table(table.tag(), &entries)?;
+                }
+            }
+
+            Ok(())
+        });
+    }
+
+    fn data_table(&mut self, tag: TagHash, entries: &[Unk808099d8]) -> anyhow::Result<()> {
+        let table_data = package_manager().read_tag(tag)?;
+        let mut cur = Cursor::new(&table_data);
+
+        for data in entries {
+            self.entity(data.entity);
+
+            if !data.data_resource.is_valid {
+                continue;
+            }
+
+            match data.data_resource.resource_type {
+                // Placement group
+                0x808071b3 => {
+                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                    let preheader_tag: TagHash = cur.read_le()?;
+                    self.check(preheader_tag, "Placement group", |v| {
+                        let preheader: Unk80806ef4 =
+                            package_manager().read_tag_struct(preheader_tag)?;
+                        for s in preheader.placement_group.statics.iter() {
+                            v.static_model(*s);
+                        }
+                        Ok(())
+                    });
+                }
+                // Terrain
+                0x8080714b => {
+                    cur.seek(SeekFrom::Start(data.data_resource.offset))?;
+                    let terrain_resource: Unk8080714b = cur.read_le()?;
+                    self.terrain(terrain_resource.terrain);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn static_model(&mut self, tag: TagHash) {
+        self.check(tag, "Static", |v| {
+            let model: Unk808071a7 = package_manager().read_tag_struct(tag)?;
+            mesh::load_static(&model)?;
+            for m in model.materials.iter() {
+                v.material(*m);
+            }
+            Ok(())
+        });
+    }
+
+    fn terrain(&mut self, tag: TagHash) {
+        self.check(tag, "Terrain", |v| {
+            let terrain: Unk8080714f = package_manager().read_tag_struct(tag)?;
+            mesh::load_terrain(&terrain)?;
+            for p in terrain.mesh_parts.iter() {
+                v.material(p.material);
+            }
+            Ok(())
+        });
+    }
+
+    fn entity(&mut self, tag: TagHash) {
+        self.check(tag, "Entity", |v| {
+            if let Some(model) = read_entity_model(tag)? {
+                mesh::load_entity_model(&model)?;
+                for p in model.meshes.iter().flat_map(|m| m.parts.iter()) {
+                    v.material(p.material);
+                }
+            }
+            Ok(())
+        });
+    }
+
+    fn material(&mut self, tag: TagHash) {
+        self.check(tag, "Material", |v| {
+            let mat: Unk808071e8 = package_manager().read_tag_struct(tag)?;
+            for t in mat.vs_textures.iter().chain(mat.ps_textures.iter()) {
+                v.check(t.texture, "Texture", |_| {
+                    load_texture_data(t.texture).map(|_| ())
+                });
+            }
+
+            for shader_tag in [mat.vertex_shader, mat.pixel_shader] {
+                v.check(shader_tag, "Shader", |_| {
+                    let data = shader::read_shader_data(shader_tag)?;
+                    Cursor::new(&data).read_le::<DxbcHeader>()?;
+                    Ok(())
+                });
+            }
+
+            TfxBytecodeOp::parse_all(&mat.vs_bytecode, binrw::Endian::Little)
+                .context("Failed to parse VS bytecode")?;
+            TfxBytecodeOp::parse_all(&mat.ps_bytecode, binrw::Endian::Little)
+                .context("Failed to parse PS bytecode")?;
+            Ok(())
+        });
+    }
+}
+
+fn validate(pkg_path: &str) -> anyhow::Result<()> {
+    let package = version::current()
+        .package_version()
+        .open(pkg_path)
+        .context("Failed to open package")?;
+
+    let mut validator = Validator::default();
+    for (index, _) in package.get_all_by_reference(0x80807dae) {
+        let tag = TagHash::new(package.pkg_id(), index as _);
+        info!("Validating map {tag}");
+        validator.map(tag);
+    }
+
+    println!(
+        "Checked {} tags, {} failed",
+        validator.checked_count,
+        validator.failures.len()
+    );
+    for (tag, kind, e) in &validator.failures {
+        println!("{kind} {tag}: {e}");
+    }
+
+    anyhow::ensure!(
+        validator.failures.is_empty(),
+        "{} tags failed to load",
+        validator.failures.len()
+    );
+
+    Ok(())
+}
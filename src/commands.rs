@@ -0,0 +1,504 @@
+//! Console commands, and the argument parsers they share with the CLI. The console is part of the
+//! viewer, so only the parsers are built on other platforms
+
+use destiny_pkg::TagHash;
+#[cfg(windows)]
+use glam::Vec3;
+
+#[cfg(windows)]
+use crate::camera::FpsCamera;
+use crate::classes;
+#[cfg(windows)]
+use crate::config;
+#[cfg(windows)]
+use crate::cvars::{self, Cvars};
+#[cfg(windows)]
+use crate::export;
+#[cfg(windows)]
+use crate::map::MapDataList;
+#[cfg(windows)]
+use crate::overlays::console;
+#[cfg(windows)]
+use crate::overlays::tag_dump::dump_tag;
+#[cfg(windows)]
+use crate::picking::{PickKind, PickResult, SelectedObject};
+#[cfg(windows)]
+use crate::resources::Resources;
+
+#[cfg(windows)]
+pub struct CommandContext<'a> {
+    pub resources: &'a Resources,
+    pub registry: &'a CommandRegistry,
+}
+
+#[cfg(windows)]
+pub type CommandHandler = fn(&mut CommandContext, &[&str]) -> anyhow::Result<()>;
+
+#[cfg(windows)]
+pub struct Command {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub min_args: usize,
+    pub handler: CommandHandler,
+    /// Completion candidates for the first argument
+    pub complete: Option<fn(&Resources) -> Vec<String>>,
+}
+
+#[cfg(windows)]
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+#[cfg(windows)]
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = CommandRegistry { commands: vec![] };
+        registry.register_builtins();
+        registry
+    }
+}
+
+#[cfg(windows)]
+impl CommandRegistry {
+    pub fn register(&mut self, command: Command) {
+        if self.get(command.name).is_some() {
+            warn!("Command '{}' is already registered", command.name);
+            return;
+        }
+
+        self.commands.push(command);
+        self.commands.sort_by_key(|c| c.name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Parses and runs a command line, logging any errors
+    pub fn execute(&self, resources: &Resources, line: &str) {
+        let args = split_args(line);
+        let Some((name, args)) = args.split_first() else {
+            return;
+        };
+
+        let Some(command) = self.get(name) else {
+            error!("Unknown command '{name}', type 'help' for a list of commands");
+            return;
+        };
+
+        if args.len() < command.min_args {
+            error!("Usage: {} {}", command.name, command.usage);
+            return;
+        }
+
+        let args: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+        let mut ctx = CommandContext {
+            resources,
+            registry: self,
+        };
+        if let Err(e) = (command.handler)(&mut ctx, &args) {
+            error!("{}: {e}", command.name);
+        }
+    }
+
+    /// Returns the completion candidates for the last word of `line`.
+    /// Candidates are full replacements for `line`.
+    pub fn complete(&self, resources: &Resources, line: &str) -> Vec<String> {
+        let args = split_args(line);
+        let ends_with_space = line.ends_with(char::is_whitespace);
+
+        match args.as_slice() {
+            [] => vec![],
+            [name] if !ends_with_space => self
+                .commands
+                .iter()
+                .filter(|c| c.name.starts_with(&name.to_lowercase()))
+                .map(|c| format!("{} ", c.name))
+                .collect(),
+            [name, rest @ ..] if rest.len() <= 1 => {
+                let Some(complete) = self.get(name).and_then(|c| c.complete) else {
+                    return vec![];
+                };
+
+                let prefix = if ends_with_space {
+                    ""
+                } else {
+                    rest.first().map(|s| s.as_str()).unwrap_or_default()
+                };
+
+                complete(resources)
+                    .into_iter()
+                    .filter(|c| c.starts_with(prefix))
+                    .map(|c| format!("{name} {c}"))
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn register_builtins(&mut self) {
+        self.register(Command {
+            name: "help",
+            usage: "[command]",
+            help: "Lists all commands, or shows the help text for a single command",
+            min_args: 0,
+            handler: cmd_help,
+            complete: None,
+        });
+        self.register(Command {
+            name: "clear",
+            usage: "",
+            help: "Clears the console log",
+            min_args: 0,
+            handler: |_, _| {
+                console::clear_log();
+                Ok(())
+            },
+            complete: None,
+        });
+        self.register(Command {
+            name: "goto",
+            usage: "<x> <y> <z>",
+            help: "Moves the camera to the given position",
+            min_args: 3,
+            handler: cmd_goto,
+            complete: None,
+        });
+        self.register(Command {
+            name: "speed",
+            usage: "<multiplier>",
+            help: "Sets the camera speed multiplier",
+            min_args: 1,
+            handler: |ctx, args| {
+                let speed: f32 = args[0].parse()?;
+                ctx.resources.get_mut::<FpsCamera>().unwrap().speed_mul = speed;
+                Ok(())
+            },
+            complete: None,
+        });
+        self.register(Command {
+            name: "map",
+            usage: "<index|name>",
+            help: "Switches to another map, or lists all maps when called without arguments",
+            min_args: 0,
+            handler: cmd_map,
+            complete: None,
+        });
+        self.register(Command {
+            name: "dump",
+            usage: "<tag>",
+            help: "Dumps the raw data of a tag to the tags/ directory",
+            min_args: 1,
+            handler: |_, args| {
+                let tag = parse_tag(args[0])?;
+                let msg = dump_tag(tag).map_err(|e| anyhow::anyhow!(e))?;
+                info!("{tag}: {msg}");
+                Ok(())
+            },
+            complete: None,
+        });
+        self.register(Command {
+            name: "export",
+            usage: "<tag>",
+            help: "Exports a static, entity or terrain tag to exports/<tag>.obj",
+            min_args: 1,
+            handler: |_, args| {
+                let tag = parse_tag(args[0])?;
+                export::obj::export_tag(tag, format!("exports/{tag}.obj"))?;
+                info!("Exported {tag} to exports/{tag}.obj");
+                Ok(())
+            },
+            complete: None,
+        });
+        self.register(Command {
+            name: "find",
+            usage: "<tag>",
+            help: "Finds all instances of a static or entity in the current map, then selects and moves to the first one",
+            min_args: 1,
+            handler: cmd_find,
+            complete: None,
+        });
+        self.register(Command {
+            name: "set",
+            usage: "<cvar> [value]",
+            help: "Sets a cvar, or prints its value when no value is given",
+            min_args: 1,
+            handler: cmd_set,
+            complete: Some(|_| Cvars::LIST.iter().map(|(n, _)| n.to_string()).collect()),
+        });
+        self.register(Command {
+            name: "cvars",
+            usage: "",
+            help: "Lists all cvars with their current values",
+            min_args: 0,
+            handler: |_, _| {
+                let cvars = cvars::get();
+                for (name, help) in Cvars::LIST {
+                    info!("{name} = {} -{help}", cvars.get(name).unwrap_or_default());
+                }
+                Ok(())
+            },
+            complete: None,
+        });
+    }
+}
+
+/// Splits a command line on whitespace, keeping quoted strings together
+pub fn split_args(line: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_arg = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+
+    if has_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+/// Parses a tag hash as it is displayed (big endian hex, eg. 'EFBE7980')
+pub fn parse_tag(s: &str) -> anyhow::Result<TagHash> {
+    let s = s.trim().trim_start_matches("0x");
+    let v = u32::from_str_radix(s, 16).map_err(|_| anyhow::anyhow!("Invalid tag '{s}'"))?;
+    Ok(TagHash(u32::from_be(v)))
+}
+
+/// Parses a TagHash64 as it is displayed (hex, eg. '3A7B5D2C6E8F9A1B')
+pub fn parse_tag64(s: &str) -> anyhow::Result<u64> {
+    let s = s.trim().trim_start_matches("0x");
+    u64::from_str_radix(s, 16).map_err(|_| anyhow::anyhow!("Invalid tag64 '{s}'"))
+}
+
+/// Parses a tag class by reference (hex, eg. '808071e8') or by name, see [classes::find_class]
+pub fn parse_class(s: &str) -> anyhow::Result<u32> {
+    if let Some(class) = classes::find_class(s) {
+        return Ok(class.reference);
+    }
+
+    u32::from_str_radix(s.trim().trim_start_matches("0x"), 16)
+        .map_err(|_| anyhow::anyhow!("'{s}' is not a known class or reference"))
+}
+
+/// Parses a tag from its package ID (hex) and entry index (decimal)
+pub fn parse_split_tag(package: &str, entry: &str) -> anyhow::Result<TagHash> {
+    let package = package.trim().trim_start_matches("0x");
+    let pkg = u16::from_str_radix(package, 16)
+        .map_err(|_| anyhow::anyhow!("Invalid package ID '{package}'"))?;
+    let entry = entry
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid entry index '{entry}'"))?;
+    Ok(TagHash::new(pkg, entry))
+}
+
+#[cfg(windows)]
+fn cmd_help(ctx: &mut CommandContext, args: &[&str]) -> anyhow::Result<()> {
+    if let Some(name) = args.first() {
+        let Some(c) = ctx.registry.get(name) else {
+            anyhow::bail!("Unknown command '{name}'");
+        };
+
+        info!("{} {} - {}", c.name, c.usage, c.help);
+    } else {
+        for c in ctx.registry.commands() {
+            info!("{} {} - {}", c.name, c.usage, c.help);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn cmd_goto(ctx: &mut CommandContext, args: &[&str]) -> anyhow::Result<()> {
+    let position = Vec3::new(args[0].parse()?, args[1].parse()?, args[2].parse()?);
+    ctx.resources.get_mut::<FpsCamera>().unwrap().position = position;
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn cmd_map(ctx: &mut CommandContext, args: &[&str]) -> anyhow::Result<()> {
+    let mut maps = ctx.resources.get_mut::<MapDataList>().unwrap();
+    if args.is_empty() {
+        for (i, m) in maps.maps.iter().enumerate() {
+            info!("{i}: {} ({})", m.name, m.hash);
+        }
+        return Ok(());
+    }
+
+    let query = args.join(" ");
+    let index = match query.parse::<usize>() {
+        Ok(i) => i,
+        Err(_) => {
+            let query = query.to_lowercase();
+            maps.maps
+                .iter()
+                .position(|m| m.name.to_lowercase().contains(&query))
+                .ok_or_else(|| anyhow::anyhow!("No map matching '{query}'"))?
+        }
+    };
+
+    anyhow::ensure!(
+        index < maps.maps.len(),
+        "Map index {index} is out of bounds ({} maps)",
+        maps.maps.len()
+    );
+
+    maps.current_map = index;
+    info!("Switched to map {index} '{}'", maps.maps[index].name);
+    ctx.resources.get_mut::<SelectedObject>().unwrap().0 = None;
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn cmd_find(ctx: &mut CommandContext, args: &[&str]) -> anyhow::Result<()> {
+    let tag = parse_tag(args[0])?;
+    let maps = ctx.resources.get::<MapDataList>().unwrap();
+    let Some(map) = maps.current_map() else {
+        anyhow::bail!("No map loaded");
+    };
+
+    let mut results = vec![];
+    for group in &map.placement_groups {
+        for instance in &group.instances {
+            if group.statics.get(instance.static_index as usize) != Some(&tag) {
+                continue;
+            }
+
+            let start = instance.instance_start as usize;
+            let end = start + instance.instance_count as usize;
+            for (i, t) in group.transforms.range(start..end).enumerate() {
+                let t = t?;
+                results.push(PickResult {
+                    kind: PickKind::Static,
+                    tag,
+                    instance_index: start + i,
+                    placement_group: Some(group.tag()),
+                    position: t.transform().w_axis.truncate(),
+                    distance: 0.0,
+                });
+            }
+        }
+    }
+
+    for (i, (rp, _)) in map.resource_points.iter().enumerate() {
+        if rp.entity == tag {
+            results.push(PickResult {
+                kind: PickKind::ResourcePoint,
+                tag,
+                instance_index: i,
+                placement_group: None,
+                position: rp.translation.truncate(),
+                distance: 0.0,
+            });
+        }
+    }
+
+    for r in &results {
+        info!(
+            "{:?} {} at {:.2} {:.2} {:.2}",
+            r.kind, r.instance_index, r.position.x, r.position.y, r.position.z
+        );
+    }
+    info!("Found {} instances of {tag}", results.len());
+
+    if let Some(first) = results.into_iter().next() {
+        let mut camera = ctx.resources.get_mut::<FpsCamera>().unwrap();
+        camera.position = first.position - camera.front * 10.0;
+        ctx.resources.get_mut::<SelectedObject>().unwrap().0 = Some(first);
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn cmd_set(_ctx: &mut CommandContext, args: &[&str]) -> anyhow::Result<()> {
+    let name = args[0];
+    if let Some(value) = args.get(1) {
+        config::with_mut(|c| c.cvars.set(name, value))?;
+        cvars::clear_override(name);
+        config::persist();
+    }
+
+    match cvars::get().get(name) {
+        Some(value) => info!("{name} = {value}"),
+        None => anyhow::bail!("Unknown cvar '{name}'"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_args_quotes() {
+        assert_eq!(split_args("  set  r_foo 1 "), ["set", "r_foo", "1"]);
+        assert_eq!(
+            split_args(r#"echo "hello world" "" x"#),
+            ["echo", "hello world", "", "x"]
+        );
+        assert_eq!(split_args(r#"a"b c"d"#), ["ab cd"]);
+        assert!(split_args("   ").is_empty());
+    }
+
+    #[test]
+    fn tags() {
+        assert_eq!(parse_tag("EFBE7980").unwrap().0, 0x8079beef);
+        assert_eq!(parse_tag(" 0xefbe7980 \n").unwrap().0, 0x8079beef);
+        assert!(parse_tag("EFBE798G").is_err());
+        assert!(parse_tag("").is_err());
+
+        assert_eq!(
+            parse_tag64("0x3A7B5D2C6E8F9A1B ").unwrap(),
+            0x3a7b5d2c6e8f9a1b
+        );
+        assert!(parse_tag64("3A7B5D2C6E8F9A1B00").is_err());
+
+        assert_eq!(
+            parse_split_tag(" 0x1234", "5 ").unwrap().0,
+            TagHash::new(0x1234, 5).0
+        );
+        assert!(parse_split_tag("1234", "0x5").is_err());
+        assert!(parse_split_tag("12345", "5").is_err());
+    }
+
+    #[test]
+    fn class_names() {
+        assert_eq!(parse_class("808071e8").unwrap(), 0x808071e8);
+        assert_eq!(parse_class(" Material ").unwrap(), 0x808071e8);
+        assert_eq!(parse_class("unk808071e8").unwrap(), 0x808071e8);
+        // Unregistered references are still accepted
+        assert_eq!(parse_class("0x80801234").unwrap(), 0x80801234);
+        assert!(parse_class("not a class").is_err());
+    }
+}
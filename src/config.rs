@@ -2,14 +2,22 @@ use lazy_static::lazy_static;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
+use crate::cvars::{self, Cvars};
+
 lazy_static! {
     pub static ref CONFIGURATION: RwLock<Config> = RwLock::new(Config::default());
 }
 
 pub fn persist() {
+    let config = CONFIGURATION.read();
+    let mut value = serde_yaml::to_value(&*config).expect("Fatal: failed to write config");
+    value["cvars"] = serde_yaml::to_value(cvars::without_overrides(&config.cvars))
+        .expect("Fatal: failed to write config");
+    drop(config);
+
     if let Err(e) = std::fs::write(
         "config.yml",
-        serde_yaml::to_string(&*CONFIGURATION.read()).expect("Fatal: failed to write config"),
+        serde_yaml::to_string(&value).expect("Fatal: failed to write config"),
     ) {
         warn!("Failed to write config: {e}");
     }
@@ -39,6 +47,12 @@ macro_rules! config {
 #[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     pub window: WindowConfig,
+    #[serde(default)]
+    pub cvars: Cvars,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub loader: LoaderConfig,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -61,3 +75,42 @@ impl Default for WindowConfig {
         }
     }
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Write the log to alkahest.log, next to config.yml
+    pub file: bool,
+    /// Size at which the log file is rotated
+    pub max_size_mb: u64,
+    /// Number of rotated log files to keep (alkahest.1.log, alkahest.2.log, ...)
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            file: false,
+            max_size_mb: 8,
+            max_files: 3,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoaderConfig {
+    /// Number of threads loading textures
+    pub texture_workers: usize,
+    /// Number of threads loading vertex and index buffers
+    pub buffer_workers: usize,
+}
+
+impl Default for LoaderConfig {
+    fn default() -> Self {
+        LoaderConfig {
+            texture_workers: 2,
+            buffer_workers: 1,
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Console variables. These are stored in the config, and can be changed from the console (`set <cvar> <value>`) or the command line (`--set <cvar>=<value>`)
+
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+pub trait CvarValue: Sized {
+    fn parse_cvar(s: &str) -> anyhow::Result<Self>;
+    fn to_cvar_string(&self) -> String;
+}
+
+impl CvarValue for bool {
+    fn parse_cvar(s: &str) -> anyhow::Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "1" | "true" | "on" | "yes" => true,
+            "0" | "false" | "off" | "no" => false,
+            _ => anyhow::bail!("Expected a boolean, got '{s}'"),
+        })
+    }
+
+    fn to_cvar_string(&self) -> String {
+        if *self { "1" } else { "0" }.to_string()
+    }
+}
+
+macro_rules! impl_cvar_value {
+    ($($t:ty),*) => {
+        $(
+            impl CvarValue for $t {
+                fn parse_cvar(s: &str) -> anyhow::Result<Self> {
+                    s.parse::<$t>().map_err(|e| anyhow::anyhow!("Invalid value '{s}': {e}"))
+                }
+
+                fn to_cvar_string(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_cvar_value!(usize, f32);
+
+macro_rules! cvars {
+    ($($(#[doc = $help:literal])+ $name:ident: $t:ty = $default:expr,)*) => {
+        #[derive(Serialize, Deserialize, Clone)]
+        #[serde(default)]
+        pub struct Cvars {
+            $(
+                $(#[doc = $help])+
+                pub $name: $t,
+            )*
+        }
+
+        impl Default for Cvars {
+            fn default() -> Self {
+                Self {
+                    $($name: $default,)*
+                }
+            }
+        }
+
+        impl Cvars {
+            /// Names and help text of all cvars
+            pub const LIST: &'static [(&'static str, &'static str)] = &[
+                $((stringify!($name), concat!($($help),+)),)*
+            ];
+
+            pub fn get(&self, name: &str) -> Option<String> {
+                match name {
+                    $(stringify!($name) => Some(self.$name.to_cvar_string()),)*
+                    _ => None,
+                }
+            }
+
+            pub fn set(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+                match name {
+                    $(stringify!($name) => self.$name = CvarValue::parse_cvar(value)?,)*
+                    _ => anyhow::bail!("Unknown cvar '{name}'"),
+                }
+
+                Ok(())
+            }
+        }
+    };
+}
+
+cvars! {
+    /// Compositor output, see COMPOSITOR_MODES
+    composition_mode: usize = 0,
+    /// Render statics
+    renderlayer_statics: bool = true,
+    /// Render transparent/overlay statics
+    renderlayer_statics_transparent: bool = true,
+    /// Render terrain
+    renderlayer_terrain: bool = true,
+    /// Render entities
+    renderlayer_entities: bool = true,
+    /// Render point lights
+    render_lights: bool = false,
+    /// Enable color blending
+    alpha_blending: bool = true,
+    /// 0 = default, 1 = blend, 2 = additive
+    blend_override: usize = 0,
+    /// Evaluate TFX bytecode (WIP)
+    evaluate_bytecode: bool = false,
+    /// Show map resource icons
+    show_map_resources: bool = false,
+    /// Show labels next to map resource icons
+    show_map_resource_label: bool = true,
+    /// Maximum distance at which map resources are shown
+    map_resource_distance: f32 = 2000.0,
+    /// Memory budget for textures and buffers in megabytes, least recently drawn resources are evicted when it's exceeded. 0 = unlimited
+    memory_budget_mb: usize = 0,
+}
+
+/// Returns a copy of the current cvars
+pub fn get() -> Cvars {
+    config::with(|c| c.cvars.clone())
+}
+
+lazy_static! {
+    /// Cvars set with `--set`, as (persisted value, override value). Overrides only apply to the
+    /// current session, so they're left out when the config is saved
+    static ref OVERRIDES: RwLock<HashMap<String, (String, String)>> = RwLock::new(HashMap::new());
+}
+
+/// `cvars` with the command line overrides that are still in effect replaced by their persisted values
+pub fn without_overrides(cvars: &Cvars) -> Cvars {
+    let mut cvars = cvars.clone();
+    for (name, (persisted, value)) in OVERRIDES.read().iter() {
+        // Cvars changed since (eg. from the settings overlays) keep their new value
+        if cvars.get(name).as_ref() == Some(value) {
+            cvars.set(name, persisted).ok();
+        }
+    }
+
+    cvars
+}
+
+/// Stops treating `name` as a command line override, so its current value gets saved
+pub fn clear_override(name: &str) {
+    OVERRIDES.write().remove(name);
+}
+
+/// Applies and removes all `--set <cvar>=<value>` arguments, returning the remaining arguments.
+/// The values are not saved to the config, see [without_overrides]
+pub fn apply_command_line(args: Vec<String>) -> Vec<String> {
+    let mut remaining = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg != "--set" {
+            remaining.push(arg);
+            continue;
+        }
+
+        let Some(assignment) = args.next() else {
+            error!("--set requires an argument in the form <cvar>=<value>");
+            break;
+        };
+
+        let Some((name, value)) = assignment.split_once('=') else {
+            error!("Invalid cvar assignment '{assignment}', expected <cvar>=<value>");
+            continue;
+        };
+
+        let persisted = get().get(name);
+        if let Err(e) = config::with_mut(|c| c.cvars.set(name, value)) {
+            error!("Failed to set cvar '{name}': {e}");
+            continue;
+        }
+
+        if let (Some(persisted), Some(value)) = (persisted, get().get(name)) {
+            OVERRIDES
+                .write()
+                .entry(name.to_string())
+                .or_insert_with(|| (persisted, String::new()))
+                .1 = value;
+        }
+    }
+
+    remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_overrides_are_not_persisted() {
+        config::with_mut(|c| c.cvars = Cvars::default());
+        let args = [
+            "alkahest",
+            "--set",
+            "render_lights=1",
+            "--set",
+            "blend_override=2",
+            "view",
+        ];
+        let remaining = apply_command_line(args.iter().map(|s| s.to_string()).collect());
+        assert_eq!(remaining, ["alkahest", "view"]);
+
+        let current = get();
+        assert!(current.render_lights);
+        assert_eq!(current.blend_override, 2);
+
+        // Changed at runtime, so the new value is saved
+        config::with_mut(|c| c.cvars.blend_override = 1);
+
+        let saved = without_overrides(&get());
+        assert!(!saved.render_lights);
+        assert_eq!(saved.blend_override, 1);
+
+        config::with_mut(|c| c.cvars.set("render_lights", "1")).unwrap();
+        clear_override("render_lights");
+        assert!(without_overrides(&get()).render_lights);
+    }
+}
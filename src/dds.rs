@@ -1,28 +1,59 @@
-// use crate::texture::TextureHeader;
-// use ddsfile::{AlphaMode, D3D10ResourceDimension};
-// use std::io::{Seek, Write};
-// use std::mem::transmute;
+use crate::texture::TextureHeader;
+use ddsfile::{AlphaMode, D3D10ResourceDimension};
+use std::io::Write;
+use std::mem::transmute;
 
-// pub fn dump_to_dds<W: Write + Seek>(out: &mut W, tex: &TextureHeader, data: &[u8]) {
-//     let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
-//         height: tex.height as u32,
-//         width: tex.width as u32,
-//         depth: Some(tex.depth as u32),
-//         format: unsafe { transmute(tex.format) },
-//         mipmap_levels: None,
-//         array_layers: Some(tex.array_size as _),
-//         caps2: None,
-//         is_cubemap: (tex.array_size % 6) == 0,
-//         resource_dimension: if tex.depth == 1 {
-//             D3D10ResourceDimension::Texture2D
-//         } else {
-//             D3D10ResourceDimension::Texture3D
-//         },
-//         alpha_mode: AlphaMode::Straight,
-//     })
-//     .unwrap();
+/// Writes texture data as returned by [crate::texture::load_texture_data] to a DDS file
+pub fn dump_to_dds<W: Write>(
+    out: &mut W,
+    tex: &TextureHeader,
+    data: &[u8],
+    mips: usize,
+) -> anyhow::Result<()> {
+    let is_cubemap = tex.array_size > 1 && (tex.array_size % 6) == 0;
+    let (_, slice_pitch) = tex
+        .format
+        .calculate_pitch(tex.width as usize, tex.height as usize);
 
-//     dds.data = data.to_vec();
+    // Only 2D textures are loaded with mips
+    let (mips, required_size) = if tex.depth > 1 {
+        (1, slice_pitch * tex.depth as usize)
+    } else if tex.array_size > 1 {
+        (1, slice_pitch * tex.array_size as usize)
+    } else {
+        let size = (0..mips)
+            .map(|i| {
+                tex.format
+                    .calculate_pitch((tex.width >> i) as usize, (tex.height >> i) as usize)
+                    .1
+            })
+            .sum();
+        (mips, size)
+    };
 
-//     dds.write(out).unwrap();
-// }
+    let mut dds = ddsfile::Dds::new_dxgi(ddsfile::NewDxgiParams {
+        height: tex.height as u32,
+        width: tex.width as u32,
+        depth: (tex.depth > 1).then_some(tex.depth as u32),
+        format: unsafe { transmute(tex.format) },
+        mipmap_levels: Some(mips as u32),
+        array_layers: Some(if is_cubemap {
+            tex.array_size as u32 / 6
+        } else {
+            tex.array_size as u32
+        }),
+        caps2: None,
+        is_cubemap,
+        resource_dimension: if tex.depth > 1 {
+            D3D10ResourceDimension::Texture3D
+        } else {
+            D3D10ResourceDimension::Texture2D
+        },
+        alpha_mode: AlphaMode::Straight,
+    })?;
+
+    dds.data = data[..required_size.min(data.len())].to_vec();
+    dds.write(out)?;
+
+    Ok(())
+}
@@ -5,6 +5,7 @@ use std::{
     fmt::Display,
     io::{Read, Seek, SeekFrom},
 };
+#[cfg(windows)]
 use windows::core::PCSTR;
 
 #[derive(BinRead, Debug)]
@@ -103,6 +104,7 @@ impl DxbcSemanticType {
         })
     }
 
+    #[cfg(windows)]
     pub fn to_pcstr(self) -> PCSTR {
         match self {
             DxbcSemanticType::Position => s!("POSITION"),
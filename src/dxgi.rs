@@ -1,5 +1,6 @@
 use binrw::BinRead;
 use std::mem::transmute;
+#[cfg(windows)]
 use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT;
 // use vulkano::format::Format as VkFormat;
 
@@ -132,6 +133,7 @@ pub enum DxgiFormat {
     FORCE_UINT = 0xffffffff,
 }
 
+#[cfg(windows)]
 impl From<DxgiFormat> for DXGI_FORMAT {
     fn from(val: DxgiFormat) -> Self {
         DXGI_FORMAT(val.into())
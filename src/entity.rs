@@ -1,21 +1,25 @@
-use crate::structure::{DeadBeefMarker, ResourcePointer, TablePointer, Tag};
+use crate::packages::package_manager;
+use crate::structure::{
+    serialize_array, serialize_tag, DeadBeefMarker, ResourcePointer, TablePointer, Tag,
+};
 use crate::types::{Vector2, Vector4};
 
-use binrw::BinRead;
+use binrw::{BinRead, BinReaderExt};
 
 use destiny_pkg::TagHash;
+use serde::Serialize;
 
 use std::cmp::Ordering;
-use std::io::SeekFrom;
+use std::io::{Cursor, Seek, SeekFrom};
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80809c0f {
     pub file_size: u64,
     #[br(seek_before(SeekFrom::Start(0x10)))]
     pub unk10: TablePointer<Unk80809c04>,
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80809c04 {
     pub unk0: Tag<Unk80809c36>,
     pub unk4: u32,
@@ -23,7 +27,7 @@ pub struct Unk80809c04 {
 }
 
 /// Entity resource
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80809c36 {
     pub file_size: u64,
     pub unk8: ResourcePointer,
@@ -31,7 +35,7 @@ pub struct Unk80809c36 {
     pub unk18: ResourcePointer,
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk808073a5 {
     pub file_size: u64,
     pub unk0: u64,
@@ -43,20 +47,27 @@ pub struct Unk808073a5 {
     pub texcoord_offset: Vector2,
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80807378 {
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_buffer1: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_buffer2: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub buffer2: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub buffer3: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub index_buffer: TagHash,
     pub unk1c: u32,
     pub parts: TablePointer<Unk8080737e>,
+    #[serde(serialize_with = "serialize_array")]
     pub unk30: [u16; 48],
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk8080737e {
+    #[serde(serialize_with = "serialize_tag")]
     pub material: TagHash,
     pub variant_shader_index: u16,
     pub primitive_type: EPrimitiveType,
@@ -80,7 +91,7 @@ pub struct Unk808072c5 {
     pub unk6: i16,
 }
 
-#[derive(BinRead, Debug, PartialEq, Copy, Clone)]
+#[derive(BinRead, Debug, PartialEq, Copy, Clone, Serialize)]
 #[br(repr(u8))]
 pub enum EPrimitiveType {
     Triangles = 3,
@@ -88,7 +99,7 @@ pub enum EPrimitiveType {
 }
 
 #[allow(non_camel_case_types, clippy::derive_ord_xor_partial_ord)]
-#[derive(BinRead, Debug, PartialEq, Eq, Ord, Copy, Clone)]
+#[derive(BinRead, Debug, PartialEq, Eq, Ord, Copy, Clone, Serialize)]
 #[br(repr(u8))]
 pub enum ELodCategory {
     /// main geometry lod0
@@ -165,3 +176,17 @@ pub struct IndexBufferHeader {
     pub deadbeef: DeadBeefMarker,
     pub zero1: u32,
 }
+
+/// Reads the model from the first entity model resource (0x808072b8) of an entity
+pub fn read_entity_model(entity: TagHash) -> anyhow::Result<Option<Tag<Unk808073a5>>> {
+    let header: Unk80809c0f = package_manager().read_tag_struct(entity)?;
+    for e in &header.unk10 {
+        if e.unk0.unk10.resource_type == 0x808072b8 {
+            let mut cur = Cursor::new(package_manager().read_tag(e.unk0.tag())?);
+            cur.seek(SeekFrom::Start(e.unk0.unk18.offset + 0x1dc))?;
+            return Ok(Some(cur.read_le()?));
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,279 @@
+//! Batch tag dumping. Tags are written to `<output>/<package>/<class>/<tag>.bin`, with a
+//! `manifest.csv` listing every dumped tag and a hash of its data for diffing between patches
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use anyhow::Context;
+use destiny_pkg::TagHash;
+use nohash_hasher::IntSet;
+use rayon::prelude::*;
+
+use crate::classes;
+use crate::export::dump::{self, DumpFormat};
+use crate::packages::{all_tags, package_manager, package_tags};
+use crate::util::fnv1a64;
+
+/// Which tags to dump
+#[derive(Debug, Clone)]
+pub enum BatchSelection {
+    /// Every tag in a package
+    Package(u16),
+    /// Every tag with the given file type, and optionally subtype
+    FileType {
+        file_type: u8,
+        file_subtype: Option<u8>,
+    },
+    /// Every tag of a reference class
+    Reference(u32),
+    /// A tag and everything it references, up to `max_depth` references deep
+    Recursive { root: TagHash, max_depth: usize },
+}
+
+pub struct BatchOptions {
+    pub selection: BatchSelection,
+    pub output: PathBuf,
+    /// Also write a structured dump next to the raw data of every tag
+    pub structured: Option<DumpFormat>,
+}
+
+#[derive(Default)]
+pub struct BatchProgress {
+    pub total: AtomicUsize,
+    pub done: AtomicUsize,
+    pub failed: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl BatchProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            0.0
+        } else {
+            self.done.load(Ordering::Relaxed) as f32 / total as f32
+        }
+    }
+}
+
+pub struct BatchSummary {
+    pub dumped: usize,
+    pub failed: usize,
+    pub manifest: PathBuf,
+}
+
+struct ManifestRow {
+    tag: TagHash,
+    file_type: u8,
+    file_subtype: u8,
+    reference: u32,
+    size: usize,
+    hash: u64,
+    /// Path of the raw data, relative to the output directory
+    path: String,
+    error: Option<String>,
+}
+
+/// Dumps the selected tags and writes the manifest. Can be cancelled through `progress`, in which
+/// case the manifest only lists the tags dumped so far
+pub fn dump_batch(
+    options: &BatchOptions,
+    progress: &BatchProgress,
+) -> anyhow::Result<BatchSummary> {
+    let tags = select_tags(&options.selection, progress)?;
+    info!(
+        "Dumping {} tags to {}",
+        tags.len(),
+        options.output.display()
+    );
+    progress.total.store(tags.len(), Ordering::Relaxed);
+
+    let mut rows: Vec<ManifestRow> = tags
+        .par_iter()
+        .filter_map(|tag| {
+            if progress.is_cancelled() {
+                return None;
+            }
+
+            let row = dump_one(*tag, options);
+            if row.error.is_some() {
+                progress.failed.fetch_add(1, Ordering::Relaxed);
+            }
+            progress.done.fetch_add(1, Ordering::Relaxed);
+            Some(row)
+        })
+        .collect();
+    rows.sort_by_key(|r| (r.tag.pkg_id(), r.tag.0));
+
+    let manifest = options.output.join("manifest.csv");
+    write_manifest(&manifest, &rows)?;
+
+    let failed = rows.iter().filter(|r| r.error.is_some()).count();
+    Ok(BatchSummary {
+        dumped: rows.len() - failed,
+        failed,
+        manifest,
+    })
+}
+
+fn select_tags(
+    selection: &BatchSelection,
+    progress: &BatchProgress,
+) -> anyhow::Result<Vec<TagHash>> {
+    Ok(match selection {
+        BatchSelection::Package(pkg_id) => {
+            anyhow::ensure!(
+                package_manager().package_paths.contains_key(pkg_id),
+                "Package {pkg_id:04x} does not exist"
+            );
+            package_tags(*pkg_id, |_, _| true)
+        }
+        BatchSelection::FileType {
+            file_type,
+            file_subtype,
+        } => all_tags(|t, st| t == *file_type && file_subtype.map_or(true, |s| s == st)),
+        BatchSelection::Reference(reference) => package_manager()
+            .get_all_by_reference(*reference)
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .collect(),
+        BatchSelection::Recursive { root, max_depth } => {
+            referenced_tags(*root, *max_depth, progress)?
+        }
+    })
+}
+
+/// `root` and every tag it references (directly or indirectly), breadth first
+fn referenced_tags(
+    root: TagHash,
+    max_depth: usize,
+    progress: &BatchProgress,
+) -> anyhow::Result<Vec<TagHash>> {
+    package_manager()
+        .get_entry(root)
+        .with_context(|| format!("Tag {root} does not exist"))?;
+
+    let mut seen: IntSet<TagHash> = IntSet::default();
+    let mut tags = vec![];
+    let mut queue = VecDeque::from([(root, 0)]);
+    seen.insert(root);
+    while let Some((tag, depth)) = queue.pop_front() {
+        if progress.is_cancelled() {
+            break;
+        }
+
+        tags.push(tag);
+        if depth >= max_depth {
+            continue;
+        }
+
+        let Ok(data) = package_manager().read_tag(tag) else {
+            continue;
+        };
+
+        for reference in dump::scan_tag_references(&data) {
+            if seen.insert(reference) {
+                queue.push_back((reference, depth + 1));
+            }
+        }
+    }
+
+    Ok(tags)
+}
+
+fn dump_one(tag: TagHash, options: &BatchOptions) -> ManifestRow {
+    let mut row = ManifestRow {
+        tag,
+        file_type: 0,
+        file_subtype: 0,
+        reference: 0,
+        size: 0,
+        hash: 0,
+        path: String::new(),
+        error: None,
+    };
+
+    if let Err(e) = dump_one_inner(tag, options, &mut row) {
+        error!("Failed to dump tag {tag}: {e:?}");
+        row.error = Some(format!("{e:#}"));
+    }
+
+    row
+}
+
+fn dump_one_inner(
+    tag: TagHash,
+    options: &BatchOptions,
+    row: &mut ManifestRow,
+) -> anyhow::Result<()> {
+    let entry = package_manager().get_entry(tag)?;
+    row.file_type = entry.file_type;
+    row.file_subtype = entry.file_subtype;
+    row.reference = entry.reference;
+
+    let class = classes::class(entry.reference)
+        .map(|c| c.type_name.to_string())
+        .unwrap_or_else(|| format!("{:08x}", entry.reference));
+    let dir = PathBuf::from(format!("{:04x}", tag.pkg_id())).join(class);
+    std::fs::create_dir_all(options.output.join(&dir))
+        .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+
+    let data = package_manager().read_tag(tag)?;
+    row.size = data.len();
+    row.hash = fnv1a64(&data);
+
+    let path = dir.join(format!("{tag}.bin"));
+    std::fs::write(options.output.join(&path), &data)?;
+    row.path = path.to_string_lossy().replace('\\', "/");
+
+    if let Some(format) = options.structured {
+        let path = options
+            .output
+            .join(&dir)
+            .join(format!("{tag}.{}", format.extension()));
+        dump::write_dump(tag, format, path).context("Structured dump failed")?;
+    }
+
+    Ok(())
+}
+
+fn write_manifest(path: &Path, rows: &[ManifestRow]) -> anyhow::Result<()> {
+    let mut out = String::from(
+        "tag,package,file_type,file_subtype,reference,class,size,fnv1a64,path,error\n",
+    );
+    for r in rows {
+        writeln!(
+            out,
+            "{},{:04x},{},{},{:08x},{},{},{:016x},{},{}",
+            r.tag,
+            r.tag.pkg_id(),
+            r.file_type,
+            r.file_subtype,
+            r.reference,
+            classes::class(r.reference).map_or("", |c| c.type_name),
+            r.size,
+            r.hash,
+            r.path,
+            r.error
+                .as_deref()
+                .map(|e| format!("\"{}\"", e.replace('"', "\"\"").replace('\n', " ")))
+                .unwrap_or_default()
+        )
+        .ok();
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, out).with_context(|| format!("Failed to write {}", path.display()))
+}
@@ -0,0 +1,202 @@
+//! Structured tag dumps. Tags with a known struct are dumped field by field, other tags get a
+//! heuristic view of their contents
+
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use destiny_pkg::TagHash;
+use serde_yaml::{Mapping, Value};
+
+use crate::classes;
+use crate::packages::package_manager;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    Yaml,
+    Json,
+}
+
+impl DumpFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DumpFormat::Yaml => "yaml",
+            DumpFormat::Json => "json",
+        }
+    }
+
+    pub fn serialize(&self, value: &Value) -> anyhow::Result<String> {
+        match self {
+            DumpFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+            DumpFormat::Json => Ok(serde_json::to_string_pretty(value)? + "\n"),
+        }
+    }
+}
+
+/// Dumps the entry info of `tag`, and either the fields of its struct (see [classes]) or a
+/// heuristic view of its data
+pub fn structured_dump(tag: TagHash) -> anyhow::Result<Value> {
+    let entry = package_manager().get_entry(tag)?;
+
+    let mut root = Mapping::new();
+    root.insert("tag".into(), tag.to_string().into());
+    root.insert(
+        "reference".into(),
+        format!("{:08x}", entry.reference).into(),
+    );
+    root.insert("file_type".into(), (entry.file_type as u64).into());
+    root.insert("file_subtype".into(), (entry.file_subtype as u64).into());
+
+    match classes::class(entry.reference) {
+        Some(class) => {
+            root.insert("class".into(), class.name.into());
+            root.insert("struct".into(), class.type_name.into());
+            root.insert("fields".into(), class.parse_value(tag)?);
+        }
+        None => {
+            let data = package_manager().read_tag(tag)?;
+            root.insert("size".into(), (data.len() as u64).into());
+            root.insert("heuristic".into(), heuristic_view(&data));
+        }
+    }
+
+    Ok(Value::Mapping(root))
+}
+
+/// Writes a structured dump of `tag` to `path`
+pub fn write_dump<P: AsRef<Path>>(tag: TagHash, format: DumpFormat, path: P) -> anyhow::Result<()> {
+    let dump = format.serialize(&structured_dump(tag)?)?;
+    if let Some(parent) = path.as_ref().parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(std::fs::write(path, dump)?)
+}
+
+/// Default path for the raw data of `tag`, `tags/<tag>_<file type>_<file subtype>.bin`
+pub fn raw_dump_path(tag: TagHash) -> anyhow::Result<PathBuf> {
+    let entry = package_manager().get_entry(tag)?;
+    Ok(PathBuf::from(format!(
+        "tags/{tag}_{}_{}.bin",
+        entry.file_type, entry.file_subtype
+    )))
+}
+
+/// Default path for a structured dump of `tag`, `tags/<tag>.<extension>`
+pub fn structured_dump_path(tag: TagHash, format: DumpFormat) -> PathBuf {
+    PathBuf::from(format!("tags/{tag}.{}", format.extension()))
+}
+
+/// Guesses what the words of a tag of unknown class are. Zero words are left out
+pub fn heuristic_view(data: &[u8]) -> Value {
+    let mut fields = Mapping::new();
+
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        let word = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+        // Relative pointers are 64-bit, and point somewhere inside the tag
+        if offset % 8 == 0 && offset + 8 <= data.len() {
+            let value = i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+            if let Some(guess) = guess_pointer(data, offset, value) {
+                fields.insert(format!("0x{offset:04x}").into(), guess.into());
+                offset += 8;
+                continue;
+            }
+        }
+
+        if word != 0 {
+            fields.insert(format!("0x{offset:04x}").into(), guess_word(word).into());
+        }
+
+        offset += 4;
+    }
+
+    Value::Mapping(fields)
+}
+
+fn guess_pointer(data: &[u8], offset: usize, value: i64) -> Option<String> {
+    if value <= 0 || value % 4 != 0 {
+        return None;
+    }
+
+    let target = (offset as i64).checked_add(value)? as usize;
+    if target >= data.len() {
+        return None;
+    }
+
+    // Tables are a count followed by a pointer to an array header repeating the count
+    if offset >= 8 && target + 16 <= data.len() {
+        let count = u64::from_le_bytes(data[offset - 8..offset].try_into().unwrap());
+        let header_count = u64::from_le_bytes(data[target..target + 8].try_into().unwrap());
+        if count != 0 && count == header_count {
+            let class = u32::from_le_bytes(data[target + 8..target + 12].try_into().unwrap());
+            return Some(format!(
+                "table of {count} (class {class:08x}) at 0x{:x}",
+                target + 16
+            ));
+        }
+    }
+
+    Some(format!("relative pointer to 0x{target:x}"))
+}
+
+/// Whether `tag` is a valid tag in the loaded packages
+pub fn is_existing_tag(tag: TagHash) -> bool {
+    tag.is_valid() && package_manager().get_entry(tag).is_ok()
+}
+
+/// Finds the tags referenced by a tag, by checking every aligned u32 in `data`
+pub fn scan_tag_references(data: &[u8]) -> Vec<TagHash> {
+    let mut tags: Vec<TagHash> = data
+        .chunks_exact(4)
+        .map(|c| TagHash(u32::from_le_bytes(c.try_into().unwrap())))
+        .filter(|t| is_existing_tag(*t))
+        .collect();
+    tags.sort_by_key(|t| t.0);
+    tags.dedup();
+    tags
+}
+
+fn guess_word(word: u32) -> String {
+    if word == 0x811c9dc5 {
+        return "DestinyHash(NONE)".to_string();
+    }
+
+    if word & 0xffff0000 == 0x80800000 {
+        return format!("class {word:08x}");
+    }
+
+    let tag = TagHash(word);
+    if tag.is_valid() {
+        if let Ok(entry) = package_manager().get_entry(tag) {
+            return format!("tag {tag} ({:08x})", entry.reference);
+        }
+    }
+
+    let float = f32::from_bits(word);
+    if float.is_finite() && (1e-4..=1e6).contains(&float.abs()) {
+        return format!("f32 {float}");
+    }
+
+    format!("u32 {word} (0x{word:08x})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn json_output() {
+        assert_eq!(
+            DumpFormat::Json
+                .serialize(&yaml("{ a: 1, b: [true, null], c: {}, d: [], 1: x }"))
+                .unwrap(),
+            "{\n  \"a\": 1,\n  \"b\": [\n    true,\n    null\n  ],\n  \"c\": {},\n  \"d\": [],\n  \
+             \"1\": \"x\"\n}\n"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Minimal Wavefront OBJ/MTL writer, meant for quick geometry checks rather than full fidelity exports
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use destiny_pkg::TagHash;
+use glam::Mat4;
+#[cfg(windows)]
+use nohash_hasher::IntMap;
+use nohash_hasher::IntSet;
+
+use crate::entity::read_entity_model;
+#[cfg(windows)]
+use crate::map::MapData;
+use crate::map::Unk8080714f;
+use crate::mesh::{self, MeshData};
+use crate::packages::package_manager;
+use crate::statics::Unk808071a7;
+
+#[derive(Default)]
+pub struct ObjExporter {
+    obj: String,
+    materials: IntSet<TagHash>,
+    vertex_count: usize,
+}
+
+impl ObjExporter {
+    /// Adds a set of meshes as a single object, with positions transformed by `transform`
+    pub fn add_object(&mut self, name: &str, meshes: &[MeshData], transform: Mat4) {
+        writeln!(self.obj, "o {name}").ok();
+
+        for mesh in meshes {
+            for p in &mesh.positions {
+                let p = transform.transform_point3(*p);
+                writeln!(self.obj, "v {} {} {}", p.x, p.y, p.z).ok();
+            }
+
+            for part in &mesh.parts {
+                writeln!(self.obj, "usemtl {}", material_name(part.material)).ok();
+                self.materials.insert(part.material);
+
+                for t in part.indices.chunks_exact(3) {
+                    // OBJ indices are 1-based
+                    let base = self.vertex_count + 1;
+                    writeln!(
+                        self.obj,
+                        "f {} {} {}",
+                        base + t[0] as usize,
+                        base + t[1] as usize,
+                        base + t[2] as usize
+                    )
+                    .ok();
+                }
+            }
+
+            self.vertex_count += mesh.positions.len();
+        }
+    }
+
+    /// Writes the .obj file to `path`, and the accompanying .mtl file next to it
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mtl_path = path.with_extension("mtl");
+        let mut obj = String::new();
+        writeln!(obj, "# Exported by Alkahest (Z-up)")?;
+        if let Some(mtl_name) = mtl_path.file_name() {
+            writeln!(obj, "mtllib {}", mtl_name.to_string_lossy())?;
+        }
+        obj += &self.obj;
+
+        let mut mtl = String::new();
+        for m in &self.materials {
+            // Give every material a stable color so parts can be told apart
+            let c = m.0.to_le_bytes();
+            writeln!(mtl, "newmtl {}", material_name(*m))?;
+            writeln!(
+                mtl,
+                "Kd {:.3} {:.3} {:.3}\n",
+                c[0] as f32 / 255.0,
+                c[1] as f32 / 255.0,
+                c[2] as f32 / 255.0
+            )?;
+        }
+
+        std::fs::write(path, obj)?;
+        std::fs::write(mtl_path, mtl)?;
+
+        Ok(())
+    }
+}
+
+fn material_name(material: TagHash) -> String {
+    if material.is_valid() {
+        format!("mat_{material}")
+    } else {
+        "mat_none".to_string()
+    }
+}
+
+/// Exports a static model (0x808071a7), entity (0x80809c0f) or terrain (0x8080714f) in model space
+pub fn export_tag<P: AsRef<Path>>(tag: TagHash, path: P) -> anyhow::Result<()> {
+    let entry = package_manager().get_entry(tag)?;
+
+    let meshes = match entry.reference {
+        0x808071a7 => mesh::load_static(&package_manager().read_tag_struct::<Unk808071a7>(tag)?)?,
+        0x80809c0f => {
+            let Some(model) = read_entity_model(tag)? else {
+                anyhow::bail!("Entity {tag} does not have a model");
+            };
+
+            mesh::load_entity_model(&model)?
+        }
+        0x8080714f => vec![mesh::load_terrain(
+            &package_manager().read_tag_struct::<Unk8080714f>(tag)?,
+        )?],
+        u => anyhow::bail!("Tag {tag} has an unsupported reference type {u:08x}"),
+    };
+
+    let mut exporter = ObjExporter::default();
+    exporter.add_object(&tag.to_string(), &meshes, Mat4::IDENTITY);
+    exporter.write(path)
+}
+
+/// Exports all statics, entities and terrain of a map.
+///
+/// When `bake_transforms` is set, every instance is written with its world transform applied.
+/// Otherwise every unique model is written once, in model space.
+#[cfg(windows)]
+pub fn export_map<P: AsRef<Path>>(
+    map: &MapData,
+    bake_transforms: bool,
+    path: P,
+) -> anyhow::Result<()> {
+    let mut exporter = ObjExporter::default();
+    // Models that have already been written when not baking transforms
+    let mut written: IntSet<TagHash> = IntSet::default();
+
+    let mut statics: IntMap<TagHash, Option<Vec<MeshData>>> = IntMap::default();
+    for group in &map.placement_groups {
+        for instance in &group.instances {
+            let Some(model_hash) = group.statics.get(instance.static_index as usize) else {
+                continue;
+            };
+
+            let meshes = statics.entry(*model_hash).or_insert_with(|| {
+                package_manager()
+                    .read_tag_struct::<Unk808071a7>(*model_hash)
+                    .and_then(|m| mesh::load_static(&m))
+                    .map_err(|e| error!("Failed to read static {model_hash}: {e}"))
+                    .ok()
+            });
+
+            let Some(meshes) = meshes else {
+                continue;
+            };
+
+            if bake_transforms {
+                let start = instance.instance_start as usize;
+                let end = start + instance.instance_count as usize;
+                for (i, t) in group.transforms.range(start..end).enumerate() {
+                    let t = t?;
+                    exporter.add_object(
+                        &format!("static_{model_hash}_{}", start + i),
+                        meshes,
+                        t.transform(),
+                    );
+                }
+            } else if written.insert(*model_hash) {
+                exporter.add_object(&format!("static_{model_hash}"), meshes, Mat4::IDENTITY);
+            }
+        }
+    }
+
+    let mut entities: IntMap<TagHash, Option<Vec<MeshData>>> = IntMap::default();
+    for (i, (rp, _)) in map.resource_points.iter().enumerate() {
+        if !rp.entity.is_valid() {
+            continue;
+        }
+
+        let meshes = entities.entry(rp.entity).or_insert_with(|| {
+            match read_entity_model(rp.entity).and_then(|m| match m {
+                Some(m) => mesh::load_entity_model(&m).map(Some),
+                None => Ok(None),
+            }) {
+                Ok(m) => m,
+                Err(e) => {
+                    error!("Failed to read entity {}: {e}", rp.entity);
+                    None
+                }
+            }
+        });
+
+        let Some(meshes) = meshes else {
+            continue;
+        };
+
+        if bake_transforms {
+            exporter.add_object(&format!("entity_{}_{i}", rp.entity), meshes, rp.transform());
+        } else if written.insert(rp.entity) {
+            exporter.add_object(&format!("entity_{}", rp.entity), meshes, Mat4::IDENTITY);
+        }
+    }
+
+    for t in &map.terrains {
+        match package_manager()
+            .read_tag_struct::<Unk8080714f>(*t)
+            .and_then(|t| mesh::load_terrain(&t))
+        {
+            // Terrain is already positioned in world space
+            Ok(m) => exporter.add_object(&format!("terrain_{t}"), &[m], Mat4::IDENTITY),
+            Err(e) => error!("Failed to read terrain {t}: {e}"),
+        }
+    }
+
+    exporter.write(&path)?;
+
+    info!(
+        "Exported map '{}' ({} vertices) to {}",
+        map.name,
+        exporter.vertex_count,
+        path.as_ref().display()
+    );
+
+    Ok(())
+}
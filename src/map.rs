@@ -1,16 +1,27 @@
+#[cfg(windows)]
+use crate::map_loader::MapDependencies;
+#[cfg(windows)]
 use crate::overlays::resource_nametags::ResourcePoint;
+#[cfg(windows)]
 use crate::render::scopes::ScopeRigidModel;
+#[cfg(windows)]
 use crate::render::ConstantBuffer;
 use crate::statics::Unk8080966d;
-use crate::structure::{ResourcePointer, TablePointer, Tag};
+use crate::structure::{
+    serialize_tag, serialize_tag64, serialize_tags, LazyTablePointer, LazyTag, ResourcePointer,
+    TablePointer, Tag,
+};
 use crate::types::{DestinyHash, Vector4};
 use binrw::BinRead;
 use destiny_pkg::{TagHash, TagHash64};
+#[cfg(windows)]
+use glam::Vec4;
+use serde::Serialize;
 
 use std::io::SeekFrom;
 
 // D2Class_1E898080
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80807dae {
     pub file_size: u64,
     // 808091e0
@@ -24,7 +35,7 @@ pub struct Unk80807dae {
     pub unk40: TablePointer<Unk80809644>,
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80809644 {
     pub unk0: u32,
     pub unk4: u32,
@@ -33,40 +44,43 @@ pub struct Unk80809644 {
 }
 
 // D2Class_01878080
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk808091e0 {
     pub file_size: u64,
     pub map_resources: TablePointer<Unk808084c1>,
 }
 
 // TODO: Custom reader once new tag parser comes around
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk808084c1 {
     // 80808a54
+    #[serde(serialize_with = "serialize_tag")]
     pub hash32: TagHash,
     pub is_hash32: u32,
+    #[serde(serialize_with = "serialize_tag64")]
     pub hash64: TagHash64, // 80808a54
 }
 
 // D2Class_07878080
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80808a54 {
     pub file_size: u64,
     #[br(seek_before(SeekFrom::Start(0x28)))]
-    pub data_tables: TablePointer<Tag<Unk808099d6>>,
+    pub data_tables: TablePointer<LazyTag<Unk808099d6>>,
 }
 
 // D2Class_83988080
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk808099d6 {
     pub file_size: u64,
-    pub data_entries: TablePointer<Unk808099d8>,
+    pub data_entries: LazyTablePointer<Unk808099d8>,
 }
 
 // D2Class_85988080
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk808099d8 {
     // 80809c0f
+    #[serde(serialize_with = "serialize_tag")]
     pub entity: TagHash,
     pub unk4: [u32; 3],
     pub rotation: Vector4,
@@ -88,7 +102,7 @@ pub struct Unk80806ef4 {
 }
 
 /// Terrain
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk8080714f {
     pub file_size: u64,
     #[br(seek_before(SeekFrom::Start(0x10)))]
@@ -98,17 +112,22 @@ pub struct Unk8080714f {
     #[br(seek_before(SeekFrom::Start(0x58)))]
     pub mesh_groups: TablePointer<Unk80807154>,
 
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_buffer: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_buffer2: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub indices: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub material1: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub material2: TagHash,
 
     #[br(seek_before(SeekFrom::Start(0x80)))]
     pub mesh_parts: TablePointer<Unk80807152>,
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80807154 {
     pub unk0: f32,
     pub unk4: f32,
@@ -127,14 +146,16 @@ pub struct Unk80807154 {
     pub unk44: u32,
     pub unk48: u32,
     pub unk4c: u32,
+    #[serde(serialize_with = "serialize_tag")]
     pub dyemap: TagHash,
     pub unk54: u32,
     pub unk58: u32,
     pub unk5c: u32,
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80807152 {
+    #[serde(serialize_with = "serialize_tag")]
     pub material: TagHash,
     pub index_start: u32,
     pub index_count: u16,
@@ -142,33 +163,57 @@ pub struct Unk80807152 {
     pub detail_level: u8,
 }
 
+#[cfg(windows)]
 pub struct MapData {
     pub hash: TagHash,
     pub name: String,
     pub placement_groups: Vec<Tag<Unk8080966d>>,
     pub resource_points: Vec<(ResourcePoint, ConstantBuffer<ScopeRigidModel>)>,
     pub terrains: Vec<TagHash>,
+    pub point_lights: Vec<Vec4>,
+    pub cb_composite_lights: ConstantBuffer<Vec4>,
+    pub dependencies: MapDependencies,
+}
+
+/// A map that can be switched to. Only the current map is loaded
+#[cfg(windows)]
+pub struct MapListEntry {
+    pub hash: TagHash,
+    pub name: String,
+    pub data: Option<MapData>,
 }
 
+#[cfg(windows)]
 pub struct MapDataList {
     pub current_map: usize, // TODO(cohae): Shouldn't be here
-    pub maps: Vec<MapData>,
+    pub maps: Vec<MapListEntry>,
 }
 
+#[cfg(windows)]
 impl MapDataList {
     pub fn current_map(&self) -> Option<&MapData> {
-        self.maps.get(self.current_map % self.maps.len())
+        self.maps
+            .get(self.current_map % self.maps.len().max(1))?
+            .data
+            .as_ref()
+    }
+
+    pub fn index_of(&self, hash: TagHash) -> Option<usize> {
+        self.maps.iter().position(|m| m.hash == hash)
     }
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80807164 {
     pub file_size: u64,
+    #[serde(serialize_with = "serialize_tags")]
     pub unk8: TablePointer<TagHash>,
     pub unk18: TablePointer<u32>,
     pub unk28: TablePointer<u32>,
     pub unk38: TablePointer<u32>,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk48: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk4c: TagHash,
     pub unk50: TablePointer<u32>,
     pub unk60: [u32; 4],
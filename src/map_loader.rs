@@ -0,0 +1,1152 @@
+//! Loading of maps and the renderers for their contents, either at startup or on demand
+
+use std::io::{Cursor, Seek, SeekFrom};
+use std::sync::Arc;
+
+use anyhow::Context;
+use binrw::{BinReaderExt, BinResult};
+use destiny_pkg::TagHash;
+use glam::{Mat4, Quat, Vec3, Vec3A, Vec4};
+use itertools::Itertools;
+use nohash_hasher::{IntMap, IntSet};
+
+use crate::entity::{Unk808072c5, Unk808073a5, Unk80809c0f};
+use crate::map::{
+    MapData, MapDataList, MapListEntry, Unk80806ef4, Unk8080714f, Unk80807164, Unk80807dae,
+    Unk80808a54,
+};
+use crate::map_resources::{
+    MapResource, Unk80806b7f, Unk80806df3, Unk80806e68, Unk8080714b, Unk80807268, Unk80809162,
+    Unk80809802,
+};
+use crate::material::{Material, Unk808071e8};
+use crate::overlays::resource_nametags::{ResourceOrigin, ResourcePoint};
+use crate::packages::package_manager;
+use crate::render::renderer::Renderer;
+use crate::render::resource_mt::LoadPriority;
+use crate::render::scopes::ScopeRigidModel;
+use crate::render::static_render::StaticModel;
+use crate::render::terrain::TerrainRenderer;
+use crate::render::{ConstantBuffer, DeviceContextSwapchain, EntityRenderer, InstancedRenderer};
+use crate::statics::{Unk808071a7, Unk8080966d};
+use crate::structure::{TablePointer, Tag};
+use crate::types::AABB;
+
+/// Map requested through the UI or console, loaded by the main loop before the next frame
+#[derive(Default)]
+pub struct MapLoadRequest(pub Option<TagHash>);
+
+/// Everything a loaded map holds a reference to, released when the map is unloaded
+#[derive(Default)]
+pub struct MapDependencies {
+    /// Placement groups, statics, entities and terrain owned by the [MapLoader]
+    pub renderers: IntSet<TagHash>,
+    /// Textures, buffers and materials in RenderData
+    pub render_data: IntSet<TagHash>,
+}
+
+/// Owns the renderers for everything placed in the loaded maps. Renderers are keyed by tag, so
+/// statics, entities and terrain shared between maps are only loaded once, and are dropped once
+/// no loaded map uses them anymore
+pub struct MapLoader {
+    dcs: Arc<DeviceContextSwapchain>,
+    strings: Arc<IntMap<u32, String>>,
+
+    pub placement_groups: IntMap<u32, (Unk8080966d, Vec<InstancedRenderer>)>,
+    pub terrain_renderers: IntMap<u32, TerrainRenderer>,
+    pub entity_renderers: IntMap<TagHash, EntityRenderer>,
+    pub static_map: IntMap<TagHash, Arc<StaticModel>>,
+    /// Transforms of the static instances in each placement group that failed to load, drawn
+    /// with the error mesh
+    pub error_instances: IntMap<u32, Vec<Mat4>>,
+
+    /// Number of loaded maps using each renderer
+    renderer_refs: IntMap<TagHash, usize>,
+    /// RenderData resources requested while loading each renderer
+    renderer_dependencies: IntMap<TagHash, IntSet<TagHash>>,
+}
+
+impl MapLoader {
+    pub fn new(dcs: Arc<DeviceContextSwapchain>, strings: Arc<IntMap<u32, String>>) -> Self {
+        Self {
+            dcs,
+            strings,
+            placement_groups: Default::default(),
+            terrain_renderers: Default::default(),
+            entity_renderers: Default::default(),
+            static_map: Default::default(),
+            error_instances: Default::default(),
+            renderer_refs: Default::default(),
+            renderer_dependencies: Default::default(),
+        }
+    }
+
+    pub fn map_name(&self, map: &Unk80807dae) -> String {
+        self.strings
+            .get(&map.map_name.0)
+            .cloned()
+            .unwrap_or(format!("[MissingString_{:08x}]", map.map_name.0))
+    }
+
+    /// Reads the name of a map without loading it
+    pub fn map_entry(&self, hash: TagHash) -> anyhow::Result<MapListEntry> {
+        let map: Unk80807dae = package_manager().read_tag_struct(hash)?;
+
+        Ok(MapListEntry {
+            hash,
+            name: self.map_name(&map),
+            data: None,
+        })
+    }
+
+    /// Makes sure the current map is loaded, and unloads every other map. The new map is loaded
+    /// before the old one is unloaded, so resources shared between them stay loaded.
+    /// Returns true if the current map was loaded
+    pub fn update(&mut self, renderer: &Renderer, maps: &mut MapDataList) -> bool {
+        if maps.maps.is_empty() {
+            return false;
+        }
+
+        maps.current_map %= maps.maps.len();
+        let current = &mut maps.maps[maps.current_map];
+        if current.data.is_some() {
+            return false;
+        }
+
+        match self.load_map(renderer, current.hash) {
+            Ok(map) => current.data = Some(map),
+            Err(e) => {
+                error!(
+                    "Failed to load map {} '{}': {e}",
+                    current.hash, current.name
+                );
+                if let Some(previous) = maps.maps.iter().position(|m| m.data.is_some()) {
+                    maps.current_map = previous;
+                }
+
+                return false;
+            }
+        }
+
+        for (i, entry) in maps.maps.iter_mut().enumerate() {
+            if i != maps.current_map {
+                if let Some(map) = entry.data.take() {
+                    self.unload_map(renderer, map);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Loads a map and takes a reference to everything it uses
+    pub fn load_map(&mut self, renderer: &Renderer, hash: TagHash) -> anyhow::Result<MapData> {
+        let (map, mut render_data) = renderer
+            .render_data
+            .track(|| self.load_map_resources(renderer, hash));
+        let mut map = match map {
+            Ok(map) => map,
+            Err(e) => {
+                renderer.render_data.cancel_unreferenced(&render_data);
+                return Err(e);
+            }
+        };
+
+        let renderers = self.renderer_tags(&map);
+        for tag in &renderers {
+            *self.renderer_refs.entry(*tag).or_default() += 1;
+            if let Some(dependencies) = self.renderer_dependencies.get(tag) {
+                render_data.extend(dependencies);
+            }
+        }
+
+        renderer.render_data.data_mut().acquire(&render_data);
+        map.dependencies = MapDependencies {
+            renderers,
+            render_data,
+        };
+
+        Ok(map)
+    }
+
+    /// Releases everything a map holds a reference to, dropping the renderers and resources no
+    /// other map uses
+    pub fn unload_map(&mut self, renderer: &Renderer, map: MapData) {
+        let _span = info_span!("Unload map", hash = %map.hash).entered();
+        let unloaded = renderer
+            .render_data
+            .data_mut()
+            .release(&map.dependencies.render_data);
+        let cancelled = renderer
+            .render_data
+            .cancel_unreferenced(&map.dependencies.render_data);
+
+        let mut dropped = 0;
+        for tag in &map.dependencies.renderers {
+            let Some(refs) = self.renderer_refs.get_mut(tag) else {
+                continue;
+            };
+
+            *refs -= 1;
+            if *refs == 0 {
+                self.renderer_refs.remove(tag);
+                self.renderer_dependencies.remove(tag);
+                self.placement_groups.remove(&tag.0);
+                self.error_instances.remove(&tag.0);
+                self.terrain_renderers.remove(&tag.0);
+                self.entity_renderers.remove(tag);
+                self.static_map.remove(tag);
+                dropped += 1;
+            }
+        }
+
+        info!(
+            "Unloaded map '{}', dropped {dropped} renderers and {unloaded} resources, cancelled {cancelled} load requests",
+            map.name
+        );
+    }
+
+    /// Raises the priority of the queued textures and buffers of the map's statics and entities
+    /// by their distance to the camera, so the closest ones are loaded first
+    pub fn prioritize_loads(&self, renderer: &Renderer, map: &MapData, camera_position: Vec3) {
+        let mut distances: IntMap<TagHash, f32> = IntMap::default();
+        let mut closest = |tag: TagHash, position: Vec3| {
+            let distance = distances.entry(tag).or_insert(f32::MAX);
+            *distance = distance.min(position.distance(camera_position));
+        };
+
+        for group in &map.placement_groups {
+            let Some((placements, _)) = self.placement_groups.get(&group.tag().0) else {
+                continue;
+            };
+
+            for instance in &placements.instances {
+                let Some(model_hash) = placements.statics.iter().nth(instance.static_index as _)
+                else {
+                    continue;
+                };
+
+                let start = instance.instance_start as usize;
+                let end = start + instance.instance_count as usize;
+                for transform in placements.transforms.range(start..end).flatten() {
+                    let t = &transform.translation;
+                    closest(*model_hash, Vec3::new(t.x, t.y, t.z));
+                }
+            }
+        }
+
+        for (rp, _) in &map.resource_points {
+            closest(rp.entity, rp.translation.truncate());
+        }
+
+        let mut priorities: IntMap<TagHash, LoadPriority> = IntMap::default();
+        for (renderer_tag, distance) in distances {
+            let priority = LoadPriority::Distance(distance);
+            for tag in self
+                .renderer_dependencies
+                .get(&renderer_tag)
+                .into_iter()
+                .flatten()
+            {
+                priorities
+                    .entry(*tag)
+                    .and_modify(|p| {
+                        if priority.is_higher_than(p) {
+                            *p = priority;
+                        }
+                    })
+                    .or_insert(priority);
+            }
+        }
+
+        renderer.render_data.prioritize(&priorities);
+    }
+
+    /// Tags of every renderer the map uses
+    fn renderer_tags(&self, map: &MapData) -> IntSet<TagHash> {
+        let mut tags = IntSet::default();
+        for group in &map.placement_groups {
+            tags.insert(group.tag());
+            tags.extend(
+                group
+                    .statics
+                    .iter()
+                    .filter(|s| self.static_map.contains_key(*s)),
+            );
+        }
+
+        tags.extend(
+            map.resource_points
+                .iter()
+                .map(|(rp, _)| rp.entity)
+                .filter(|e| self.entity_renderers.contains_key(e)),
+        );
+        tags.extend(
+            map.terrains
+                .iter()
+                .filter(|t| self.terrain_renderers.contains_key(&t.0)),
+        );
+
+        tags
+    }
+
+    /// Parses a map and loads every resource it references that isn't loaded yet
+    fn load_map_resources(
+        &mut self,
+        renderer: &Renderer,
+        hash: TagHash,
+    ) -> anyhow::Result<MapData> {
+        let _span = info_span!("Load map", %hash).entered();
+        let think: Unk80807dae = package_manager().read_tag_struct(hash)?;
+
+        let mut placement_groups = vec![];
+        let mut resource_points = vec![];
+        let mut terrains = vec![];
+        let mut terrain_headers = vec![];
+        let mut material_map: IntMap<TagHash, Material> = Default::default();
+
+        // First light reserved for camera light
+        let mut point_lights = vec![Vec4::ZERO];
+
+        let mut unknown_root_resources: IntMap<u32, ()> = IntMap::default();
+        for res in &think.child_map.map_resources {
+            let thing2: Unk80808a54 = if res.is_hash32 != 0 {
+                match package_manager().read_tag_struct(res.hash32) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        renderer.render_data.fail(res.hash32, e);
+                        continue;
+                    }
+                }
+            } else {
+                match package_manager().read_tag64_struct(res.hash64.0) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        error!("Failed to load map resource {:?}: {e}", res.hash64);
+                        continue;
+                    }
+                }
+            };
+
+            for table in &thing2.data_tables {
+                let entries = match table.load() {
+                    Ok(t) => t.data_entries,
+                    Err(e) => {
+                        renderer.render_data.fail(table.tag(), e);
+                        continue;
+                    }
+                };
+                let table_data = match package_manager().read_tag(table.tag()) {
+                    Ok(d) => d,
+                    Err(e) => {
+                        renderer.render_data.fail(table.tag(), e);
+                        continue;
+                    }
+                };
+                let mut cur = Cursor::new(&table_data);
+
+                for (i, data) in entries.iter().enumerate() {
+                    let data = match data {
+                        Ok(d) => d,
+                        Err(e) => {
+                            error!(
+                                "Failed to read entry {i} of data table {}: {e}",
+                                table.tag()
+                            );
+                            continue;
+                        }
+                    };
+                    let origin = ResourceOrigin {
+                        map_resource: if res.is_hash32 != 0 {
+                            res.hash32
+                        } else {
+                            TagHash(u32::MAX)
+                        },
+                        map_resource64: (res.is_hash32 == 0).then_some(res.hash64),
+                        data_table: table.tag(),
+                        entry: data.clone(),
+                    };
+
+                    // A resource that fails to parse is skipped, instead of failing the whole map
+                    let result = (|| -> anyhow::Result<()> {
+                        if data.data_resource.is_valid {
+                            match data.data_resource.resource_type {
+                                // D2Class_C96C8080 (placement)
+                                0x808071b3 => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let preheader_tag: TagHash = cur.read_le()?;
+                                    let preheader: Unk80806ef4 =
+                                        package_manager().read_tag_struct(preheader_tag)?;
+
+                                    placement_groups.push(preheader.placement_group);
+                                }
+                                0x808071ad => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let header_tag: TagHash = cur.read_le()?;
+                                    let header: Unk80807164 =
+                                        package_manager().read_tag_struct(header_tag)?;
+
+                                    resource_points.push(ResourcePoint {
+                                        translation: Vec4::new(
+                                            (header.unk70.x + header.unk80.x) / 2.,
+                                            (header.unk70.y + header.unk80.y) / 2.,
+                                            (header.unk70.z + header.unk80.z) / 2.,
+                                            (header.unk70.w + header.unk80.w) / 2.,
+                                        ),
+                                        rotation: Quat::IDENTITY,
+                                        entity: data.entity,
+                                        origin: origin.clone(),
+                                        resource_type: data.data_resource.resource_type,
+                                        resource: MapResource::Unk808071ad(AABB {
+                                            min: Vec3A::new(
+                                                header.unk70.x,
+                                                header.unk70.y,
+                                                header.unk70.z,
+                                            ),
+                                            max: Vec3A::new(
+                                                header.unk80.x,
+                                                header.unk80.y,
+                                                header.unk80.z,
+                                            ),
+                                        }),
+                                    });
+                                }
+                                // D2Class_7D6C8080 (terrain)
+                                0x8080714b => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset))?;
+
+                                    let terrain_resource: Unk8080714b = cur.read_le()?;
+                                    let terrain: Unk8080714f = package_manager()
+                                        .read_tag_struct(terrain_resource.terrain)?;
+
+                                    for p in &terrain.mesh_parts {
+                                        if p.material.is_valid() {
+                                            load_material_tag(
+                                                renderer,
+                                                &mut material_map,
+                                                p.material,
+                                            );
+                                        }
+                                    }
+
+                                    terrain_headers.push((terrain_resource.terrain, terrain));
+                                    terrains.push(terrain_resource.terrain);
+                                }
+                                // Cubemap volume
+                                0x80806b7f => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset))?;
+
+                                    let cubemap_volume: Unk80806b7f = cur.read_le()?;
+                                    let extents_center = Vec4::new(
+                                        data.translation.x,
+                                        data.translation.y,
+                                        data.translation.z,
+                                        data.translation.w,
+                                    );
+                                    let extents = Vec4::new(
+                                        cubemap_volume.cubemap_extents.x,
+                                        cubemap_volume.cubemap_extents.y,
+                                        cubemap_volume.cubemap_extents.z,
+                                        cubemap_volume.cubemap_extents.w,
+                                    );
+
+                                    let volume_min = extents_center - extents;
+                                    let volume_max = extents_center + extents;
+
+                                    renderer
+                                        .render_data
+                                        .load_texture(cubemap_volume.cubemap_texture);
+
+                                    resource_points.push(ResourcePoint {
+                                        translation: extents_center,
+                                        rotation: Quat::from_xyzw(
+                                            data.rotation.x,
+                                            data.rotation.y,
+                                            data.rotation.z,
+                                            data.rotation.w,
+                                        ),
+                                        entity: data.entity,
+                                        origin: origin.clone(),
+                                        resource_type: data.data_resource.resource_type,
+                                        resource: MapResource::CubemapVolume(
+                                            Box::new(cubemap_volume),
+                                            AABB {
+                                                min: volume_min.truncate().into(),
+                                                max: volume_max.truncate().into(),
+                                            },
+                                        ),
+                                    });
+                                }
+                                // Point light
+                                0x80806cbf => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let tag: TagHash = cur.read_le()?;
+                                    resource_points.push(ResourcePoint {
+                                        translation: Vec4::new(
+                                            data.translation.x,
+                                            data.translation.y,
+                                            data.translation.z,
+                                            data.translation.w,
+                                        ),
+                                        rotation: Quat::from_xyzw(
+                                            data.rotation.x,
+                                            data.rotation.y,
+                                            data.rotation.z,
+                                            data.rotation.w,
+                                        ),
+                                        entity: data.entity,
+                                        origin: origin.clone(),
+                                        resource_type: data.data_resource.resource_type,
+                                        resource: MapResource::PointLight(tag),
+                                    });
+                                    point_lights.push(Vec4::new(
+                                        data.translation.x,
+                                        data.translation.y,
+                                        data.translation.z,
+                                        data.translation.w,
+                                    ));
+                                }
+                                // Decal collection
+                                0x80806e62 => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let tag: TagHash = cur.read_le()?;
+                                    if !tag.is_valid() {
+                                        return Ok(());
+                                    }
+
+                                    let header: Unk80806e68 =
+                                        package_manager().read_tag_struct(tag)?;
+
+                                    for inst in &header.instances {
+                                        for i in inst.start..(inst.start + inst.count) {
+                                            let transform = header.transforms[i as usize];
+                                            resource_points.push(ResourcePoint {
+                                                translation: Vec4::new(
+                                                    transform.x,
+                                                    transform.y,
+                                                    transform.z,
+                                                    transform.w,
+                                                ),
+                                                rotation: Quat::from_xyzw(
+                                                    data.rotation.x,
+                                                    data.rotation.y,
+                                                    data.rotation.z,
+                                                    data.rotation.w,
+                                                ),
+                                                entity: data.entity,
+                                                origin: origin.clone(),
+                                                resource_type: data.data_resource.resource_type,
+                                                resource: MapResource::Decal {
+                                                    material: inst.material,
+                                                    scale: transform.w,
+                                                },
+                                            })
+                                        }
+                                    }
+                                }
+                                // Unknown, every element has a mesh (material+index+vertex) and the required transforms
+                                0x80806df1 => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let tag: TagHash = cur.read_le()?;
+                                    if !tag.is_valid() {
+                                        return Ok(());
+                                    }
+
+                                    let header: Unk80806df3 =
+                                        package_manager().read_tag_struct(tag)?;
+
+                                    for p in &header.unk8 {
+                                        resource_points.push(ResourcePoint {
+                                            translation: Vec4::new(
+                                                p.translation.x,
+                                                p.translation.y,
+                                                p.translation.z,
+                                                p.translation.w,
+                                            ),
+                                            rotation: Quat::IDENTITY,
+                                            entity: data.entity,
+                                            origin: origin.clone(),
+                                            resource_type: data.data_resource.resource_type,
+                                            resource: MapResource::Unk80806df1,
+                                        });
+                                    }
+                                }
+                                // Unknown, structure seems like that of an octree
+                                0x80806f38 => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let tag: TagHash = cur.read_le()?;
+                                    if !tag.is_valid() {
+                                        return Ok(());
+                                    }
+
+                                    let header: Unk80807268 =
+                                        package_manager().read_tag_struct(tag)?;
+
+                                    for p in &header.unk50 {
+                                        resource_points.push(ResourcePoint {
+                                            translation: Vec4::new(
+                                                p.unk0.x, p.unk0.y, p.unk0.z, p.unk0.w,
+                                            ),
+                                            rotation: Quat::IDENTITY,
+                                            entity: data.entity,
+                                            origin: origin.clone(),
+                                            resource_type: data.data_resource.resource_type,
+                                            resource: MapResource::Unk80806f38,
+                                        });
+                                    }
+                                }
+                                0x80809160 => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let tag: TagHash = cur.read_le()?;
+                                    if !tag.is_valid() {
+                                        return Ok(());
+                                    }
+
+                                    let header: Unk80809162 =
+                                        package_manager().read_tag_struct(tag)?;
+
+                                    for p in &header.unk8 {
+                                        resource_points.push(ResourcePoint {
+                                            translation: Vec4::new(
+                                                p.unk10.x, p.unk10.y, p.unk10.z, p.unk10.w,
+                                            ),
+                                            rotation: Quat::IDENTITY,
+                                            entity: data.entity,
+                                            origin: origin.clone(),
+                                            resource_type: data.data_resource.resource_type,
+                                            resource: MapResource::RespawnPoint,
+                                        });
+                                    }
+                                }
+                                // (ambient) sound source
+                                0x80806b5b => {
+                                    cur.seek(SeekFrom::Start(data.data_resource.offset + 16))?;
+                                    let tag: TagHash = cur.read_le()?;
+                                    if !tag.is_valid() {
+                                        return Ok(());
+                                    }
+
+                                    let header: Unk80809802 =
+                                        package_manager().read_tag_struct(tag)?;
+
+                                    resource_points.push(ResourcePoint {
+                                        translation: Vec4::new(
+                                            data.translation.x,
+                                            data.translation.y,
+                                            data.translation.z,
+                                            data.translation.w,
+                                        ),
+                                        rotation: Quat::IDENTITY,
+                                        entity: data.entity,
+                                        origin: origin.clone(),
+                                        resource_type: data.data_resource.resource_type,
+                                        resource: MapResource::AmbientSound(header),
+                                    });
+                                }
+                                u => {
+                                    if data.translation.x == 0.0
+                                        && data.translation.y == 0.0
+                                        && data.translation.z == 0.0
+                                        && !unknown_root_resources.contains_key(&u)
+                                    {
+                                        warn!("World origin resource {} is not parsed! Resource points might be missing (table {})", TagHash(u), table.tag());
+                                        unknown_root_resources.insert(u, ());
+                                    }
+
+                                    debug!(
+                                    "Skipping unknown resource type {u:x} {:?} (table file {:?})",
+                                    data.translation,
+                                    table.tag()
+                                );
+                                    resource_points.push(ResourcePoint {
+                                        translation: Vec4::new(
+                                            data.translation.x,
+                                            data.translation.y,
+                                            data.translation.z,
+                                            data.translation.w,
+                                        ),
+                                        rotation: Quat::from_xyzw(
+                                            data.rotation.x,
+                                            data.rotation.y,
+                                            data.rotation.z,
+                                            data.rotation.w,
+                                        ),
+                                        entity: data.entity,
+                                        origin: origin.clone(),
+                                        resource_type: data.data_resource.resource_type,
+                                        resource: MapResource::Unknown(
+                                            data.data_resource.resource_type,
+                                        ),
+                                    });
+                                }
+                            };
+                        } else {
+                            resource_points.push(ResourcePoint {
+                                translation: Vec4::new(
+                                    data.translation.x,
+                                    data.translation.y,
+                                    data.translation.z,
+                                    data.translation.w,
+                                ),
+                                rotation: Quat::from_xyzw(
+                                    data.rotation.x,
+                                    data.rotation.y,
+                                    data.rotation.z,
+                                    data.rotation.w,
+                                ),
+                                entity: data.entity,
+                                origin: origin.clone(),
+                                resource_type: u32::MAX,
+                                resource: MapResource::Entity(data.entity),
+                            });
+                        }
+
+                        Ok(())
+                    })();
+
+                    if let Err(e) = result {
+                        renderer.render_data.fail(
+                            table.tag(),
+                            e.context(format!(
+                                "Failed to parse resource {:08X} at offset 0x{:x}",
+                                data.data_resource.resource_type.to_be(),
+                                data.data_resource.offset
+                            )),
+                        );
+                    }
+                }
+            }
+        }
+
+        let map_name = self.map_name(&think);
+        info!(
+            "Map {:x?} '{map_name}' - {} placement groups, {} decals",
+            think.map_name,
+            placement_groups.len(),
+            resource_points
+                .iter()
+                .filter(|r| r.resource.is_decal())
+                .count()
+        );
+
+        let resource_points = resource_points
+            .into_iter()
+            .map(|rp| {
+                let cb = ConstantBuffer::create(self.dcs.clone(), None)?;
+
+                Ok((rp, cb))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.load_entities(renderer, &resource_points, &mut material_map)?;
+        info!("{} lights", point_lights.len());
+
+        // TODO(cohae): Maybe not the best idea?
+        info!("Updating resource constant buffers");
+        for (rp, cb) in &resource_points {
+            if let Some(ent) = self.entity_renderers.get(&rp.entity) {
+                let mm = Mat4::from_scale_rotation_translation(
+                    Vec3::splat(rp.translation.w),
+                    rp.rotation.inverse(),
+                    Vec3::ZERO,
+                );
+                let model_matrix = Mat4::from_cols(
+                    mm.x_axis.truncate().extend(rp.translation.x),
+                    mm.y_axis.truncate().extend(rp.translation.y),
+                    mm.z_axis.truncate().extend(rp.translation.z),
+                    mm.w_axis,
+                );
+
+                cb.write(&ScopeRigidModel {
+                    mesh_to_world: model_matrix.transpose(),
+                    position_scale: ent.mesh_scale(),
+                    position_offset: ent.mesh_offset(),
+                    texcoord0_scale_offset: ent.texcoord_transform(),
+                    dynamic_sh_ao_values: Vec4::new(1.0, 1.0, 1.0, 0.0),
+                })?;
+            }
+        }
+
+        info!("Loading terrain");
+        info_span!("Loading terrain").in_scope(|| {
+            for (t, header) in terrain_headers.into_iter() {
+                if self.terrain_renderers.contains_key(&t.0) {
+                    continue;
+                }
+
+                let (result, dependencies) = renderer.render_data.track(|| {
+                    for t in &header.mesh_groups {
+                        renderer.render_data.load_texture(t.dyemap);
+                    }
+
+                    TerrainRenderer::load(header, self.dcs.clone(), renderer)
+                });
+
+                match result {
+                    Ok(renderer) => {
+                        self.terrain_renderers.insert(t.0, renderer);
+                        self.renderer_dependencies.insert(t, dependencies);
+                    }
+                    Err(e) => {
+                        error!("Failed to load terrain: {e}");
+                    }
+                }
+            }
+        });
+
+        self.load_placement_groups(renderer, &placement_groups, &mut material_map);
+        self.load_materials(renderer, material_map);
+
+        let cb_composite_lights =
+            ConstantBuffer::<Vec4>::create_array_init(self.dcs.clone(), &point_lights)?;
+
+        Ok(MapData {
+            hash,
+            name: map_name,
+            placement_groups,
+            resource_points,
+            terrains,
+            point_lights,
+            cb_composite_lights,
+            dependencies: MapDependencies::default(),
+        })
+    }
+
+    fn load_entities(
+        &mut self,
+        renderer: &Renderer,
+        resource_points: &[(ResourcePoint, ConstantBuffer<ScopeRigidModel>)],
+        material_map: &mut IntMap<TagHash, Material>,
+    ) -> anyhow::Result<()> {
+        let to_load_entities: IntSet<TagHash> = resource_points
+            .iter()
+            .map(|(r, _)| r.entity)
+            .filter(|v| v.is_valid() && !self.entity_renderers.contains_key(v))
+            .collect();
+
+        for te in &to_load_entities {
+            let (result, dependencies) = renderer
+                .render_data
+                .track(|| self.load_entity(renderer, *te, material_map));
+            if let Err(e) = result {
+                renderer.render_data.fail(*te, e);
+                continue;
+            }
+
+            if self.entity_renderers.contains_key(te) {
+                self.renderer_dependencies.insert(*te, dependencies);
+            } else {
+                warn!("Entity {te} does not contain any geometry!");
+            }
+        }
+
+        info!(
+            "Found {} entity models ({} new entities)",
+            self.entity_renderers.len(),
+            to_load_entities.len()
+        );
+
+        Ok(())
+    }
+
+    fn load_entity(
+        &mut self,
+        renderer: &Renderer,
+        te: TagHash,
+        material_map: &mut IntMap<TagHash, Material>,
+    ) -> anyhow::Result<()> {
+        let _span = debug_span!("Load entity", hash = %te).entered();
+        let header: Unk80809c0f = package_manager().read_tag_struct(te)?;
+        debug!("Loading entity {te}");
+        for e in &header.unk10 {
+            match e.unk0.unk10.resource_type {
+                0x808072b8 => {
+                    debug!(
+                        "\t- EntityModel {:08x}/{}",
+                        e.unk0.unk18.resource_type.to_be(),
+                        e.unk0.unk10.resource_type.to_be(),
+                    );
+                    let mut cur = Cursor::new(package_manager().read_tag(e.unk0.tag())?);
+                    cur.seek(SeekFrom::Start(e.unk0.unk18.offset + 0x1dc))?;
+                    let model: Tag<Unk808073a5> = cur.read_le()?;
+                    cur.seek(SeekFrom::Start(e.unk0.unk18.offset + 0x300))?;
+                    let entity_material_map: TablePointer<Unk808072c5> = cur.read_le()?;
+                    let materials: TablePointer<Tag<Unk808071e8>> = cur.read_le()?;
+
+                    for m in &materials {
+                        load_material(renderer, material_map, m.tag(), m.0.clone());
+                    }
+
+                    for m in &model.meshes {
+                        for p in &m.parts {
+                            if p.material.is_valid() {
+                                load_material_tag(renderer, material_map, p.material);
+                            }
+                        }
+                    }
+
+                    let er = EntityRenderer::load(
+                        model.0,
+                        entity_material_map.to_vec(),
+                        materials.iter().map(|m| m.tag()).collect_vec(),
+                        renderer,
+                        &self.dcs,
+                    )?;
+                    if self.entity_renderers.insert(te, er).is_some() {
+                        error!("More than 1 model was loaded for entity {te}");
+                    }
+                }
+                u => debug!(
+                    "\t- Unknown entity resource type {:08X}/{:08X} (table {})",
+                    u.to_be(),
+                    e.unk0.unk10.resource_type.to_be(),
+                    e.unk0.tag()
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads the statics used by the given placement groups, and creates their instance renderers
+    fn load_placement_groups(
+        &mut self,
+        renderer: &Renderer,
+        placement_groups: &[Tag<Unk8080966d>],
+        material_map: &mut IntMap<TagHash, Material>,
+    ) {
+        let mut to_load_statics: IntSet<TagHash> = Default::default();
+        let mut new_groups = vec![];
+        for placements in placement_groups {
+            if self.placement_groups.contains_key(&placements.tag().0) {
+                continue;
+            }
+
+            for v in &placements.statics {
+                if !self.static_map.contains_key(v) {
+                    to_load_statics.insert(*v);
+                }
+            }
+            self.placement_groups
+                .insert(placements.tag().0, (placements.0.clone(), vec![]));
+            new_groups.push(placements.tag().0);
+        }
+
+        info!("Loading statics");
+        info_span!("Loading statics").in_scope(|| {
+            for almostloadable in &to_load_statics {
+                let (result, dependencies) =
+                    renderer
+                        .render_data
+                        .track(|| -> anyhow::Result<StaticModel> {
+                            let mheader: Unk808071a7 =
+                                package_manager().read_tag_struct(*almostloadable)?;
+                            for m in &mheader.materials {
+                                if m.is_valid() {
+                                    load_material_tag(renderer, material_map, *m);
+                                }
+                            }
+                            for m in &mheader.unk20 {
+                                let m = m.material;
+                                if m.is_valid() {
+                                    load_material_tag(renderer, material_map, m);
+                                }
+                            }
+
+                            StaticModel::load(mheader, &self.dcs.device, renderer, *almostloadable)
+                        });
+
+                match result {
+                    Ok(model) => {
+                        self.static_map.insert(*almostloadable, Arc::new(model));
+                        self.renderer_dependencies
+                            .insert(*almostloadable, dependencies);
+                    }
+                    Err(e) => {
+                        renderer
+                            .render_data
+                            .fail(*almostloadable, e.context("Failed to load model"));
+                    }
+                }
+            }
+        });
+
+        info!("Loaded {} statics", self.static_map.len());
+
+        info_span!("Constructing instance renderers").in_scope(|| {
+            let mut total_instance_data = 0;
+            for group in &new_groups {
+                let (placements, renderers) = self.placement_groups.get_mut(group).unwrap();
+                for instance in &placements.instances {
+                    if let Some(model_hash) =
+                        placements.statics.iter().nth(instance.static_index as _)
+                    {
+                        let _span =
+                            debug_span!("Draw static instance", count = instance.instance_count, model = ?model_hash)
+                                .entered();
+
+                        let start = instance.instance_start as usize;
+                        let end = start + instance.instance_count as usize;
+                        let transforms = match placements
+                            .transforms
+                            .range(start..end)
+                            .collect::<BinResult<Vec<_>>>()
+                        {
+                            Ok(t) => t,
+                            Err(e) => {
+                                error!("Failed to read instance transforms: {e}");
+                                continue;
+                            }
+                        };
+
+                        let instance_renderer = match self.static_map.get(model_hash) {
+                            Some(model) => InstancedRenderer::load(
+                                model.clone(),
+                                &transforms,
+                                self.dcs.clone(),
+                            ),
+                            None => Err(anyhow::anyhow!("Couldn't get static model {model_hash}")),
+                        };
+
+                        match instance_renderer {
+                            Ok(r) => renderers.push(r),
+                            Err(e) => {
+                                error!("{e}");
+                                self.error_instances
+                                    .entry(*group)
+                                    .or_default()
+                                    .extend(transforms.iter().map(|t| t.transform()));
+                            }
+                        }
+
+                        total_instance_data += instance.instance_count as usize * 16 * 4;
+                    } else {
+                        error!("Couldn't get instance static #{}", instance.static_index);
+                    }
+                }
+            }
+            debug!("Total instance data: {}kb", total_instance_data / 1024);
+        });
+    }
+
+    /// Loads the shaders, textures and samplers of the given materials and hands the materials to the renderer
+    fn load_materials(&self, renderer: &Renderer, material_map: IntMap<TagHash, Material>) {
+        let mut to_load_samplers: IntSet<TagHash> = Default::default();
+        info_span!("Loading shaders").in_scope(|| {
+            for m in material_map.values() {
+                for sampler in m.vs_samplers.iter().chain(m.ps_samplers.iter()) {
+                    to_load_samplers.insert(sampler.sampler);
+                }
+
+                if package_manager().get_entry(m.vertex_shader).is_ok() {
+                    let _span = debug_span!("load vshader", shader = ?m.vertex_shader).entered();
+                    renderer
+                        .render_data
+                        .load_vshader(&self.dcs, m.vertex_shader);
+                }
+
+                if package_manager().get_entry(m.pixel_shader).is_ok() {
+                    let _span = debug_span!("load pshader", shader = ?m.pixel_shader).entered();
+                    renderer.render_data.load_pshader(&self.dcs, m.pixel_shader);
+                }
+            }
+        });
+
+        for m in material_map.values() {
+            for t in m.ps_textures.iter().chain(m.vs_textures.iter()) {
+                renderer.render_data.load_texture(t.texture);
+            }
+        }
+
+        let mut data = renderer.render_data.data_mut();
+        for s in to_load_samplers {
+            if data.samplers.contains_key(&s) {
+                continue;
+            }
+
+            let result = (|| -> anyhow::Result<_> {
+                let sampler_header_ref = package_manager().get_entry(s)?.reference;
+                let sampler_data = package_manager().read_tag(sampler_header_ref)?;
+
+                let sampler = unsafe {
+                    self.dcs
+                        .device
+                        .CreateSamplerState(sampler_data.as_ptr() as _)
+                        .context("Failed to create sampler state")?
+                };
+
+                Ok(sampler)
+            })();
+
+            match result {
+                Ok(sampler) => {
+                    data.samplers.insert(s, sampler);
+                }
+                Err(e) => {
+                    error!("Failed to load sampler {s}: {e:#}");
+                    data.failures.insert(s, format!("{e:#}"));
+                }
+            }
+        }
+
+        data.materials.extend(material_map);
+        info!(
+            "Loaded {} materials, {} vertex shaders, {} pixel shaders, {} samplers",
+            data.materials.len(),
+            data.vshaders.len(),
+            data.pshaders.len(),
+            data.samplers.len()
+        );
+    }
+}
+
+/// Whether `tag` was loaded by an earlier map or earlier in this load. Loaded materials can be
+/// in use by other maps, so they're shared instead of being loaded again
+fn is_material_loaded(
+    renderer: &Renderer,
+    material_map: &IntMap<TagHash, Material>,
+    tag: TagHash,
+) -> bool {
+    material_map.contains_key(&tag) || renderer.render_data.data().materials.contains_key(&tag)
+}
+
+/// Loads a material into `material_map`, recording it as a dependency of whatever is being loaded
+fn load_material(
+    renderer: &Renderer,
+    material_map: &mut IntMap<TagHash, Material>,
+    tag: TagHash,
+    material: Unk808071e8,
+) {
+    renderer.render_data.record(tag);
+    if is_material_loaded(renderer, material_map, tag) {
+        return;
+    }
+
+    match Material::load(renderer, material, tag, true) {
+        Ok(material) => {
+            material_map.insert(tag, material);
+        }
+        Err(e) => renderer.render_data.fail(tag, e),
+    }
+}
+
+/// Reads a material and loads it with [load_material]. Materials that can't be read are recorded
+/// as failed, drawcalls using them are skipped
+fn load_material_tag(
+    renderer: &Renderer,
+    material_map: &mut IntMap<TagHash, Material>,
+    tag: TagHash,
+) {
+    if is_material_loaded(renderer, material_map, tag) {
+        renderer.render_data.record(tag);
+        return;
+    }
+
+    match package_manager().read_tag_struct(tag) {
+        Ok(material) => load_material(renderer, material_map, tag, material),
+        Err(e) => {
+            renderer.render_data.record(tag);
+            renderer.render_data.fail(tag, e);
+        }
+    }
+}
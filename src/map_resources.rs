@@ -2,17 +2,20 @@ use crate::icons::{
     ICON_ACCOUNT_CONVERT, ICON_CHESS_PAWN, ICON_HELP, ICON_HELP_BOX_OUTLINE, ICON_LIGHTBULB_ON,
     ICON_SPHERE, ICON_STICKER, ICON_VOLUME_HIGH,
 };
+#[cfg(windows)]
 use crate::render::debug::DebugShapes;
-use crate::structure::{RelPointer, TablePointer};
+use crate::structure::{serialize_tag, serialize_tags, RelPointer, TablePointer};
 use crate::types::{DestinyHash, Vector4, AABB};
 use binrw::{BinRead, NullString};
 use destiny_pkg::TagHash;
+#[cfg(windows)]
 use glam::{Quat, Vec3, Vec4, Vec4Swizzles};
 use itertools::Itertools;
+use serde::Serialize;
 use std::io::SeekFrom;
 use strum::{EnumCount, EnumIs, EnumVariantNames};
 
-#[derive(Clone, EnumVariantNames, EnumCount, EnumIs)]
+#[derive(Debug, Clone, EnumVariantNames, EnumCount, EnumIs)]
 #[repr(u8)]
 pub enum MapResource {
     // PlacementGroup(TagHash),
@@ -113,6 +116,7 @@ impl MapResource {
         }
     }
 
+    #[cfg(windows)]
     pub fn draw_debug_shape(
         &self,
         translation: Vec4,
@@ -156,6 +160,7 @@ impl MapResource {
     }
 }
 
+#[cfg(windows)]
 fn darken_color(v: [u8; 3]) -> [u8; 3] {
     [
         (v[0] as f32 * 0.75) as u8,
@@ -203,30 +208,34 @@ pub struct Unk80806b7f {
 }
 
 /// Decal collection resource
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80806e68 {
     pub file_size: u64,
     pub instances: TablePointer<Unk80806e6c>,
     pub transforms: TablePointer<Vector4>, // 80806e6d
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80806e6c {
+    #[serde(serialize_with = "serialize_tag")]
     pub material: TagHash,
     pub start: u16,
     pub count: u16,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80806df3 {
     pub file_size: u64,
     pub unk8: TablePointer<Unk80806dec>,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80806dec {
+    #[serde(serialize_with = "serialize_tag")]
     pub material: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub index_buffer: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_buffer: TagHash,
     pub unkc: u32,
     pub unk10: [u32; 4],
@@ -239,15 +248,17 @@ pub struct Unk80806dec {
 }
 
 // Unknown resource (some kind of octree?)
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80807268 {
     pub file_size: u64,
     /// Vertex buffer
+    #[serde(serialize_with = "serialize_tag")]
     pub unk8: TagHash,
     pub unkc: u32,
     pub unk10: TablePointer<Unk8080726a>,
     pub unk20: [u32; 6],
     /// Vertex buffer
+    #[serde(serialize_with = "serialize_tag")]
     pub unk38: TagHash,
     pub unk3c: u32,
     pub unk40: TablePointer<Unk8080726a>,
@@ -255,38 +266,44 @@ pub struct Unk80807268 {
     pub unk60: TablePointer<u16>,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk8080726a {
     pub unk0: [u32; 4],
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk8080726d {
     pub unk0: Vector4,
     pub unk10: Vector4,
     pub unk20: Vector4,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80809162 {
     pub file_size: u64,
     pub unk8: TablePointer<Unk80809164>,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80809164 {
     pub unk0: Vector4,
     pub unk10: Vector4,
     pub unk20: [u32; 4],
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80809802 {
     pub file_size: u64,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk8: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub unkc: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk10: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub soundbank: TagHash,
+    #[serde(serialize_with = "serialize_tags")]
     pub streams: TablePointer<TagHash>,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk28: TagHash,
 }
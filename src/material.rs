@@ -1,18 +1,41 @@
+#[cfg(windows)]
 use std::ops::Deref;
 
+#[cfg(windows)]
 use crate::packages::package_manager;
+#[cfg(windows)]
 use crate::render::bytecode::interpreter::TfxBytecodeInterpreter;
+#[cfg(windows)]
 use crate::render::bytecode::opcodes::TfxBytecodeOp;
+#[cfg(windows)]
 use crate::render::renderer::Renderer;
+#[cfg(windows)]
 use crate::render::{ConstantBuffer, DeviceContextSwapchain, RenderData};
-use crate::structure::{RelPointer, TablePointer};
+use crate::structure::{
+    serialize_array, serialize_string_pointer, serialize_tag, RelPointer, TablePointer,
+};
 use crate::types::Vector4;
-use binrw::{BinRead, NullString};
+use crate::version;
+use binrw::{binread, BinRead, NullString};
 use destiny_pkg::TagHash;
+#[cfg(windows)]
 use glam::Vec4;
+use serde::Serialize;
+use std::io::SeekFrom;
+
+/// Offsets that differ between versions, see [crate::version::StructLayouts]
+#[derive(Debug)]
+pub struct MaterialLayout {
+    pub vertex_shader: u64,
+    pub pixel_shader: u64,
+}
 
-#[derive(BinRead, Debug, Clone)]
+#[binread]
+#[derive(Debug, Clone, Serialize)]
 pub struct Unk808071e8 {
+    #[br(temp, try_calc(version::layouts().map(|l| &l.material)))]
+    layout: &'static MaterialLayout,
+
     pub file_size: u64,
     /// 1 = normal
     /// 2 = depth prepass?
@@ -27,6 +50,8 @@ pub struct Unk808071e8 {
     pub unk24: u32,
     pub unk28: [u32; 8],
 
+    #[br(seek_before(SeekFrom::Start(layout.vertex_shader)))]
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_shader: TagHash,
     pub unk4c: u32,
     pub vs_textures: TablePointer<Unk80807211>,
@@ -37,9 +62,13 @@ pub struct Unk808071e8 {
     pub unk98: TablePointer<Vector4>,
     pub unka8: [u32; 9],
 
+    #[serde(serialize_with = "serialize_tag")]
     pub unkcc: TagHash,
+    #[serde(serialize_with = "serialize_array")]
     pub unkd0: [u32; 126],
 
+    #[br(seek_before(SeekFrom::Start(layout.pixel_shader)))]
+    #[serde(serialize_with = "serialize_tag")]
     pub pixel_shader: TagHash,
     pub unk2cc: u32,
     pub ps_textures: TablePointer<Unk80807211>,
@@ -51,24 +80,28 @@ pub struct Unk808071e8 {
     pub unk328: [u32; 9],
 
     /// Pointer to a float4 buffer, usually passed into cbuffer0
+    #[serde(serialize_with = "serialize_tag")]
     pub unk34c: TagHash,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80807211 {
     /// Material slot to assign to
     pub index: u32,
+    #[serde(serialize_with = "serialize_tag")]
     pub texture: TagHash,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk808073f3 {
+    #[serde(serialize_with = "serialize_tag")]
     pub sampler: TagHash,
     pub unk4: u32,
     pub unk8: u32,
     pub unkc: u32,
 }
 
+#[cfg(windows)]
 pub struct Material {
     pub mat: Unk808071e8,
     tag: TagHash,
@@ -79,23 +112,31 @@ pub struct Material {
     tfx_bytecode_ps: Option<TfxBytecodeInterpreter>,
 }
 
+#[cfg(windows)]
 impl Material {
+    /// Size of the constant buffers in bytes
+    pub fn size(&self) -> usize {
+        self.cb0_vs.as_ref().map(|cb| cb.size()).unwrap_or_default()
+            + self.cb0_ps.as_ref().map(|cb| cb.size()).unwrap_or_default()
+    }
+
     // TODO(cohae): load_shaders is a hack, i fucking hate locks
-    pub fn load(renderer: &Renderer, mat: Unk808071e8, tag: TagHash, load_shaders: bool) -> Self {
+    /// Constant buffers that fail to load are replaced with the default float4 cbuffer
+    pub fn load(
+        renderer: &Renderer,
+        mat: Unk808071e8,
+        tag: TagHash,
+        load_shaders: bool,
+    ) -> anyhow::Result<Self> {
         let _span = debug_span!("Load material", hash = %tag).entered();
         let cb0_vs = if mat.unkcc.is_valid() {
-            let buffer_header_ref = package_manager().get_entry(mat.unkcc).unwrap().reference;
-
-            let data_raw = package_manager().read_tag(buffer_header_ref).unwrap();
-            let data = bytemuck::cast_slice(&data_raw);
-
-            trace!(
-                "Read {} elements cbuffer from {buffer_header_ref:?}",
-                data.len()
-            );
-            let buf = ConstantBuffer::create_array_init(renderer.dcs.clone(), data).unwrap();
-
-            Some(buf)
+            match Self::load_cbuffer(renderer, mat.unkcc) {
+                Ok(buf) => Some(buf),
+                Err(e) => {
+                    renderer.render_data.fail(mat.unkcc, e);
+                    Some(Self::default_cbuffer(renderer)?)
+                }
+            }
         } else if mat.unk98.len() > 1
             && mat
                 .unk98
@@ -106,34 +147,21 @@ impl Material {
             let buf = ConstantBuffer::create_array_init(
                 renderer.dcs.clone(),
                 bytemuck::cast_slice(&mat.unk98),
-            )
-            .unwrap();
+            )?;
 
             Some(buf)
         } else {
-            trace!("Loading default float4 cbuffer");
-            let buf = ConstantBuffer::create_array_init(
-                renderer.dcs.clone(),
-                &[Vec4::new(1.0, 1.0, 1.0, 1.0)],
-            )
-            .unwrap();
-
-            Some(buf)
+            Some(Self::default_cbuffer(renderer)?)
         };
 
         let cb0_ps = if mat.unk34c.is_valid() {
-            let buffer_header_ref = package_manager().get_entry(mat.unk34c).unwrap().reference;
-
-            let data_raw = package_manager().read_tag(buffer_header_ref).unwrap();
-
-            let data = bytemuck::cast_slice(&data_raw);
-            trace!(
-                "Read {} elements cbuffer from {buffer_header_ref:?}",
-                data.len()
-            );
-            let buf = ConstantBuffer::create_array_init(renderer.dcs.clone(), data).unwrap();
-
-            Some(buf)
+            match Self::load_cbuffer(renderer, mat.unk34c) {
+                Ok(buf) => Some(buf),
+                Err(e) => {
+                    renderer.render_data.fail(mat.unk34c, e);
+                    Some(Self::default_cbuffer(renderer)?)
+                }
+            }
         } else if !mat.unk318.is_empty()
             && mat
                 .unk318
@@ -144,8 +172,7 @@ impl Material {
             let buf = ConstantBuffer::create_array_init(
                 renderer.dcs.clone(),
                 bytemuck::cast_slice(&mat.unk318),
-            )
-            .unwrap();
+            )?;
 
             Some(buf)
         } else {
@@ -170,14 +197,33 @@ impl Material {
             .ok()
             .map(TfxBytecodeInterpreter::new);
 
-        Self {
+        Ok(Self {
             mat,
             tag,
             cb0_vs,
             tfx_bytecode_vs,
             cb0_ps,
             tfx_bytecode_ps,
-        }
+        })
+    }
+
+    fn load_cbuffer(renderer: &Renderer, tag: TagHash) -> anyhow::Result<ConstantBuffer<Vec4>> {
+        let buffer_header_ref = package_manager().get_entry(tag)?.reference;
+
+        let data_raw = package_manager().read_tag(buffer_header_ref)?;
+        let data = bytemuck::try_cast_slice(&data_raw)
+            .map_err(|e| anyhow::anyhow!("Invalid cbuffer data in {buffer_header_ref:?}: {e:?}"))?;
+
+        trace!(
+            "Read {} elements cbuffer from {buffer_header_ref:?}",
+            data.len()
+        );
+        ConstantBuffer::create_array_init(renderer.dcs.clone(), data)
+    }
+
+    fn default_cbuffer(renderer: &Renderer) -> anyhow::Result<ConstantBuffer<Vec4>> {
+        trace!("Loading default float4 cbuffer");
+        ConstantBuffer::create_array_init(renderer.dcs.clone(), &[Vec4::new(1.0, 1.0, 1.0, 1.0)])
     }
 
     // pub fn tag(&self) -> TagHash {
@@ -232,16 +278,14 @@ impl Material {
             }
 
             for p in &self.vs_textures {
-                // TODO(cohae): Bind error texture on error
-                if let Some(t) = render_data.textures.get(&p.texture) {
+                if let Some(t) = render_data.texture_or_error(p.texture) {
                     dcs.context()
                         .VSSetShaderResources(p.index, Some(&[Some(t.view.clone())]));
                 }
             }
 
             for p in &self.ps_textures {
-                // TODO(cohae): Bind error texture on error
-                if let Some(t) = render_data.textures.get(&p.texture) {
+                if let Some(t) = render_data.texture_or_error(p.texture) {
                     dcs.context()
                         .PSSetShaderResources(p.index, Some(&[Some(t.view.clone())]));
                 }
@@ -310,6 +354,7 @@ impl Material {
     }
 }
 
+#[cfg(windows)]
 impl Deref for Material {
     type Target = Unk808071e8;
 
@@ -317,22 +362,28 @@ impl Deref for Material {
         &self.mat
     }
 }
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80806cb1 {
     pub file_size: u64,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk8: TagHash,
     pub unkc: u32,
     pub unk10: TablePointer<Unk80806cb6>,
     pub unk20: TablePointer<Unk80806cb5>,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk30: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk34: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk38: TagHash,
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80806cb5 {
+    #[serde(serialize_with = "serialize_string_pointer")]
     pub name: RelPointer<NullString>,
     pub unk8: u32,
+    #[serde(serialize_with = "serialize_tag")]
     pub unkc: TagHash,
 }
 
@@ -0,0 +1,198 @@
+//! CPU-side mesh data, for consumers that can't (or shouldn't) go through the GPU buffers
+
+pub mod stats;
+pub mod strip;
+
+use std::io::Cursor;
+
+use binrw::BinReaderExt;
+use destiny_pkg::TagHash;
+use glam::Vec3;
+
+use crate::entity::{EPrimitiveType, IndexBufferHeader, Unk808073a5, VertexBufferHeader};
+use crate::map::Unk8080714f;
+use crate::packages::package_manager;
+use crate::statics::{Unk80807194, Unk808071a7};
+use crate::types::Vector3;
+
+pub struct MeshPart {
+    pub material: TagHash,
+    /// Triangle list indices into [MeshData::positions]
+    pub indices: Vec<u32>,
+}
+
+/// A single vertex/index buffer pair with all of its (highest detail) parts
+pub struct MeshData {
+    pub vertex_buffer: TagHash,
+    pub index_buffer: TagHash,
+    /// Positions in model space, with the model scale and offset applied
+    pub positions: Vec<Vec3>,
+    pub parts: Vec<MeshPart>,
+}
+
+impl MeshData {
+    pub fn triangle_count(&self) -> usize {
+        self.parts.iter().map(|p| p.indices.len() / 3).sum()
+    }
+}
+
+/// Reads vertex positions from a vertex buffer, applying `scale` and `offset` to each of them
+pub fn read_positions(buffer: TagHash, scale: Vec3, offset: Vec3) -> anyhow::Result<Vec<Vec3>> {
+    let header: VertexBufferHeader = package_manager().read_tag_struct(buffer)?;
+    let data = package_manager().read_tag(package_manager().get_entry(buffer)?.reference)?;
+
+    let stride = header.stride as usize;
+    anyhow::ensure!(
+        stride >= 6,
+        "Vertex buffer {buffer} has an invalid stride ({stride})"
+    );
+
+    let mut cur = Cursor::new(&data);
+    let mut positions = Vec::with_capacity(data.len() / stride);
+    for i in 0..(data.len() / stride) {
+        cur.set_position((i * stride) as u64);
+
+        // Buffers with these strides store their positions as 32-bit floats
+        let position: Vector3 = if stride == 24 || stride == 48 {
+            cur.read_le::<[f32; 3]>()?.into()
+        } else {
+            cur.read_le::<[i16; 3]>()?.into()
+        };
+
+        positions.push(Vec3::new(position.x, position.y, position.z) * scale + offset);
+    }
+
+    Ok(positions)
+}
+
+/// Reads the header and raw index data of an index buffer
+pub fn read_index_buffer(buffer: TagHash) -> anyhow::Result<(IndexBufferHeader, Vec<u8>)> {
+    let header: IndexBufferHeader = package_manager().read_tag_struct(buffer)?;
+    let data = package_manager().read_tag(package_manager().get_entry(buffer)?.reference)?;
+
+    Ok((header, data))
+}
+
+pub fn load_static(model: &Unk808071a7) -> anyhow::Result<Vec<MeshData>> {
+    let header: Unk80807194 = package_manager().read_tag_struct(model.unk8)?;
+
+    let scale = Vec3::splat(model.model_scale);
+    let offset = Vec3::new(
+        model.model_offset.x,
+        model.model_offset.y,
+        model.model_offset.z,
+    );
+
+    let mut meshes = vec![];
+    for (buffer_index, (index_buffer, vertex_buffer, _, _)) in header.buffers.iter().enumerate() {
+        let (index_header, index_data) = read_index_buffer(*index_buffer)?;
+        let mut parts = vec![];
+        for (iu, u) in header
+            .mesh_groups
+            .iter()
+            .enumerate()
+            .filter(|(_, u)| u.unk2 == 0)
+        {
+            let p = &header.parts[u.part_index as usize];
+            if p.buffer_index as usize != buffer_index || !p.lod_category.is_highest_detail() {
+                continue;
+            }
+
+            parts.push(MeshPart {
+                material: model
+                    .materials
+                    .get(iu)
+                    .cloned()
+                    .unwrap_or(TagHash(u32::MAX)),
+                indices: strip::triangulate(
+                    &index_header,
+                    &index_data,
+                    p.index_start,
+                    p.index_count,
+                    p.primitive_type,
+                )?,
+            });
+        }
+
+        meshes.push(MeshData {
+            vertex_buffer: *vertex_buffer,
+            index_buffer: *index_buffer,
+            positions: read_positions(*vertex_buffer, scale, offset)?,
+            parts,
+        });
+    }
+
+    Ok(meshes)
+}
+
+pub fn load_entity_model(model: &Unk808073a5) -> anyhow::Result<Vec<MeshData>> {
+    let scale = Vec3::new(
+        model.model_scale.x,
+        model.model_scale.y,
+        model.model_scale.z,
+    );
+    let offset = Vec3::new(
+        model.model_offset.x,
+        model.model_offset.y,
+        model.model_offset.z,
+    );
+
+    let mut meshes = vec![];
+    for mesh in &model.meshes {
+        let (index_header, index_data) = read_index_buffer(mesh.index_buffer)?;
+        let mut parts = vec![];
+        for p in mesh
+            .parts
+            .iter()
+            .filter(|p| p.lod_category.is_highest_detail())
+        {
+            parts.push(MeshPart {
+                material: p.material,
+                indices: strip::triangulate(
+                    &index_header,
+                    &index_data,
+                    p.index_start,
+                    p.index_count,
+                    p.primitive_type,
+                )?,
+            });
+        }
+
+        meshes.push(MeshData {
+            vertex_buffer: mesh.vertex_buffer1,
+            index_buffer: mesh.index_buffer,
+            positions: read_positions(mesh.vertex_buffer1, scale, offset)?,
+            parts,
+        });
+    }
+
+    Ok(meshes)
+}
+
+pub fn load_terrain(terrain: &Unk8080714f) -> anyhow::Result<MeshData> {
+    // unk30 is passed to the terrain scope as the position offset (xyz) and scale (w)
+    let scale = Vec3::splat(terrain.unk30.w);
+    let offset = Vec3::new(terrain.unk30.x, terrain.unk30.y, terrain.unk30.z);
+
+    let (index_header, index_data) = read_index_buffer(terrain.indices)?;
+    let mut parts = vec![];
+    for p in terrain.mesh_parts.iter().filter(|p| p.detail_level == 0) {
+        parts.push(MeshPart {
+            material: p.material,
+            indices: strip::triangulate(
+                &index_header,
+                &index_data,
+                p.index_start,
+                p.index_count as u32,
+                EPrimitiveType::TriangleStrip,
+            )?,
+        });
+    }
+
+    Ok(MeshData {
+        vertex_buffer: terrain.vertex_buffer,
+        index_buffer: terrain.indices,
+        positions: read_positions(terrain.vertex_buffer, scale, offset)?,
+        parts,
+    })
+}
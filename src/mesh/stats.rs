@@ -0,0 +1,420 @@
+//! Per-model geometry statistics, used to track down the heaviest props in a map
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use destiny_pkg::TagHash;
+use glam::Vec3;
+#[cfg(windows)]
+use nohash_hasher::IntMap;
+use nohash_hasher::IntSet;
+
+use crate::entity::{
+    read_entity_model, ELodCategory, EPrimitiveType, Unk808073a5, VertexBufferHeader,
+};
+#[cfg(windows)]
+use crate::map::MapData;
+use crate::packages::package_manager;
+use crate::statics::{Unk80807194, Unk808071a7};
+
+use super::{read_index_buffer, read_positions, strip};
+
+#[derive(Default, Clone)]
+pub struct LodStats {
+    pub parts: usize,
+    /// Unique vertices referenced by the parts of this LOD
+    pub vertices: usize,
+    pub triangles: usize,
+}
+
+#[derive(Clone)]
+pub struct ModelStats {
+    pub tag: TagHash,
+    pub lods: BTreeMap<ELodCategory, LodStats>,
+    pub vertex_buffer_size: u64,
+    pub index_buffer_size: u64,
+    pub material_count: usize,
+    /// Model space bounds of all vertices
+    pub bb_min: Vec3,
+    pub bb_max: Vec3,
+}
+
+impl ModelStats {
+    fn new(tag: TagHash) -> Self {
+        Self {
+            tag,
+            lods: BTreeMap::new(),
+            vertex_buffer_size: 0,
+            index_buffer_size: 0,
+            material_count: 0,
+            bb_min: Vec3::splat(f32::INFINITY),
+            bb_max: Vec3::splat(f32::NEG_INFINITY),
+        }
+    }
+
+    /// Triangle count of the highest detail LODs, which is what ends up being drawn
+    pub fn highest_detail_triangles(&self) -> usize {
+        self.lods
+            .iter()
+            .filter(|(l, _)| l.is_highest_detail())
+            .map(|(_, s)| s.triangles)
+            .sum()
+    }
+
+    pub fn buffer_size(&self) -> u64 {
+        self.vertex_buffer_size + self.index_buffer_size
+    }
+
+    fn add_vertex_buffer_size(&mut self, buffer: TagHash) -> anyhow::Result<()> {
+        if buffer.is_valid() {
+            let header: VertexBufferHeader = package_manager().read_tag_struct(buffer)?;
+            self.vertex_buffer_size += header.data_size as u64;
+        }
+
+        Ok(())
+    }
+
+    fn add_vertex_buffer(
+        &mut self,
+        buffer: TagHash,
+        scale: Vec3,
+        offset: Vec3,
+    ) -> anyhow::Result<()> {
+        if !buffer.is_valid() {
+            return Ok(());
+        }
+
+        self.add_vertex_buffer_size(buffer)?;
+        for p in read_positions(buffer, scale, offset)? {
+            self.bb_min = self.bb_min.min(p);
+            self.bb_max = self.bb_max.max(p);
+        }
+
+        Ok(())
+    }
+
+    /// Adds the parts of a single index buffer, given as `(lod, index_start, index_count, primitive_type)`
+    fn add_parts(
+        &mut self,
+        index_buffer: TagHash,
+        parts: impl Iterator<Item = (ELodCategory, u32, u32, EPrimitiveType)>,
+    ) -> anyhow::Result<()> {
+        let (header, data) = read_index_buffer(index_buffer)?;
+        self.index_buffer_size += header.data_size;
+
+        let parts = parts
+            .map(|(lod, start, count, primitive_type)| {
+                strip::triangulate(&header, &data, start, count, primitive_type)
+                    .map(|indices| (lod, indices))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.add_triangle_lists(parts);
+
+        Ok(())
+    }
+
+    /// Adds the parts of a single index buffer, given as `(lod, triangle list)`
+    fn add_triangle_lists(&mut self, parts: impl IntoIterator<Item = (ELodCategory, Vec<u32>)>) {
+        let mut lod_vertices: BTreeMap<ELodCategory, IntSet<u32>> = BTreeMap::new();
+        for (lod, indices) in parts {
+            let s = self.lods.entry(lod).or_default();
+            s.parts += 1;
+            s.triangles += indices.len() / 3;
+
+            lod_vertices.entry(lod).or_default().extend(indices);
+        }
+
+        for (lod, vertices) in lod_vertices {
+            self.lods.entry(lod).or_default().vertices += vertices.len();
+        }
+    }
+}
+
+pub fn static_stats(tag: TagHash, model: &Unk808071a7) -> anyhow::Result<ModelStats> {
+    let header: Unk80807194 = package_manager().read_tag_struct(model.unk8)?;
+    let mut stats = ModelStats::new(tag);
+    stats.material_count = model.materials.len();
+
+    let scale = Vec3::splat(model.model_scale);
+    let offset = Vec3::new(
+        model.model_offset.x,
+        model.model_offset.y,
+        model.model_offset.z,
+    );
+
+    for (buffer_index, (index_buffer, vertex_buffer, vertex_buffer2, _)) in
+        header.buffers.iter().enumerate()
+    {
+        stats.add_vertex_buffer(*vertex_buffer, scale, offset)?;
+        stats.add_vertex_buffer_size(*vertex_buffer2)?;
+
+        stats.add_parts(
+            *index_buffer,
+            header
+                .mesh_groups
+                .iter()
+                .filter(|u| u.unk2 == 0)
+                .map(|u| &header.parts[u.part_index as usize])
+                .filter(|p| p.buffer_index as usize == buffer_index)
+                .map(|p| {
+                    (
+                        p.lod_category,
+                        p.index_start,
+                        p.index_count,
+                        p.primitive_type,
+                    )
+                }),
+        )?;
+    }
+
+    Ok(stats)
+}
+
+pub fn entity_model_stats(tag: TagHash, model: &Unk808073a5) -> anyhow::Result<ModelStats> {
+    let mut stats = ModelStats::new(tag);
+
+    let scale = Vec3::new(
+        model.model_scale.x,
+        model.model_scale.y,
+        model.model_scale.z,
+    );
+    let offset = Vec3::new(
+        model.model_offset.x,
+        model.model_offset.y,
+        model.model_offset.z,
+    );
+
+    let mut materials: IntSet<TagHash> = IntSet::default();
+    for mesh in &model.meshes {
+        stats.add_vertex_buffer(mesh.vertex_buffer1, scale, offset)?;
+        stats.add_vertex_buffer_size(mesh.vertex_buffer2)?;
+
+        materials.extend(mesh.parts.iter().map(|p| p.material));
+        stats.add_parts(
+            mesh.index_buffer,
+            mesh.parts.iter().map(|p| {
+                (
+                    p.lod_category,
+                    p.index_start,
+                    p.index_count,
+                    p.primitive_type,
+                )
+            }),
+        )?;
+    }
+    stats.material_count = materials.len();
+
+    Ok(stats)
+}
+
+/// Writes a per-LOD breakdown of a single model
+pub fn write_model_report(out: &mut String, stats: &ModelStats) {
+    writeln!(
+        out,
+        "{} - {} materials, {:.1}KiB vertex data, {:.1}KiB index data",
+        stats.tag,
+        stats.material_count,
+        stats.vertex_buffer_size as f32 / 1024.0,
+        stats.index_buffer_size as f32 / 1024.0
+    )
+    .ok();
+    writeln!(
+        out,
+        "  AABB ({:.2}, {:.2}, {:.2}) - ({:.2}, {:.2}, {:.2})",
+        stats.bb_min.x,
+        stats.bb_min.y,
+        stats.bb_min.z,
+        stats.bb_max.x,
+        stats.bb_max.y,
+        stats.bb_max.z
+    )
+    .ok();
+    for (lod, s) in &stats.lods {
+        writeln!(
+            out,
+            "  {:<12} {:>4} parts {:>8} vertices {:>8} triangles",
+            format!("{lod:?}"),
+            s.parts,
+            s.vertices,
+            s.triangles
+        )
+        .ok();
+    }
+}
+
+/// Gathers statistics for a static (0x808071a7) or entity (0x80809c0f) and returns them as a readable report
+pub fn tag_report(tag: TagHash) -> anyhow::Result<String> {
+    let entry = package_manager().get_entry(tag)?;
+    let stats = match entry.reference {
+        0x808071a7 => static_stats(tag, &package_manager().read_tag_struct(tag)?)?,
+        0x80809c0f => {
+            let Some(model) = read_entity_model(tag)? else {
+                anyhow::bail!("Entity {tag} does not have a model");
+            };
+
+            entity_model_stats(tag, &model)?
+        }
+        u => anyhow::bail!("Tag {tag} has an unsupported reference type {u:08x}"),
+    };
+
+    let mut out = String::new();
+    write_model_report(&mut out, &stats);
+    Ok(out)
+}
+
+#[cfg(windows)]
+pub struct MapModelStats {
+    pub stats: ModelStats,
+    pub is_entity: bool,
+    pub instances: usize,
+}
+
+#[cfg(windows)]
+impl MapModelStats {
+    pub fn total_triangles(&self) -> usize {
+        self.stats.highest_detail_triangles() * self.instances
+    }
+}
+
+/// Gathers statistics for every static and entity model in a map, sorted by the total amount of triangles they contribute
+#[cfg(windows)]
+pub fn map_stats(map: &MapData) -> Vec<MapModelStats> {
+    let mut models: IntMap<TagHash, Option<MapModelStats>> = IntMap::default();
+
+    for group in &map.placement_groups {
+        for instance in &group.instances {
+            let Some(model_hash) = group.statics.get(instance.static_index as usize) else {
+                continue;
+            };
+
+            let model = models.entry(*model_hash).or_insert_with(|| {
+                package_manager()
+                    .read_tag_struct::<Unk808071a7>(*model_hash)
+                    .and_then(|m| static_stats(*model_hash, &m))
+                    .map(|stats| MapModelStats {
+                        stats,
+                        is_entity: false,
+                        instances: 0,
+                    })
+                    .map_err(|e| error!("Failed to read static {model_hash}: {e}"))
+                    .ok()
+            });
+
+            if let Some(model) = model {
+                model.instances += instance.instance_count as usize;
+            }
+        }
+    }
+
+    for (rp, _) in &map.resource_points {
+        if !rp.entity.is_valid() {
+            continue;
+        }
+
+        let model = models
+            .entry(rp.entity)
+            .or_insert_with(|| match read_entity_model(rp.entity) {
+                Ok(Some(m)) => entity_model_stats(rp.entity, &m)
+                    .map(|stats| MapModelStats {
+                        stats,
+                        is_entity: true,
+                        instances: 0,
+                    })
+                    .map_err(|e| error!("Failed to read entity {}: {e}", rp.entity))
+                    .ok(),
+                Ok(None) => None,
+                Err(e) => {
+                    error!("Failed to read entity {}: {e}", rp.entity);
+                    None
+                }
+            });
+
+        if let Some(model) = model {
+            model.instances += 1;
+        }
+    }
+
+    let mut models: Vec<MapModelStats> = models.into_values().flatten().collect();
+    models.sort_by_key(|m| std::cmp::Reverse(m.total_triangles()));
+
+    models
+}
+
+/// Returns a map-wide summary followed by the per-model breakdown, heaviest models first
+#[cfg(windows)]
+pub fn map_report(map: &MapData) -> String {
+    let models = map_stats(map);
+
+    let mut out = String::new();
+    writeln!(out, "Map '{}' ({})", map.name, map.hash).ok();
+    writeln!(
+        out,
+        "{} statics, {} entities, {} instances, {} triangles, {:.1}MiB buffer data\n",
+        models.iter().filter(|m| !m.is_entity).count(),
+        models.iter().filter(|m| m.is_entity).count(),
+        models.iter().map(|m| m.instances).sum::<usize>(),
+        models.iter().map(|m| m.total_triangles()).sum::<usize>(),
+        models.iter().map(|m| m.stats.buffer_size()).sum::<u64>() as f32 / 1024.0 / 1024.0
+    )
+    .ok();
+
+    writeln!(
+        out,
+        "{:<8} {:<10} {:>9} {:>10} {:>12}",
+        "Kind", "Model", "Instances", "Triangles", "Total"
+    )
+    .ok();
+    for m in &models {
+        writeln!(
+            out,
+            "{:<8} {:<10} {:>9} {:>10} {:>12}",
+            if m.is_entity { "entity" } else { "static" },
+            m.stats.tag.to_string(),
+            m.instances,
+            m.stats.highest_detail_triangles(),
+            m.total_triangles()
+        )
+        .ok();
+    }
+
+    writeln!(out).ok();
+    for m in &models {
+        write_model_report(&mut out, &m.stats);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_counts() {
+        let mut stats = ModelStats::new(TagHash(u32::MAX));
+        stats.add_triangle_lists([
+            (ELodCategory::Lod_0_0, vec![0, 1, 2, 2, 1, 3]),
+            // Shares vertices 2 and 3 with the first part
+            (ELodCategory::Lod_0_0, vec![2, 3, 4]),
+            (ELodCategory::Lod_1_0, vec![0, 1, 2]),
+        ]);
+
+        let lod0 = &stats.lods[&ELodCategory::Lod_0_0];
+        assert_eq!((lod0.parts, lod0.triangles, lod0.vertices), (2, 3, 5));
+        let lod1 = &stats.lods[&ELodCategory::Lod_1_0];
+        assert_eq!((lod1.parts, lod1.triangles, lod1.vertices), (1, 1, 3));
+
+        assert_eq!(stats.highest_detail_triangles(), 3);
+    }
+
+    #[test]
+    fn vertices_per_index_buffer() {
+        // Index buffers don't share vertices, so the same indices in another buffer are counted again
+        let mut stats = ModelStats::new(TagHash(u32::MAX));
+        stats.add_triangle_lists([(ELodCategory::Lod_0_0, vec![0, 1, 2])]);
+        stats.add_triangle_lists([(ELodCategory::Lod_0_0, vec![0, 1, 2])]);
+
+        let lod0 = &stats.lods[&ELodCategory::Lod_0_0];
+        assert_eq!((lod0.parts, lod0.triangles, lod0.vertices), (2, 2, 6));
+    }
+}
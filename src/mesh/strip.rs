@@ -0,0 +1,236 @@
+//! Triangle strip to triangle list conversion
+
+use crate::entity::{EPrimitiveType, IndexBufferHeader};
+
+pub trait StripIndex: Copy + Eq {
+    /// Primitive restart value for this index width
+    const RESTART: Self;
+
+    fn widen(self) -> u32;
+}
+
+impl StripIndex for u16 {
+    const RESTART: Self = u16::MAX;
+
+    fn widen(self) -> u32 {
+        self as u32
+    }
+}
+
+impl StripIndex for u32 {
+    const RESTART: Self = u32::MAX;
+
+    fn widen(self) -> u32 {
+        self
+    }
+}
+
+fn is_degenerate(a: u32, b: u32, c: u32) -> bool {
+    a == b || b == c || a == c
+}
+
+/// Converts a triangle strip into a triangle list.
+///
+/// Restart indices end the current strip, and degenerate triangles (commonly used to stitch strips together) are dropped.
+/// Every odd triangle in a strip has its first two vertices swapped to keep the winding order consistent.
+pub fn strip_to_list<I: StripIndex>(strip: &[I]) -> Vec<u32> {
+    let mut list = Vec::with_capacity(strip.len().saturating_sub(2) * 3);
+
+    for run in strip.split(|i| *i == I::RESTART) {
+        for (i, t) in run.windows(3).enumerate() {
+            let (a, b, c) = (t[0].widen(), t[1].widen(), t[2].widen());
+            if is_degenerate(a, b, c) {
+                continue;
+            }
+
+            if i % 2 == 0 {
+                list.extend([a, b, c]);
+            } else {
+                list.extend([b, a, c]);
+            }
+        }
+    }
+
+    list
+}
+
+/// Widens a triangle list, dropping any degenerate triangles
+pub fn list_to_list<I: StripIndex>(list: &[I]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(list.len());
+    for t in list.chunks_exact(3) {
+        let (a, b, c) = (t[0].widen(), t[1].widen(), t[2].widen());
+        if !is_degenerate(a, b, c) {
+            out.extend([a, b, c]);
+        }
+    }
+
+    out
+}
+
+/// Converts a range of raw index buffer data into a triangle list, using the index width from `header`
+pub fn triangulate(
+    header: &IndexBufferHeader,
+    data: &[u8],
+    index_start: u32,
+    index_count: u32,
+    primitive_type: EPrimitiveType,
+) -> anyhow::Result<Vec<u32>> {
+    let index_size = if header.is_32bit { 4 } else { 2 };
+    // Part data can be corrupt, so the range is computed in usize to keep it from overflowing
+    let start = index_start as usize;
+    let end = start + index_count as usize;
+    let range = start * index_size..end * index_size;
+    let Some(data) = data.get(range.clone()) else {
+        anyhow::bail!(
+            "Index range {range:?} is out of bounds (buffer is {} bytes)",
+            data.len()
+        );
+    };
+
+    Ok(if header.is_32bit {
+        triangulate_indices(
+            &read_indices::<u32, 4>(data, u32::from_le_bytes),
+            primitive_type,
+        )
+    } else {
+        triangulate_indices(
+            &read_indices::<u16, 2>(data, u16::from_le_bytes),
+            primitive_type,
+        )
+    })
+}
+
+pub fn triangulate_indices<I: StripIndex>(
+    indices: &[I],
+    primitive_type: EPrimitiveType,
+) -> Vec<u32> {
+    match primitive_type {
+        EPrimitiveType::Triangles => list_to_list(indices),
+        EPrimitiveType::TriangleStrip => strip_to_list(indices),
+    }
+}
+
+fn read_indices<I, const N: usize>(data: &[u8], f: fn([u8; N]) -> I) -> Vec<I> {
+    data.chunks_exact(N)
+        .map(|c| f(c.try_into().unwrap()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use binrw::BinReaderExt;
+
+    use super::*;
+
+    #[test]
+    fn strip_winding() {
+        let strip: [u16; 5] = [0, 1, 2, 3, 4];
+        assert_eq!(strip_to_list(&strip), vec![0, 1, 2, 2, 1, 3, 2, 3, 4]);
+    }
+
+    #[test]
+    fn strip_too_short() {
+        assert!(strip_to_list::<u16>(&[]).is_empty());
+        assert!(strip_to_list::<u16>(&[0, 1]).is_empty());
+    }
+
+    #[test]
+    fn strip_degenerates() {
+        // Two strips (0 1 2 3) and (4 5 6 7) stitched together with degenerate triangles
+        let strip: [u32; 10] = [0, 1, 2, 3, 3, 4, 4, 5, 6, 7];
+        assert_eq!(
+            strip_to_list(&strip),
+            vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7]
+        );
+    }
+
+    #[test]
+    fn strip_degenerates_odd() {
+        // Stitching an odd length strip takes an extra degenerate to keep the winding
+        let strip: [u16; 9] = [0, 1, 2, 2, 2, 3, 3, 4, 5];
+        assert_eq!(strip_to_list(&strip), vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn strip_restart() {
+        let strip: [u16; 8] = [0, 1, 2, 3, u16::MAX, 4, 5, 6];
+        assert_eq!(strip_to_list(&strip), vec![0, 1, 2, 2, 1, 3, 4, 5, 6]);
+
+        let strip: [u32; 8] = [0, 1, 2, 3, u32::MAX, 4, 5, 6];
+        assert_eq!(strip_to_list(&strip), vec![0, 1, 2, 2, 1, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn strip_restart_16bit_value_in_32bit() {
+        // 0xffff is a regular index in a 32-bit buffer
+        let strip: [u32; 4] = [0, 1, 0xffff, 2];
+        assert_eq!(strip_to_list(&strip), vec![0, 1, 0xffff, 0xffff, 1, 2]);
+    }
+
+    #[test]
+    fn list_degenerates() {
+        let list: [u16; 9] = [0, 1, 2, 3, 3, 4, 4, 5, 6];
+        assert_eq!(list_to_list(&list), vec![0, 1, 2, 4, 5, 6]);
+    }
+
+    fn header(is_32bit: bool) -> IndexBufferHeader {
+        let mut data = vec![0u8, is_32bit as u8, 0, 0, 0, 0, 0, 0];
+        data.extend(0u64.to_le_bytes());
+        data.extend(0xdeadbeefu32.to_le_bytes());
+        data.extend(0u32.to_le_bytes());
+        Cursor::new(data).read_le().unwrap()
+    }
+
+    #[test]
+    fn triangulate_range() {
+        let data: Vec<u8> = [0u16, 1, 2, 3]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+
+        assert_eq!(
+            triangulate(&header(false), &data, 1, 3, EPrimitiveType::Triangles).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert!(triangulate(&header(false), &data, 2, 3, EPrimitiveType::Triangles).is_err());
+        assert!(triangulate(&header(true), &data, 0, 3, EPrimitiveType::Triangles).is_err());
+        assert!(triangulate(
+            &header(false),
+            &data,
+            u32::MAX,
+            3,
+            EPrimitiveType::Triangles
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn raw_indices() {
+        let data_16: Vec<u8> = [0u16, 1, 2, 3]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let data_32: Vec<u8> = [0u32, 1, 2, 3]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+
+        assert_eq!(
+            read_indices::<u16, 2>(&data_16, u16::from_le_bytes),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(
+            read_indices::<u32, 4>(&data_32, u32::from_le_bytes),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(
+            triangulate_indices(
+                &read_indices::<u16, 2>(&data_16[2..], u16::from_le_bytes),
+                EPrimitiveType::TriangleStrip
+            ),
+            vec![1, 2, 3]
+        );
+    }
+}
@@ -2,6 +2,7 @@ use destiny_pkg::TagHash;
 use strum::{EnumCount, VariantNames};
 use winit::window::Window;
 
+use crate::config;
 use crate::icons::ICON_BUG;
 use crate::map_resources::MapResource;
 use crate::resources::Resources;
@@ -10,10 +11,7 @@ use crate::FpsCamera;
 use super::gui::OverlayProvider;
 
 pub struct CameraPositionOverlay {
-    pub show_map_resources: bool,
-    pub show_map_resource_label: bool,
     pub map_resource_filter: [bool; MapResource::COUNT],
-    pub map_resource_distance: f32,
 }
 
 impl OverlayProvider for CameraPositionOverlay {
@@ -33,28 +31,34 @@ impl OverlayProvider for CameraPositionOverlay {
             ui.separator();
             ui.slider("Speed Multiplier", 0.01, 10.0, &mut camera.speed_mul);
             ui.separator();
-            ui.checkbox("Show map resources", &mut self.show_map_resources);
-            if self.show_map_resources {
-                ui.indent();
-                ui.group(|| {
-                    for (i, n) in MapResource::VARIANTS.iter().enumerate() {
-                        ui.checkbox(
-                            format!("{} {}", MapResource::get_icon_by_index(i as u8), n),
-                            &mut self.map_resource_filter[i],
-                        );
-                    }
-                });
-                ui.unindent();
-                ui.checkbox("Show map resource label", &mut self.show_map_resource_label);
-                ui.spacing();
+            config::with_mut(|c| {
+                let cvars = &mut c.cvars;
+                ui.checkbox("Show map resources", &mut cvars.show_map_resources);
+                if cvars.show_map_resources {
+                    ui.indent();
+                    ui.group(|| {
+                        for (i, n) in MapResource::VARIANTS.iter().enumerate() {
+                            ui.checkbox(
+                                format!("{} {}", MapResource::get_icon_by_index(i as u8), n),
+                                &mut self.map_resource_filter[i],
+                            );
+                        }
+                    });
+                    ui.unindent();
+                    ui.checkbox(
+                        "Show map resource label",
+                        &mut cvars.show_map_resource_label,
+                    );
+                    ui.spacing();
 
-                ui.slider(
-                    "Debug distance",
-                    25.0,
-                    4000.0,
-                    &mut self.map_resource_distance,
-                );
-            }
+                    ui.slider(
+                        "Debug distance",
+                        25.0,
+                        4000.0,
+                        &mut cvars.map_resource_distance,
+                    );
+                }
+            });
         });
     }
 }
@@ -1,15 +1,25 @@
+use crate::commands::CommandRegistry;
+use crate::config;
 use crate::input::InputState;
 use crate::overlays::gui::OverlayProvider;
 use crate::resources::Resources;
 
+use imgui::{HistoryDirection, InputTextCallback, InputTextCallbackHandler, TextCallbackData};
+
+use itertools::Itertools;
 use lazy_static::lazy_static;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use ringbuffer::{AllocRingBuffer, RingBuffer};
 use std::fmt::Debug;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::field::{Field, Visit};
 use tracing::{Event, Level, Subscriber};
 use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::Layer;
 use winit::event::VirtualKeyCode;
 use winit::window::Window;
@@ -17,6 +27,80 @@ use winit::window::Window;
 lazy_static! {
     static ref MESSAGE_BUFFER: Arc<RwLock<AllocRingBuffer<CapturedEvent>>> =
         Arc::new(RwLock::new(AllocRingBuffer::new(8192)));
+    static ref LOG_FILE: Mutex<Option<RollingLogFile>> = Mutex::new(None);
+    static ref START_TIME: Instant = Instant::now();
+}
+
+pub fn clear_log() {
+    MESSAGE_BUFFER.write().clear();
+}
+
+/// Starts writing the log to alkahest.log if it is enabled in the config
+pub fn open_log_file() {
+    let (enabled, max_size_mb, max_files) =
+        config::with(|c| (c.log.file, c.log.max_size_mb, c.log.max_files));
+    if !enabled {
+        return;
+    }
+
+    match RollingLogFile::open(PathBuf::from("alkahest.log"), max_size_mb, max_files) {
+        Ok(f) => *LOG_FILE.lock() = Some(f),
+        Err(e) => error!("Failed to open log file: {e}"),
+    }
+}
+
+/// Log file that is moved to `<name>.1.log` once it exceeds `max_size` bytes, shifting older files up to `<name>.<max_files>.log`
+struct RollingLogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RollingLogFile {
+    fn open(path: PathBuf, max_size_mb: u64, max_files: usize) -> std::io::Result<Self> {
+        let file = File::options().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size: max_size_mb.max(1) * 1024 * 1024,
+            max_files,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        self.path.with_extension(format!("{index}.log"))
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            std::fs::remove_file(self.rotated_path(self.max_files)).ok();
+            for i in (1..self.max_files).rev() {
+                std::fs::rename(self.rotated_path(i), self.rotated_path(i + 1)).ok();
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+            self.file = File::create(&self.path)?;
+        }
+
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.size + line.len() as u64 + 1 > self.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{line}")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
 }
 
 /// Tracing layer to capture events
@@ -34,16 +118,41 @@ impl Visit for ConsoleLogVisitor {
 }
 
 struct CapturedEvent {
+    /// Time since startup
+    time: Duration,
     level: Level,
     target: String,
+    /// Names of the spans the event was emitted in, from the outermost span inwards
+    spans: Vec<&'static str>,
     message: String,
 }
 
+impl CapturedEvent {
+    fn span_context(&self) -> String {
+        self.spans.join(" > ")
+    }
+
+    fn to_line(&self) -> String {
+        let mut line = format!(
+            "[{:>10.3}] {:5} {}: ",
+            self.time.as_secs_f32(),
+            self.level,
+            self.target
+        );
+        if !self.spans.is_empty() {
+            line += &format!("[{}] ", self.span_context());
+        }
+        line += &self.message;
+
+        line
+    }
+}
+
 impl<S> Layer<S> for ConsoleLogLayer
 where
-    S: Subscriber,
+    S: Subscriber + for<'a> LookupSpan<'a>,
 {
-    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
         let mut visitor = ConsoleLogVisitor { fields: vec![] };
 
         event.record(&mut visitor);
@@ -55,12 +164,85 @@ where
         }
 
         if let Some(message) = message {
-            MESSAGE_BUFFER.write().push(CapturedEvent {
+            let spans = ctx
+                .event_scope(event)
+                .map(|scope| scope.from_root().map(|s| s.name()).collect())
+                .unwrap_or_default();
+
+            let captured = CapturedEvent {
+                time: START_TIME.elapsed(),
                 level: *event.metadata().level(),
                 target: event.metadata().target().to_string(),
+                spans,
                 message,
-            })
+            };
+
+            if let Some(f) = LOG_FILE.lock().as_mut() {
+                if let Err(e) = f.write_line(&captured.to_line()) {
+                    // Logging here would recurse into this layer
+                    eprintln!("Failed to write to log file: {e}");
+                }
+            }
+
+            MESSAGE_BUFFER.write().push(captured)
+        }
+    }
+}
+
+/// Filter applied to the console log
+struct LogFilter {
+    /// Indexed by [level_index]
+    levels: [bool; 5],
+    target: String,
+    search: String,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            levels: [true; 5],
+            target: String::new(),
+            search: String::new(),
+        }
+    }
+}
+
+const LEVELS: [Level; 5] = [
+    Level::TRACE,
+    Level::DEBUG,
+    Level::INFO,
+    Level::WARN,
+    Level::ERROR,
+];
+
+fn level_index(level: Level) -> usize {
+    LEVELS.iter().position(|l| *l == level).unwrap_or(0)
+}
+
+fn level_color(level: Level) -> [f32; 4] {
+    match level {
+        Level::TRACE => [0.8, 0.4, 0.8, 1.0],
+        Level::DEBUG => [0.35, 0.35, 1.0, 1.0],
+        Level::INFO => [0.25, 1.0, 0.25, 1.0],
+        Level::WARN => [1.0, 1.0, 0.15, 1.0],
+        Level::ERROR => [1.0, 0.15, 0.15, 1.0],
+    }
+}
+
+impl LogFilter {
+    /// `search` is expected to be lowercase
+    fn matches(&self, e: &CapturedEvent, search: &str) -> bool {
+        if !self.levels[level_index(e.level)] {
+            return false;
+        }
+
+        if !self.target.is_empty() && !e.target.contains(self.target.trim()) {
+            return false;
         }
+
+        search.is_empty()
+            || e.message.to_lowercase().contains(search)
+            || e.spans.iter().any(|s| s.to_lowercase().contains(search))
     }
 }
 
@@ -69,6 +251,11 @@ pub struct ConsoleOverlay {
     pub autoscroll: bool,
     pub focus_input: bool,
     pub open: bool,
+
+    pub commands: CommandRegistry,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    filter: LogFilter,
 }
 
 impl Default for ConsoleOverlay {
@@ -78,6 +265,67 @@ impl Default for ConsoleOverlay {
             autoscroll: true,
             focus_input: false,
             open: false,
+            commands: CommandRegistry::default(),
+            history: vec![],
+            history_index: None,
+            filter: LogFilter::default(),
+        }
+    }
+}
+
+struct ConsoleInputCallbacks<'a> {
+    commands: &'a CommandRegistry,
+    resources: &'a Resources,
+    history: &'a [String],
+    history_index: &'a mut Option<usize>,
+}
+
+impl InputTextCallbackHandler for ConsoleInputCallbacks<'_> {
+    fn on_completion(&mut self, mut data: TextCallbackData) {
+        let candidates = self.commands.complete(self.resources, data.str());
+        let Some(first) = candidates.first() else {
+            return;
+        };
+
+        // Complete as far as all candidates agree
+        let mut prefix_len = first.len();
+        for c in &candidates[1..] {
+            prefix_len = prefix_len.min(
+                first
+                    .char_indices()
+                    .zip(c.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map(|((i, _), _)| i)
+                    .unwrap_or(first.len().min(c.len())),
+            );
+        }
+
+        if candidates.len() > 1 {
+            info!("{}", candidates.join("  "));
+        }
+
+        if prefix_len > data.str().len() {
+            let completed = first[..prefix_len].to_string();
+            data.clear();
+            data.push_str(&completed);
+        }
+    }
+
+    fn on_history(&mut self, dir: HistoryDirection, mut data: TextCallbackData) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        *self.history_index = match (dir, *self.history_index) {
+            (HistoryDirection::Up, None) => Some(self.history.len() - 1),
+            (HistoryDirection::Up, Some(i)) => Some(i.saturating_sub(1)),
+            (HistoryDirection::Down, Some(i)) if i + 1 < self.history.len() => Some(i + 1),
+            (HistoryDirection::Down, _) => None,
+        };
+
+        data.clear();
+        if let Some(i) = *self.history_index {
+            data.push_str(&self.history[i]);
         }
     }
 }
@@ -98,33 +346,75 @@ impl OverlayProvider for ConsoleOverlay {
             ui.window("Console").opened(&mut self.open).build(|| {
                 is_focused = ui.is_window_focused();
 
-                let c = MESSAGE_BUFFER.read();
-                ui.group(|| {
-                    ui.child_window("Console log")
-                        // .flags(WindowFlags::NO_TITLE_BAR)
-                        .size([ui.window_size()[0] - 16.0, ui.window_size()[1] - 58.0])
-                        .build(|| {
-                            is_focused |= ui.is_window_focused();
-                            for e in c.iter() {
-                                let level_color = match e.level {
-                                    Level::TRACE => [0.8, 0.4, 0.8, 1.0],
-                                    Level::DEBUG => [0.35, 0.35, 1.0, 1.0],
-                                    Level::INFO => [0.25, 1.0, 0.25, 1.0],
-                                    Level::WARN => [1.0, 1.0, 0.15, 1.0],
-                                    Level::ERROR => [1.0, 0.15, 0.15, 1.0],
-                                };
-                                ui.text_colored(level_color, format!("{:5} ", e.level));
-                                ui.same_line();
-                                ui.text_colored([0.6, 0.6, 0.6, 1.0], format!("{}: ", e.target));
-                                ui.same_line();
-                                ui.text(&e.message);
-                            }
-
-                            if self.autoscroll {
-                                ui.set_scroll_here_y();
-                            }
-                        });
-                });
+                for (i, level) in LEVELS.iter().enumerate() {
+                    let _color = ui.push_style_color(imgui::StyleColor::Text, level_color(*level));
+                    ui.checkbox(format!("{level}"), &mut self.filter.levels[i]);
+                    ui.same_line();
+                }
+                ui.set_next_item_width(160.0);
+                ui.input_text("##target", &mut self.filter.target)
+                    .hint("Target")
+                    .build();
+                ui.same_line();
+                ui.set_next_item_width(240.0);
+                ui.input_text("##search", &mut self.filter.search)
+                    .hint("Search")
+                    .build();
+                ui.same_line();
+                ui.checkbox("Autoscroll", &mut self.autoscroll);
+                ui.same_line();
+                let copy = ui.button("Copy");
+                ui.same_line();
+                if ui.button("Clear") {
+                    clear_log();
+                }
+
+                // The buffer has to be unlocked before running commands, as they log to it
+                {
+                    let c = MESSAGE_BUFFER.read();
+                    let search = self.filter.search.trim().to_lowercase();
+                    let events = c
+                        .iter()
+                        .filter(|e| self.filter.matches(e, &search))
+                        .collect_vec();
+
+                    if copy {
+                        ui.set_clipboard_text(events.iter().map(|e| e.to_line()).join("\n"));
+                    }
+
+                    ui.group(|| {
+                        ui.child_window("Console log")
+                            // .flags(WindowFlags::NO_TITLE_BAR)
+                            .size([0.0, -ui.frame_height_with_spacing()])
+                            .build(|| {
+                                is_focused |= ui.is_window_focused();
+                                for e in events {
+                                    ui.text_colored(
+                                        level_color(e.level),
+                                        format!("{:5} ", e.level),
+                                    );
+                                    ui.same_line();
+                                    ui.text_colored(
+                                        [0.6, 0.6, 0.6, 1.0],
+                                        format!("{}: ", e.target),
+                                    );
+                                    if !e.spans.is_empty() {
+                                        ui.same_line();
+                                        ui.text_colored(
+                                            [0.45, 0.65, 0.9, 1.0],
+                                            format!("[{}] ", e.span_context()),
+                                        );
+                                    }
+                                    ui.same_line();
+                                    ui.text(&e.message);
+                                }
+
+                                if self.autoscroll {
+                                    ui.set_scroll_here_y();
+                                }
+                            });
+                    });
+                }
 
                 ui.set_next_item_width(ui.content_region_avail()[0]);
                 if self.focus_input {
@@ -135,8 +425,27 @@ impl OverlayProvider for ConsoleOverlay {
                 if ui
                     .input_text(" ", &mut self.command_buffer)
                     .enter_returns_true(true)
+                    .callback(
+                        InputTextCallback::COMPLETION | InputTextCallback::HISTORY,
+                        ConsoleInputCallbacks {
+                            commands: &self.commands,
+                            resources,
+                            history: &self.history,
+                            history_index: &mut self.history_index,
+                        },
+                    )
                     .build()
                 {
+                    let command = self.command_buffer.trim().to_string();
+                    if !command.is_empty() {
+                        info!("> {command}");
+                        if self.history.last() != Some(&command) {
+                            self.history.push(command.clone());
+                        }
+                        self.commands.execute(resources, &command);
+                    }
+
+                    self.history_index = None;
                     self.command_buffer.clear();
                     self.focus_input = true;
                 }
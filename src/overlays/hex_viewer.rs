@@ -0,0 +1,378 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use destiny_pkg::TagHash;
+use imgui::{ListClipper, Ui};
+use nohash_hasher::IntMap;
+use winit::window::Window;
+
+use crate::classes::{self, TagClass};
+use crate::commands::parse_tag;
+use crate::export::dump::is_existing_tag;
+use crate::packages::package_manager;
+use crate::references::resolve_hash64;
+use crate::resources::Resources;
+use crate::structure::TagBuffer;
+use crate::types::DestinyHash;
+
+use super::gui::OverlayProvider;
+
+const BYTES_PER_ROW: usize = 16;
+
+/// Alternated between the fields read by the known struct, so field boundaries are visible
+const FIELD_COLORS: [[f32; 4]; 4] = [
+    [0.45, 0.75, 1.0, 1.0],
+    [0.55, 0.95, 0.55, 1.0],
+    [1.0, 0.75, 0.4, 1.0],
+    [0.85, 0.6, 1.0, 1.0],
+];
+const UNPARSED_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const SELECTED_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+
+struct TagView {
+    tag: TagHash,
+    data: TagBuffer,
+    class: Option<&'static TagClass>,
+    /// Byte ranges read by the struct of `class`, sorted by offset
+    regions: Vec<Range<u64>>,
+    parse_error: Option<String>,
+}
+
+impl TagView {
+    fn load(tag: TagHash) -> anyhow::Result<TagView> {
+        let data = Arc::new(package_manager().read_tag(tag)?);
+        let class = classes::class_of(tag);
+        let (mut regions, parse_error) = match class {
+            Some(class) => {
+                let (regions, result) = class.regions(&data);
+                (regions, result.err().map(|e| e.to_string()))
+            }
+            None => (vec![], None),
+        };
+        regions.sort_by_key(|r| r.start);
+
+        Ok(TagView {
+            tag,
+            data,
+            class,
+            regions,
+            parse_error,
+        })
+    }
+
+    /// Index of the field containing `offset`
+    fn region(&self, offset: usize) -> Option<usize> {
+        let offset = offset as u64;
+        let i = self.regions.partition_point(|r| r.start <= offset);
+        if i > 0 && self.regions[i - 1].contains(&offset) {
+            Some(i - 1)
+        } else {
+            None
+        }
+    }
+
+    fn read<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.data.get(offset..offset + N)?.try_into().ok()
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        self.read(offset).map(u32::from_le_bytes)
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        self.read(offset).map(u64::from_le_bytes)
+    }
+
+    /// Null-terminated string at `offset`, if it's printable
+    fn string(&self, offset: usize) -> Option<String> {
+        let bytes = self.data.get(offset..)?;
+        let end = bytes.iter().take(256).position(|b| *b == 0)?;
+        let s = std::str::from_utf8(&bytes[..end]).ok()?;
+        (!s.is_empty() && s.chars().all(|c| !c.is_control())).then(|| s.to_string())
+    }
+}
+
+enum Navigation {
+    Offset(usize),
+    Tag(TagHash),
+}
+
+/// Shows the raw data of a tag, colored by the fields of its known struct, with an inspector for
+/// the value at the selected offset
+pub struct HexViewerOverlay {
+    strings: Arc<IntMap<u32, String>>,
+    tag_string: String,
+    view: Option<TagView>,
+    /// Previous (tag, offset) pairs, for going back after following a pointer or tag
+    history: Vec<(TagHash, usize)>,
+    selected: usize,
+    scroll_to: Option<usize>,
+    error: Option<String>,
+}
+
+impl HexViewerOverlay {
+    pub fn new(strings: Arc<IntMap<u32, String>>) -> Self {
+        Self {
+            strings,
+            tag_string: String::new(),
+            view: None,
+            history: vec![],
+            selected: 0,
+            scroll_to: None,
+            error: None,
+        }
+    }
+
+    /// Opens `tag` at offset 0, keeping the current tag in the history
+    pub fn open(&mut self, tag: TagHash) {
+        if let Some(view) = &self.view {
+            self.history.push((view.tag, self.selected));
+        }
+
+        self.open_at(tag, 0);
+    }
+
+    fn open_at(&mut self, tag: TagHash, offset: usize) {
+        self.tag_string = format!("{:08X}", u32::from_be(tag.0));
+        match TagView::load(tag) {
+            Ok(view) => {
+                self.view = Some(view);
+                self.error = None;
+                self.select(offset);
+            }
+            Err(e) => {
+                error!("Failed to read tag {tag}: {e}");
+                self.error = Some(format!("Failed to read tag {tag}: {e}"));
+            }
+        }
+    }
+
+    /// Selects and scrolls to `offset` in the current tag
+    pub fn select(&mut self, offset: usize) {
+        self.selected = offset;
+        self.scroll_to = Some(offset);
+    }
+
+    fn navigate(&mut self, nav: Navigation) {
+        let Some(view) = &self.view else {
+            return;
+        };
+
+        self.history.push((view.tag, self.selected));
+        match nav {
+            Navigation::Offset(offset) => self.select(offset),
+            Navigation::Tag(tag) => self.open_at(tag, 0),
+        }
+    }
+
+    fn back(&mut self) {
+        if let Some((tag, offset)) = self.history.pop() {
+            if self.view.as_ref().is_some_and(|v| v.tag == tag) {
+                self.select(offset);
+            } else {
+                self.open_at(tag, offset);
+            }
+        }
+    }
+
+    /// Value inspector for the selected offset. Returns where to go if a follow button was pressed
+    fn inspector(&self, ui: &Ui, view: &TagView) -> Option<Navigation> {
+        let offset = self.selected;
+        let mut nav = None;
+
+        match view.region(offset) {
+            Some(i) => {
+                let r = &view.regions[i];
+                ui.text(format!(
+                    "0x{offset:x}: field 0x{:x}..0x{:x} ({} bytes)",
+                    r.start,
+                    r.end,
+                    r.end - r.start
+                ));
+            }
+            None => ui.text(format!("0x{offset:x}: not parsed")),
+        }
+
+        if let Some(v) = view.u32(offset) {
+            ui.text(format!(
+                "u32 {v} (0x{v:08x})  i32 {}  f32 {}",
+                v as i32,
+                f32::from_bits(v)
+            ));
+
+            let tag = TagHash(v);
+            if is_existing_tag(tag) {
+                let class = classes::class_of(tag).map_or("", |c| c.name);
+                ui.text(format!("TagHash {tag} {class}"));
+                ui.same_line();
+                if ui.button("Follow tag") {
+                    nav = Some(Navigation::Tag(tag));
+                }
+            }
+
+            let hash = DestinyHash(v);
+            if hash.is_none() {
+                ui.text("DestinyHash NONE");
+            } else if let Some(s) = self.strings.get(&v) {
+                ui.text(format!("DestinyHash \"{s}\""));
+            }
+        }
+
+        if let Some(v) = view.u64(offset) {
+            ui.text(format!("u64 {v} (0x{v:016x})"));
+
+            if let Some(tag) = resolve_hash64(v) {
+                ui.text(format!("TagHash64 {v:016X} -> {tag}"));
+                ui.same_line();
+                if ui.button("Follow tag64") {
+                    nav = Some(Navigation::Tag(tag));
+                }
+            }
+
+            let target = (offset as i64).wrapping_add(v as i64);
+            if v != 0 && (0..view.data.len() as i64).contains(&target) {
+                ui.text(format!("Relative pointer -> 0x{target:x}"));
+                ui.same_line();
+                if ui.button("Follow pointer") {
+                    nav = Some(Navigation::Offset(target as usize));
+                }
+            }
+        }
+
+        if let Some(v) = view.u32(offset) {
+            let target = offset as i64 + v as i32 as i64;
+            if v != 0 && (0..view.data.len() as i64).contains(&target) {
+                ui.text(format!("Relative pointer (32-bit) -> 0x{target:x}"));
+                ui.same_line();
+                if ui.button("Follow pointer##32") {
+                    nav = Some(Navigation::Offset(target as usize));
+                }
+            }
+        }
+
+        if let Some(s) = view.string(offset) {
+            ui.text(format!("String \"{s}\""));
+        }
+
+        nav
+    }
+
+    fn hex_view(&mut self, ui: &Ui) {
+        let Some(view) = &self.view else {
+            return;
+        };
+
+        let mut clicked = None;
+        ui.child_window("Hex").build(|| {
+            let rows = view.data.len().div_ceil(BYTES_PER_ROW);
+            if let Some(offset) = self.scroll_to.take() {
+                let row = offset / BYTES_PER_ROW;
+                ui.set_scroll_y(row as f32 * ui.text_line_height_with_spacing());
+            }
+
+            let mut clipper = ListClipper::new(rows as i32).begin(ui);
+            while clipper.step() {
+                for row in clipper.display_start()..clipper.display_end() {
+                    let start = row as usize * BYTES_PER_ROW;
+                    let end = (start + BYTES_PER_ROW).min(view.data.len());
+
+                    ui.text_disabled(format!("{start:08x}"));
+                    for offset in start..end {
+                        ui.same_line();
+                        let color = if offset == self.selected {
+                            SELECTED_COLOR
+                        } else {
+                            view.region(offset)
+                                .map_or(UNPARSED_COLOR, |i| FIELD_COLORS[i % FIELD_COLORS.len()])
+                        };
+
+                        ui.text_colored(color, format!("{:02x}", view.data[offset]));
+                        if ui.is_item_clicked() {
+                            clicked = Some(offset);
+                        }
+                    }
+
+                    let ascii: String = view.data[start..end]
+                        .iter()
+                        .map(|b| {
+                            if b.is_ascii_graphic() || *b == b' ' {
+                                *b as char
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect();
+                    ui.same_line();
+                    ui.text(ascii);
+                }
+            }
+        });
+
+        if let Some(offset) = clicked {
+            self.selected = offset;
+        }
+    }
+}
+
+impl OverlayProvider for HexViewerOverlay {
+    fn create_overlay(&mut self, ui: &mut Ui, _window: &Window, _resources: &mut Resources) {
+        ui.window("Hex Viewer").build(|| {
+            if ui
+                .input_text("Tag", &mut self.tag_string)
+                .hint("XXXXXXXX")
+                .enter_returns_true(true)
+                .build()
+            {
+                match parse_tag(self.tag_string.trim()) {
+                    Ok(tag) => self.open(tag),
+                    Err(_) => self.error = Some("Malformed input tag.".to_string()),
+                }
+            }
+
+            ui.same_line();
+            ui.disabled(self.history.is_empty(), || {
+                if ui.button("Back") {
+                    self.back();
+                }
+            });
+
+            if let Some(e) = &self.error {
+                ui.text_colored([1.0, 0.0, 0.0, 1.0], e);
+            }
+
+            let Some(view) = &self.view else {
+                return;
+            };
+
+            ui.text(format!("{} ({} bytes)", view.tag, view.data.len()));
+            match view.class {
+                Some(class) => {
+                    ui.same_line();
+                    ui.text(format!(
+                        "{} ({}), {} fields parsed",
+                        class.name,
+                        class.type_name,
+                        view.regions.len()
+                    ));
+                }
+                None => {
+                    ui.same_line();
+                    ui.text_disabled("no known struct");
+                }
+            }
+            if let Some(e) = &view.parse_error {
+                ui.text_colored([1.0, 0.5, 0.0, 1.0], format!("Parse error: {e}"));
+            }
+
+            ui.separator();
+            let nav = self.inspector(ui, view);
+            ui.separator();
+
+            if let Some(nav) = nav {
+                self.navigate(nav);
+            }
+
+            self.hex_view(ui);
+        });
+    }
+}
@@ -0,0 +1,362 @@
+use anyhow::Context;
+use destiny_pkg::TagHash;
+use glam::Mat4;
+use imgui::TreeNodeFlags;
+use itertools::Itertools;
+use winit::window::Window;
+
+use crate::camera::FpsCamera;
+use crate::classes;
+use crate::entity::read_entity_model;
+use crate::export;
+use crate::icons::ICON_INFORMATION;
+use crate::map::{MapData, MapDataList, Unk8080714f};
+use crate::map_resources::MapResource;
+use crate::material::Unk808071e8;
+use crate::packages::package_manager;
+use crate::picking::{PickKind, PickResult, SelectedObject};
+use crate::resources::Resources;
+use crate::statics::Unk808071a7;
+
+use super::gui::OverlayProvider;
+use super::tag_dump::dump_tag;
+
+struct MaterialInfo {
+    tag: TagHash,
+    vertex_shader: TagHash,
+    pixel_shader: TagHash,
+    vs_textures: Vec<(u32, TagHash)>,
+    ps_textures: Vec<(u32, TagHash)>,
+}
+
+impl MaterialInfo {
+    fn load(tag: TagHash) -> Option<MaterialInfo> {
+        let mat: Unk808071e8 = package_manager()
+            .read_tag_struct(tag)
+            .map_err(|e| error!("Failed to read material {tag}: {e}"))
+            .ok()?;
+
+        Some(MaterialInfo {
+            tag,
+            vertex_shader: mat.vertex_shader,
+            pixel_shader: mat.pixel_shader,
+            vs_textures: mat
+                .vs_textures
+                .iter()
+                .map(|t| (t.index, t.texture))
+                .collect(),
+            ps_textures: mat
+                .ps_textures
+                .iter()
+                .map(|t| (t.index, t.texture))
+                .collect(),
+        })
+    }
+}
+
+/// Everything known about the selected object, gathered once when the selection changes
+struct ObjectInfo {
+    title: String,
+    fields: Vec<(&'static str, String)>,
+    transform: Mat4,
+    /// Debug output of the map data entry
+    entry: Option<String>,
+    /// Debug output of the parsed resource/tag struct
+    resource: Option<String>,
+    materials: Vec<MaterialInfo>,
+    /// Tag used by the dump and export actions
+    tag: TagHash,
+    exportable: bool,
+}
+
+impl ObjectInfo {
+    fn gather(selected: &PickResult, map: &MapData) -> anyhow::Result<ObjectInfo> {
+        match selected.kind {
+            PickKind::Static => {
+                let group = map
+                    .placement_groups
+                    .iter()
+                    .find(|g| Some(g.tag()) == selected.placement_group)
+                    .context("Placement group is not part of the current map")?;
+                let transform = group
+                    .transforms
+                    .get(selected.instance_index)
+                    .context("Instance index is out of bounds")?;
+                let model: Unk808071a7 = package_manager().read_tag_struct(selected.tag)?;
+
+                Ok(ObjectInfo {
+                    title: format!("Static {}", selected.tag),
+                    fields: vec![
+                        ("Model", selected.tag.to_string()),
+                        ("Placement group", group.tag().to_string()),
+                        ("Instance", selected.instance_index.to_string()),
+                    ],
+                    transform: transform.transform(),
+                    entry: Some(format!("{transform:#?}")),
+                    resource: Some(format!("{model:#?}")),
+                    materials: model
+                        .materials
+                        .iter()
+                        .unique()
+                        .filter_map(|m| MaterialInfo::load(*m))
+                        .collect(),
+                    tag: selected.tag,
+                    exportable: true,
+                })
+            }
+            PickKind::Terrain => {
+                let terrain: Unk8080714f = package_manager().read_tag_struct(selected.tag)?;
+
+                Ok(ObjectInfo {
+                    title: format!("Terrain {}", selected.tag),
+                    fields: vec![
+                        ("Terrain", selected.tag.to_string()),
+                        ("Vertex buffer", terrain.vertex_buffer.to_string()),
+                        ("Index buffer", terrain.indices.to_string()),
+                    ],
+                    // Terrain has no transform of its own, so teleport to where it was picked
+                    transform: Mat4::from_translation(selected.position),
+                    entry: None,
+                    materials: terrain
+                        .mesh_parts
+                        .iter()
+                        .map(|p| p.material)
+                        .filter(|m| m.is_valid())
+                        .unique()
+                        .filter_map(MaterialInfo::load)
+                        .collect(),
+                    resource: Some(format!("{terrain:#?}")),
+                    tag: selected.tag,
+                    exportable: true,
+                })
+            }
+            PickKind::ResourcePoint => {
+                let (rp, _) = map
+                    .resource_points
+                    .get(selected.instance_index)
+                    .context("Resource point index is out of bounds")?;
+                let origin = &rp.origin;
+
+                let mut materials = vec![];
+                if let MapResource::Decal { material, .. } = &rp.resource {
+                    materials.push(*material);
+                }
+
+                let mut exportable = false;
+                if rp.entity.is_valid() {
+                    match read_entity_model(rp.entity) {
+                        Ok(Some(model)) => {
+                            exportable = true;
+                            materials.extend(
+                                model
+                                    .meshes
+                                    .iter()
+                                    .flat_map(|m| m.parts.iter().map(|p| p.material)),
+                            );
+                        }
+                        Ok(None) => {}
+                        Err(e) => error!("Failed to read entity model for {}: {e}", rp.entity),
+                    }
+                }
+
+                Ok(ObjectInfo {
+                    title: rp.resource.debug_string(),
+                    fields: vec![
+                        ("Entity", rp.entity.to_string()),
+                        ("Resource type", format!("{:08X}", rp.resource_type.to_be())),
+                        (
+                            "Resource pointer",
+                            format!(
+                                "{:?} @ 0x{:x}",
+                                origin.entry.data_resource, origin.entry.data_resource.offset
+                            ),
+                        ),
+                        ("Map resource", origin.map_resource.to_string()),
+                        (
+                            "Map resource (64)",
+                            origin
+                                .map_resource64
+                                .map(|h| format!("{:016X}", h.0))
+                                .unwrap_or_else(|| "None".to_string()),
+                        ),
+                        ("Data table", origin.data_table.to_string()),
+                        ("Index", selected.instance_index.to_string()),
+                    ],
+                    transform: rp.transform(),
+                    entry: Some(format!("{:#?}", origin.entry)),
+                    resource: Some(format!("{:#?}", rp.resource)),
+                    materials: materials
+                        .into_iter()
+                        .filter(|m| m.is_valid())
+                        .unique()
+                        .filter_map(MaterialInfo::load)
+                        .collect(),
+                    tag: if rp.entity.is_valid() {
+                        rp.entity
+                    } else {
+                        origin.data_table
+                    },
+                    exportable,
+                })
+            }
+        }
+    }
+}
+
+/// Debug output of a resource point's tag, when its class is registered
+fn parse_registered(tag: TagHash) -> Option<(String, String)> {
+    let class = classes::class_of(tag)?;
+    let title = format!("{} ({})", class.name, class.type_name);
+    Some(match class.parse(tag) {
+        Ok(parsed) => (title, format!("{parsed:#?}")),
+        Err(e) => (title, format!("Failed to parse {tag}: {e:?}")),
+    })
+}
+
+#[derive(Default)]
+pub struct InspectorOverlay {
+    selection: Option<PickResult>,
+    info: Option<Result<ObjectInfo, String>>,
+    /// Title and debug output of the registered struct of the selected resource point's tag
+    tag_struct: Option<(String, String)>,
+    message: Option<Result<String, String>>,
+}
+
+impl OverlayProvider for InspectorOverlay {
+    fn create_overlay(&mut self, ui: &mut imgui::Ui, _window: &Window, resources: &mut Resources) {
+        let selected = resources.get::<SelectedObject>().unwrap().0.clone();
+        let Some(selected) = selected else {
+            self.selection = None;
+            self.info = None;
+            return;
+        };
+
+        if !self
+            .selection
+            .as_ref()
+            .is_some_and(|s| s.is_same_object(&selected))
+        {
+            let maps = resources.get::<MapDataList>().unwrap();
+            self.info = maps.current_map().map(|map| {
+                ObjectInfo::gather(&selected, map).map_err(|e| {
+                    error!("Failed to inspect {}: {e}", selected.tag);
+                    e.to_string()
+                })
+            });
+            self.tag_struct = match (&self.info, selected.kind) {
+                (Some(Ok(info)), PickKind::ResourcePoint) => parse_registered(info.tag),
+                _ => None,
+            };
+            self.selection = Some(selected.clone());
+            self.message = None;
+        }
+
+        ui.window(format!("{} Inspector", ICON_INFORMATION))
+            .build(|| {
+                let info = match &self.info {
+                    Some(Ok(info)) => info,
+                    Some(Err(e)) => {
+                        ui.text_colored(
+                            [1.0, 0.0, 0.0, 1.0],
+                            format!("Failed to inspect object: {e}"),
+                        );
+                        return;
+                    }
+                    None => return,
+                };
+
+                ui.text(&info.title);
+                ui.separator();
+                for (name, value) in &info.fields {
+                    ui.text(format!("{name}: {value}"));
+                }
+
+                let (scale, rotation, translation) = info.transform.to_scale_rotation_translation();
+                ui.text(format!(
+                    "Position: {:.3} {:.3} {:.3}",
+                    translation.x, translation.y, translation.z
+                ));
+                ui.text(format!(
+                    "Rotation: {:.3} {:.3} {:.3} {:.3}",
+                    rotation.x, rotation.y, rotation.z, rotation.w
+                ));
+                ui.text(format!("Scale: {:.3}", scale.x));
+                ui.text(format!(
+                    "Picked at: {:.3} {:.3} {:.3}",
+                    selected.position.x, selected.position.y, selected.position.z
+                ));
+
+                ui.separator();
+                let tag = info.tag;
+                if ui.button("Dump tag") {
+                    self.message = Some(dump_tag(tag));
+                }
+
+                if info.exportable {
+                    ui.same_line();
+                    if ui.button("Export OBJ") {
+                        self.message = Some(
+                            export::obj::export_tag(tag, format!("exports/{tag}.obj"))
+                                .map(|_| format!("Exported to exports/{tag}.obj"))
+                                .map_err(|e| {
+                                    error!("Failed to export {tag}: {e}");
+                                    format!("Failed to export tag: {e}")
+                                }),
+                        );
+                    }
+                }
+
+                ui.same_line();
+                if ui.button("Teleport") {
+                    let mut camera = resources.get_mut::<FpsCamera>().unwrap();
+                    camera.position = translation - camera.front * 10.0;
+                }
+                ui.same_line();
+                if ui.button("Deselect") {
+                    resources.get_mut::<SelectedObject>().unwrap().0 = None;
+                }
+
+                match &self.message {
+                    Some(Ok(msg)) => ui.text_colored([0.0, 1.0, 0.0, 1.0], msg),
+                    Some(Err(msg)) => ui.text_colored([1.0, 0.0, 0.0, 1.0], msg),
+                    None => {}
+                }
+
+                if ui.collapsing_header(
+                    format!("Materials ({})", info.materials.len()),
+                    TreeNodeFlags::empty(),
+                ) {
+                    for m in &info.materials {
+                        if let Some(_node) = ui.tree_node(format!("Material {}", m.tag)) {
+                            ui.text(format!("Vertex shader: {}", m.vertex_shader));
+                            ui.text(format!("Pixel shader: {}", m.pixel_shader));
+                            for (slot, t) in &m.vs_textures {
+                                ui.text(format!("VS t{slot}: {t}"));
+                            }
+                            for (slot, t) in &m.ps_textures {
+                                ui.text(format!("PS t{slot}: {t}"));
+                            }
+                        }
+                    }
+                }
+
+                if let Some(entry) = &info.entry {
+                    if ui.collapsing_header("Entry", TreeNodeFlags::empty()) {
+                        ui.text(entry);
+                    }
+                }
+
+                if let Some(resource) = &info.resource {
+                    if ui.collapsing_header("Resource", TreeNodeFlags::empty()) {
+                        ui.text(resource);
+                    }
+                }
+
+                if let Some((title, parsed)) = &self.tag_struct {
+                    if ui.collapsing_header(format!("Tag: {title}"), TreeNodeFlags::empty()) {
+                        ui.text(parsed);
+                    }
+                }
+            });
+    }
+}
@@ -2,7 +2,7 @@ use imgui::Condition;
 use winit::window::Window;
 
 use crate::{
-    render::resource_mt::{self, LoadingThreadState},
+    render::resource_mt::{LoadQueue, BUFFER_QUEUE, TEXTURE_QUEUE},
     resources::Resources,
 };
 
@@ -35,55 +35,59 @@ const SPINNER_FRAMES: &[&str] = &[
     "\u{F1456}", // ICON_CLOCK_TIME_TWELVE_OUTLINE
 ];
 const SPINNER_INTERVAL: usize = 50;
+
 impl OverlayProvider for LoadIndicatorOverlay {
     fn create_overlay(&mut self, ui: &mut imgui::Ui, _window: &Window, _resources: &mut Resources) {
-        let open = *resource_mt::STATUS_TEXTURES.read() != LoadingThreadState::Idle
-            || *resource_mt::STATUS_BUFFERS.read() != LoadingThreadState::Idle;
-        // || *resource_mt::STATUS_TEXTURES.read() != LoadingThreadState::Idle;
+        let queues: [&LoadQueue; 2] = [&TEXTURE_QUEUE, &BUFFER_QUEUE];
+        let stats = queues.map(|q| (q.name, q.stats()));
 
-        if open {
-            ui.window("Loading")
-                .no_inputs()
-                .title_bar(false)
-                .resizable(false)
-                .save_settings(false)
-                .position(
-                    [ui.io().display_size[0] - self.window_size[0] - 12.0, 12.0],
-                    Condition::Always,
-                )
-                .build(|| {
-                    ui.set_window_font_scale(1.1);
+        if stats.iter().all(|(_, s)| s.is_idle()) {
+            return;
+        }
 
-                    if let LoadingThreadState::Loading {
-                        start_time,
-                        remaining,
-                    } = *resource_mt::STATUS_TEXTURES.read()
-                    {
-                        let time_millis = start_time.elapsed().as_millis() as usize;
-                        ui.text(format!(
-                            "{} Loading {} textures ({:.1}s)",
-                            SPINNER_FRAMES[(time_millis / SPINNER_INTERVAL) % SPINNER_FRAMES.len()],
-                            remaining,
-                            start_time.elapsed().as_secs_f32()
-                        ));
-                    }
+        ui.window("Loading")
+            .no_inputs()
+            .title_bar(false)
+            .resizable(false)
+            .save_settings(false)
+            .position(
+                [ui.io().display_size[0] - self.window_size[0] - 12.0, 12.0],
+                Condition::Always,
+            )
+            .build(|| {
+                ui.set_window_font_scale(1.1);
+
+                for (name, stats) in &stats {
+                    let Some(start_time) = stats.start_time else {
+                        continue;
+                    };
 
-                    if let LoadingThreadState::Loading {
-                        start_time,
-                        remaining,
-                    } = *resource_mt::STATUS_BUFFERS.read()
-                    {
-                        let time_millis = start_time.elapsed().as_millis() as usize;
-                        ui.text(format!(
-                            "{} Loading {} buffers ({:.1}s)",
-                            SPINNER_FRAMES[(time_millis / SPINNER_INTERVAL) % SPINNER_FRAMES.len()],
-                            remaining,
-                            start_time.elapsed().as_secs_f32()
-                        ));
+                    if stats.is_idle() {
+                        continue;
                     }
 
-                    self.window_size = ui.window_size();
-                });
-        }
+                    let time_millis = start_time.elapsed().as_millis() as usize;
+                    ui.text(format!(
+                        "{} Loading {} {}s ({:.1}s)",
+                        SPINNER_FRAMES[(time_millis / SPINNER_INTERVAL) % SPINNER_FRAMES.len()],
+                        stats.queued + stats.in_flight,
+                        name.to_lowercase(),
+                        start_time.elapsed().as_secs_f32()
+                    ));
+
+                    ui.set_window_font_scale(0.9);
+                    ui.text_disabled(format!(
+                        "  {} queued, {} in flight on {} threads",
+                        stats.queued, stats.in_flight, stats.workers
+                    ));
+                    ui.text_disabled(format!(
+                        "  {} loaded, {} failed, {} cancelled",
+                        stats.loaded, stats.failed, stats.cancelled
+                    ));
+                    ui.set_window_font_scale(1.1);
+                }
+
+                self.window_size = ui.window_size();
+            });
     }
 }
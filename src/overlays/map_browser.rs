@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use destiny_pkg::TagHash;
+use imgui::{MouseButton, SelectableFlags, TableFlags};
+use nohash_hasher::IntMap;
+use winit::window::Window;
+
+use crate::map::{MapDataList, Unk80807dae};
+use crate::map_loader::MapLoadRequest;
+use crate::packages::package_manager;
+use crate::resources::Resources;
+
+use super::gui::OverlayProvider;
+
+pub struct MapEntry {
+    pub hash: TagHash,
+    pub name: String,
+}
+
+/// Lists the maps in every package, any of which can be loaded without restarting
+pub struct MapBrowserOverlay {
+    strings: Arc<IntMap<u32, String>>,
+    /// Populated on first draw, as reading every map header takes a moment
+    maps: Option<Vec<MapEntry>>,
+    search: String,
+    selected: Option<TagHash>,
+}
+
+impl MapBrowserOverlay {
+    pub fn new(strings: Arc<IntMap<u32, String>>) -> Self {
+        Self {
+            strings,
+            maps: None,
+            search: String::new(),
+            selected: None,
+        }
+    }
+
+    fn scan(&self) -> Vec<MapEntry> {
+        let _span = info_span!("Scanning maps").entered();
+        let mut maps = vec![];
+        for (hash, _) in package_manager().get_all_by_reference(0x80807dae) {
+            match package_manager().read_tag_struct::<Unk80807dae>(hash) {
+                Ok(map) => maps.push(MapEntry {
+                    hash,
+                    name: self
+                        .strings
+                        .get(&map.map_name.0)
+                        .cloned()
+                        .unwrap_or(format!("[MissingString_{:08x}]", map.map_name.0)),
+                }),
+                Err(e) => error!("Failed to read map {hash}: {e}"),
+            }
+        }
+
+        maps.sort_by(|a, b| a.name.cmp(&b.name).then(a.hash.0.cmp(&b.hash.0)));
+        info!("Found {} maps", maps.len());
+        maps
+    }
+
+    fn matches(&self, entry: &MapEntry) -> bool {
+        if self.search.is_empty() {
+            return true;
+        }
+
+        let search = self.search.to_lowercase();
+        entry.name.to_lowercase().contains(&search)
+            || entry.hash.to_string().to_lowercase().contains(&search)
+            || format!("{:04x}", entry.hash.pkg_id()).contains(&search)
+    }
+}
+
+impl OverlayProvider for MapBrowserOverlay {
+    fn create_overlay(&mut self, ui: &mut imgui::Ui, _window: &Window, resources: &mut Resources) {
+        ui.window("Map Browser").build(|| {
+            if self.maps.is_none() {
+                self.maps = Some(self.scan());
+            }
+
+            ui.input_text("Search", &mut self.search)
+                .hint("Name, tag or package ID")
+                .build();
+            ui.same_line();
+            if ui.button("Refresh") {
+                self.maps = Some(self.scan());
+            }
+
+            let current = resources
+                .get::<MapDataList>()
+                .unwrap()
+                .current_map()
+                .map(|m| m.hash);
+            let mut load = None;
+
+            let maps = self.maps.as_ref().unwrap();
+            let visible = maps.iter().filter(|m| self.matches(m)).collect::<Vec<_>>();
+            ui.text(format!("{}/{} maps", visible.len(), maps.len()));
+            if let Some(selected) = self.selected {
+                ui.same_line();
+                if ui.button(format!("Load {selected}")) {
+                    load = Some(selected);
+                }
+            }
+
+            if let Some(_table) = ui.begin_table_with_flags(
+                "maps",
+                3,
+                TableFlags::ROW_BG | TableFlags::SCROLL_Y | TableFlags::BORDERS_INNER_V,
+            ) {
+                ui.table_setup_column("Name");
+                ui.table_setup_column("Tag");
+                ui.table_setup_column("Package");
+                ui.table_headers_row();
+
+                for m in visible {
+                    ui.table_next_row();
+                    ui.table_next_column();
+
+                    let label = if Some(m.hash) == current {
+                        format!("{} (current)###{}", m.name, m.hash.0)
+                    } else {
+                        format!("{}###{}", m.name, m.hash.0)
+                    };
+
+                    if ui
+                        .selectable_config(label)
+                        .selected(self.selected == Some(m.hash))
+                        .flags(
+                            SelectableFlags::SPAN_ALL_COLUMNS | SelectableFlags::ALLOW_DOUBLE_CLICK,
+                        )
+                        .build()
+                    {
+                        self.selected = Some(m.hash);
+                        if ui.is_mouse_double_clicked(MouseButton::Left) {
+                            load = Some(m.hash);
+                        }
+                    }
+
+                    ui.table_next_column();
+                    ui.text(m.hash.to_string());
+                    ui.table_next_column();
+                    ui.text(format!("{:04x}", m.hash.pkg_id()));
+                }
+            }
+
+            if load.is_some() {
+                resources.get_mut::<MapLoadRequest>().unwrap().0 = load;
+            }
+        });
+    }
+}
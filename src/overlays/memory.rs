@@ -0,0 +1,81 @@
+use imgui::{ProgressBar, TableFlags};
+use winit::window::Window;
+
+use crate::config;
+use crate::render::data::{CacheUsage, MemoryUsage};
+use crate::resources::Resources;
+
+use super::gui::OverlayProvider;
+
+/// Snapshot of the RenderData caches, updated by the main loop every frame
+#[derive(Default)]
+pub struct MemoryStats {
+    pub usage: MemoryUsage,
+    /// Number of resources evicted since startup
+    pub evicted: usize,
+    /// Evicted resources that are being loaded again
+    pub pending_reloads: usize,
+}
+
+pub struct MemoryOverlay;
+
+fn megabytes(bytes: usize) -> f32 {
+    bytes as f32 / 1024.0 / 1024.0
+}
+
+impl OverlayProvider for MemoryOverlay {
+    fn create_overlay(&mut self, ui: &mut imgui::Ui, _window: &Window, resources: &mut Resources) {
+        let stats = resources.get::<MemoryStats>().unwrap();
+        let usage = &stats.usage;
+
+        ui.window("Memory").build(|| {
+            if let Some(_table) =
+                ui.begin_table_with_flags("memory", 3, TableFlags::ROW_BG | TableFlags::BORDERS)
+            {
+                ui.table_setup_column("Cache");
+                ui.table_setup_column("Count");
+                ui.table_setup_column("Size");
+                ui.table_headers_row();
+
+                let rows: [(&str, CacheUsage); 5] = [
+                    ("Textures", usage.textures),
+                    ("Vertex buffers", usage.vertex_buffers),
+                    ("Index buffers", usage.index_buffers),
+                    ("Materials", usage.materials),
+                    ("Shaders", usage.shaders),
+                ];
+
+                for (name, cache) in rows {
+                    ui.table_next_row();
+                    ui.table_next_column();
+                    ui.text(name);
+                    ui.table_next_column();
+                    ui.text(cache.count.to_string());
+                    ui.table_next_column();
+                    ui.text(format!("{:.1}MB", megabytes(cache.bytes)));
+                }
+            }
+
+            ui.text(format!("Total: {:.1}MB", megabytes(usage.total())));
+
+            config::with_mut(|c| {
+                let budget = &mut c.cvars.memory_budget_mb;
+                ui.input_scalar("Budget (MB)", budget).step(256).build();
+
+                if *budget > 0 {
+                    let budget_bytes = *budget * 1024 * 1024;
+                    ProgressBar::new(usage.evictable() as f32 / budget_bytes as f32)
+                        .overlay_text(format!("{:.1}/{}MB", megabytes(usage.evictable()), *budget))
+                        .build(ui);
+                } else {
+                    ui.text_disabled("No budget, nothing will be evicted");
+                }
+            });
+
+            ui.text(format!(
+                "{} evicted, {} reloading",
+                stats.evicted, stats.pending_reloads
+            ));
+        });
+    }
+}
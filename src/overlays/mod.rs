@@ -2,7 +2,13 @@ pub mod camera_settings;
 pub mod console;
 pub mod fps_display;
 pub mod gui;
+pub mod hex_viewer;
+pub mod inspector;
 pub mod load_indicator;
+pub mod map_browser;
+pub mod memory;
+pub mod references;
 pub mod render_settings;
 pub mod resource_nametags;
+pub mod search;
 pub mod tag_dump;
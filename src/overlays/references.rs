@@ -0,0 +1,151 @@
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use destiny_pkg::TagHash;
+use imgui::{ProgressBar, TreeNodeFlags, Ui};
+use winit::window::Window;
+
+use crate::classes;
+use crate::commands::parse_tag;
+use crate::references::{self, IndexProgress, ReferenceIndex};
+use crate::resources::Resources;
+
+use super::gui::OverlayProvider;
+
+type IndexJob = (
+    Arc<IndexProgress>,
+    JoinHandle<anyhow::Result<Arc<ReferenceIndex>>>,
+);
+
+/// Shows what references a tag and what it references, using the reference index
+#[derive(Default)]
+pub struct ReferencesOverlay {
+    tag_string: String,
+    tag: Option<TagHash>,
+    /// Maps using `tag`, only looked up when asked for as it can walk a large part of the graph
+    maps: Option<Vec<TagHash>>,
+    job: Option<IndexJob>,
+    error: Option<String>,
+}
+
+impl ReferencesOverlay {
+    /// Shows the references of `tag`
+    pub fn select(&mut self, tag: TagHash) {
+        self.tag_string = format!("{:08X}", u32::from_be(tag.0));
+        self.tag = Some(tag);
+        self.maps = None;
+    }
+
+    fn start_job(&mut self, rebuild: bool) {
+        let progress = Arc::new(IndexProgress::default());
+        let handle = std::thread::spawn({
+            let progress = progress.clone();
+            move || {
+                if rebuild {
+                    references::rebuild(&progress)
+                } else {
+                    references::load_or_build(&progress)
+                }
+            }
+        });
+        self.job = Some((progress, handle));
+    }
+
+    fn tag_list(&mut self, ui: &Ui, label: &str, tags: &[TagHash]) {
+        if !ui.collapsing_header(
+            format!("{label} ({})###{label}", tags.len()),
+            TreeNodeFlags::DEFAULT_OPEN,
+        ) {
+            return;
+        }
+
+        let mut selected = None;
+        ui.indent();
+        for &tag in tags {
+            let class = classes::class_of(tag).map_or("", |c| c.name);
+            if ui.selectable(format!("{tag} {class}###{label}{}", tag.0)) {
+                selected = Some(tag);
+            }
+        }
+        ui.unindent();
+
+        if let Some(tag) = selected {
+            self.select(tag);
+        }
+    }
+}
+
+impl OverlayProvider for ReferencesOverlay {
+    fn create_overlay(&mut self, ui: &mut Ui, _window: &Window, _resources: &mut Resources) {
+        ui.window("References").build(|| {
+            if let Some((progress, handle)) = &self.job {
+                ProgressBar::new(progress.fraction())
+                    .overlay_text("Indexing tags")
+                    .build(ui);
+
+                if handle.is_finished() {
+                    let (_, handle) = self.job.take().unwrap();
+                    self.error = match handle.join() {
+                        Ok(Ok(_)) => None,
+                        Ok(Err(e)) => {
+                            error!("Failed to build reference index: {e:?}");
+                            Some(format!("Failed to build reference index: {e}"))
+                        }
+                        Err(_) => Some("Reference indexer panicked".to_string()),
+                    };
+                }
+
+                return;
+            }
+
+            let Some(index) = references::index() else {
+                ui.text_wrapped(
+                    "The reference index hasn't been loaded yet. Building it reads every tag and can take a few minutes, after which it is cached.",
+                );
+                if ui.button("Load index") {
+                    self.start_job(false);
+                }
+                if let Some(e) = &self.error {
+                    ui.text_colored([1.0, 0.0, 0.0, 1.0], e);
+                }
+                return;
+            };
+
+            if ui
+                .input_text("Tag", &mut self.tag_string)
+                .hint("XXXXXXXX")
+                .enter_returns_true(true)
+                .build()
+            {
+                match parse_tag(self.tag_string.trim()) {
+                    Ok(tag) => self.select(tag),
+                    Err(_) => self.tag = None,
+                }
+            }
+            ui.same_line();
+            if ui.button("Rebuild index") {
+                self.start_job(true);
+            }
+
+            let Some(tag) = self.tag else {
+                return;
+            };
+
+            if let Some(class) = classes::class_of(tag) {
+                ui.text(format!("{tag}: {} ({})", class.name, class.type_name));
+            }
+
+            self.tag_list(ui, "Referenced by", index.referenced_by(tag));
+            self.tag_list(ui, "References", index.references(tag));
+
+            match self.maps.clone() {
+                Some(maps) => self.tag_list(ui, "Used by maps", &maps),
+                None => {
+                    if ui.button("Find maps using this tag") {
+                        self.maps = Some(index.maps_using(tag));
+                    }
+                }
+            }
+        });
+    }
+}
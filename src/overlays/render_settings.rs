@@ -3,23 +3,12 @@ use imgui::{Condition, TreeNodeFlags, WindowFlags};
 use std::{fmt::Display, fmt::Formatter};
 use winit::window::Window;
 
-use crate::{map::MapDataList, render::renderer::ScopeOverrides, resources::Resources};
+use crate::{config, map::MapDataList, render::renderer::ScopeOverrides, resources::Resources};
 
 use super::gui::OverlayProvider;
 
-pub struct RenderSettingsOverlay {
-    pub composition_mode: usize,
-
-    pub renderlayer_statics: bool,
-    pub renderlayer_statics_transparent: bool,
-    pub renderlayer_terrain: bool,
-    pub renderlayer_entities: bool,
-
-    pub render_lights: bool,
-    pub alpha_blending: bool,
-    pub blend_override: usize,
-    pub evaluate_bytecode: bool,
-}
+/// Edits the render cvars, see [crate::cvars::Cvars]
+pub struct RenderSettingsOverlay;
 
 impl OverlayProvider for RenderSettingsOverlay {
     fn create_overlay(&mut self, ui: &mut imgui::Ui, _window: &Window, resources: &mut Resources) {
@@ -27,27 +16,30 @@ impl OverlayProvider for RenderSettingsOverlay {
             .flags(WindowFlags::NO_TITLE_BAR)
             .size([178.0, 72.0], Condition::FirstUseEver)
             .build(|| {
-                ui.checkbox("Render lights", &mut self.render_lights);
-                ui.checkbox("Evaluate TFX bytecode (WIP)", &mut self.evaluate_bytecode);
-                ui.checkbox("Enable color blending", &mut self.alpha_blending);
-                if self.alpha_blending {
-                    ui.combo_simple_string(
-                        "Blend Override",
-                        &mut self.blend_override,
-                        &["Default", "Blend", "Additive"],
-                    );
-                }
-                if ui.collapsing_header("Render Layers", TreeNodeFlags::DEFAULT_OPEN) {
-                    ui.indent();
-                    ui.checkbox("Statics", &mut self.renderlayer_statics);
-                    ui.checkbox(
-                        "Statics (overlay/transparent)",
-                        &mut self.renderlayer_statics_transparent,
-                    );
-                    ui.checkbox("Terrain", &mut self.renderlayer_terrain);
-                    ui.checkbox("Entities", &mut self.renderlayer_entities);
-                    ui.unindent();
-                }
+                config::with_mut(|c| {
+                    let cvars = &mut c.cvars;
+                    ui.checkbox("Render lights", &mut cvars.render_lights);
+                    ui.checkbox("Evaluate TFX bytecode (WIP)", &mut cvars.evaluate_bytecode);
+                    ui.checkbox("Enable color blending", &mut cvars.alpha_blending);
+                    if cvars.alpha_blending {
+                        ui.combo_simple_string(
+                            "Blend Override",
+                            &mut cvars.blend_override,
+                            &["Default", "Blend", "Additive"],
+                        );
+                    }
+                    if ui.collapsing_header("Render Layers", TreeNodeFlags::DEFAULT_OPEN) {
+                        ui.indent();
+                        ui.checkbox("Statics", &mut cvars.renderlayer_statics);
+                        ui.checkbox(
+                            "Statics (overlay/transparent)",
+                            &mut cvars.renderlayer_statics_transparent,
+                        );
+                        ui.checkbox("Terrain", &mut cvars.renderlayer_terrain);
+                        ui.checkbox("Entities", &mut cvars.renderlayer_entities);
+                        ui.unindent();
+                    }
+                });
 
                 if ui.collapsing_header("Scope Overrides", TreeNodeFlags::empty()) {
                     let mut overrides = resources.get_mut::<ScopeOverrides>().unwrap();
@@ -180,8 +172,13 @@ impl OverlayProvider for RenderSettingsOverlay {
             .position([0.0, 0.0], Condition::Always)
             .build(|| {
                 let width = ui.push_item_width(128.0);
-                ui.combo("Pass", &mut self.composition_mode, COMPOSITOR_MODES, |v| {
-                    v.to_string().into()
+                config::with_mut(|c| {
+                    ui.combo(
+                        "Pass",
+                        &mut c.cvars.composition_mode,
+                        COMPOSITOR_MODES,
+                        |v| v.to_string().into(),
+                    )
                 });
                 width.end();
                 ui.same_line();
@@ -1,10 +1,14 @@
 use crate::{
-    camera::FpsCamera, map::MapDataList, map_resources::MapResource, render::debug::DebugShapes,
+    camera::FpsCamera,
+    cvars,
+    map::{MapDataList, Unk808099d8},
+    map_resources::MapResource,
+    render::debug::DebugShapes,
     resources::Resources,
 };
-use destiny_pkg::TagHash;
+use destiny_pkg::{TagHash, TagHash64};
 use frustum_query::frustum::Frustum;
-use glam::{Mat4, Quat, Vec2, Vec4};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
 use imgui::{Condition, ImColor32, WindowFlags};
 use std::{cell::RefCell, rc::Rc};
 use winit::window::Window;
@@ -17,7 +21,8 @@ pub struct ResourceTypeOverlay {
 
 impl OverlayProvider for ResourceTypeOverlay {
     fn create_overlay(&mut self, ui: &mut imgui::Ui, window: &Window, resources: &mut Resources) {
-        if self.debug_overlay.borrow().show_map_resources {
+        let cvars = cvars::get();
+        if cvars.show_map_resources {
             let screen_size = ui.io().display_size;
             let window_dims = window.inner_size();
 
@@ -60,7 +65,7 @@ impl OverlayProvider for ResourceTypeOverlay {
                                 }
 
                                 let distance = res.translation.truncate().distance(camera.position);
-                                if distance > self.debug_overlay.borrow().map_resource_distance {
+                                if distance > cvars.map_resource_distance {
                                     continue;
                                 }
 
@@ -88,7 +93,7 @@ impl OverlayProvider for ResourceTypeOverlay {
                                 );
 
                                 ui.set_window_font_scale(1.0);
-                                if self.debug_overlay.borrow().show_map_resource_label {
+                                if cvars.show_map_resource_label {
                                     draw_list.add_text(
                                         (screen_point + Vec2::new(22.0, 0.0)).to_array(),
                                         color,
@@ -127,4 +132,26 @@ pub struct ResourcePoint {
     pub entity: TagHash,
     pub resource_type: u32,
     pub resource: MapResource,
+    pub origin: ResourceOrigin,
+}
+
+/// The map data entry a resource point was read from
+#[derive(Clone)]
+pub struct ResourceOrigin {
+    /// Map resource (0x80808a54) containing the data table. Invalid if it is referenced by a 64-bit hash
+    pub map_resource: TagHash,
+    pub map_resource64: Option<TagHash64>,
+    pub data_table: TagHash,
+    pub entry: Unk808099d8,
+}
+
+impl ResourcePoint {
+    /// Resource to world transform, with the scale stored in `translation.w`
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(
+            Vec3::splat(self.translation.w),
+            self.rotation,
+            self.translation.truncate(),
+        )
+    }
 }
@@ -0,0 +1,260 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use imgui::{ProgressBar, TableFlags, Ui};
+use itertools::Itertools;
+use nohash_hasher::IntMap;
+use winit::window::Window;
+
+use crate::classes;
+use crate::commands::{parse_class, parse_split_tag, parse_tag, parse_tag64};
+use crate::export::dump::DumpFormat;
+use crate::packages::package_manager;
+use crate::resources::Resources;
+use crate::search::{self, SearchProgress, SearchQuery, SearchResult};
+
+use super::gui::OverlayProvider;
+use super::hex_viewer::HexViewerOverlay;
+use super::tag_dump::{dump_tag, dump_tag_structured};
+
+const KINDS: [&str; 8] = [
+    "Tag",
+    "Split tag",
+    "TagHash64",
+    "Class",
+    "File type",
+    "Bytes",
+    "Float",
+    "DestinyHash",
+];
+
+type SearchJob = (
+    Arc<SearchProgress>,
+    JoinHandle<anyhow::Result<Vec<SearchResult>>>,
+);
+
+/// Finds tags by hash, class, file type or contents, see [search]
+pub struct SearchOverlay {
+    strings: Arc<IntMap<u32, String>>,
+    hex_viewer: Rc<RefCell<HexViewerOverlay>>,
+
+    kind: usize,
+    query: String,
+    /// Entry index for split tags, subtype for file types and tolerance for floats
+    query_extra: String,
+    job: Option<SearchJob>,
+    results: Vec<SearchResult>,
+    message: Result<String, String>,
+}
+
+impl SearchOverlay {
+    pub fn new(
+        strings: Arc<IntMap<u32, String>>,
+        hex_viewer: Rc<RefCell<HexViewerOverlay>>,
+    ) -> Self {
+        Self {
+            strings,
+            hex_viewer,
+            kind: 0,
+            query: String::new(),
+            query_extra: String::new(),
+            job: None,
+            results: vec![],
+            message: Ok(String::new()),
+        }
+    }
+
+    fn parse_query(&self) -> anyhow::Result<SearchQuery> {
+        let query = self.query.trim();
+        let extra = self.query_extra.trim();
+        Ok(match self.kind {
+            0 => SearchQuery::Tag(parse_tag(query)?),
+            1 => SearchQuery::Tag(parse_split_tag(query, extra)?),
+            2 => SearchQuery::Tag64(parse_tag64(query)?),
+            3 => SearchQuery::Class(parse_class(query)?),
+            4 => SearchQuery::FileType {
+                file_type: query.parse()?,
+                file_subtype: if extra.is_empty() {
+                    None
+                } else {
+                    Some(extra.parse()?)
+                },
+            },
+            5 => SearchQuery::Bytes(search::parse_bytes(query)?),
+            6 => SearchQuery::Float {
+                value: query.parse()?,
+                tolerance: if extra.is_empty() {
+                    0.0001
+                } else {
+                    extra.parse()?
+                },
+            },
+            _ => match search::parse_u32(query) {
+                Ok(hash) => SearchQuery::Hashes(vec![hash]),
+                Err(_) => {
+                    let hashes = search::string_hashes(&self.strings, query);
+                    anyhow::ensure!(!hashes.is_empty(), "No strings matching '{query}'");
+                    SearchQuery::Hashes(hashes)
+                }
+            },
+        })
+    }
+
+    fn start(&mut self) {
+        let query = match self.parse_query() {
+            Ok(q) => q,
+            Err(e) => {
+                self.message = Err(format!("Invalid query: {e}"));
+                return;
+            }
+        };
+
+        let progress = Arc::new(SearchProgress::default());
+        let handle = std::thread::spawn({
+            let progress = progress.clone();
+            move || search::search(&query, &progress)
+        });
+        self.job = Some((progress, handle));
+        self.results.clear();
+    }
+
+    fn query_ui(&mut self, ui: &Ui) {
+        ui.combo_simple_string("Search by", &mut self.kind, &KINDS);
+
+        let (hint, extra) = match self.kind {
+            0 => ("XXXXXXXX", None),
+            1 => ("Package ID", Some("Entry index")),
+            2 => ("XXXXXXXXXXXXXXXX", None),
+            3 => ("808071e8 or name", None),
+            4 => ("File type", Some("Subtype (optional)")),
+            5 => ("de ad be ef", None),
+            6 => ("Value", Some("Tolerance (0.0001)")),
+            _ => ("Hash (0x for hex) or string", None),
+        };
+
+        let mut enter = ui
+            .input_text("Query", &mut self.query)
+            .hint(hint)
+            .enter_returns_true(true)
+            .build();
+        if let Some(extra) = extra {
+            enter |= ui
+                .input_text("##query_extra", &mut self.query_extra)
+                .hint(extra)
+                .enter_returns_true(true)
+                .build();
+        }
+
+        if ui.button("Search") || enter {
+            self.start();
+        }
+    }
+
+    fn results_ui(&mut self, ui: &Ui) {
+        ui.text(format!("{} tags", self.results.len()));
+
+        let Some(_table) = ui.begin_table_with_flags(
+            "results",
+            4,
+            TableFlags::ROW_BG | TableFlags::SCROLL_Y | TableFlags::BORDERS_INNER_V,
+        ) else {
+            return;
+        };
+
+        ui.table_setup_column("Tag");
+        ui.table_setup_column("Class");
+        ui.table_setup_column("Matches");
+        ui.table_setup_column("");
+        ui.table_headers_row();
+
+        let mut open = None;
+        for r in &self.results {
+            ui.table_next_row();
+            ui.table_next_column();
+            ui.text(r.tag.to_string());
+
+            ui.table_next_column();
+            match package_manager().get_entry(r.tag) {
+                Ok(entry) => match classes::class(entry.reference) {
+                    Some(class) => ui.text(class.name),
+                    None => ui.text(format!(
+                        "{:08x} ({}/{})",
+                        entry.reference, entry.file_type, entry.file_subtype
+                    )),
+                },
+                Err(_) => ui.text_disabled("?"),
+            }
+
+            ui.table_next_column();
+            if !r.offsets.is_empty() {
+                let offsets = r.offsets.iter().map(|o| format!("0x{o:x}")).join(", ");
+                ui.text(format!("{}: {offsets}", r.match_count));
+            }
+
+            ui.table_next_column();
+            if ui.small_button(format!("Hex##{}", r.tag.0)) {
+                open = Some((r.tag, r.offsets.first().copied()));
+            }
+            ui.same_line();
+            if ui.small_button(format!("Dump##{}", r.tag.0)) {
+                self.message = dump_tag(r.tag);
+            }
+            ui.same_line();
+            if ui.small_button(format!("YAML##{}", r.tag.0)) {
+                self.message = dump_tag_structured(r.tag, DumpFormat::Yaml);
+            }
+        }
+
+        if let Some((tag, offset)) = open {
+            let mut hex_viewer = self.hex_viewer.borrow_mut();
+            hex_viewer.open(tag);
+            if let Some(offset) = offset {
+                hex_viewer.select(offset);
+            }
+        }
+    }
+}
+
+impl OverlayProvider for SearchOverlay {
+    fn create_overlay(&mut self, ui: &mut Ui, _window: &Window, _resources: &mut Resources) {
+        ui.window("Tag Search").build(|| {
+            if let Some((progress, handle)) = &self.job {
+                ProgressBar::new(progress.fraction())
+                    .overlay_text(format!(
+                        "{}/{} tags",
+                        progress.done.load(Ordering::Relaxed),
+                        progress.total.load(Ordering::Relaxed)
+                    ))
+                    .build(ui);
+                if ui.button("Cancel") {
+                    progress.cancel();
+                }
+
+                if handle.is_finished() {
+                    let (_, handle) = self.job.take().unwrap();
+                    match handle.join() {
+                        Ok(Ok(results)) => {
+                            self.message = Ok(String::new());
+                            self.results = results;
+                        }
+                        Ok(Err(e)) => self.message = Err(format!("Search failed: {e}")),
+                        Err(_) => self.message = Err("Search panicked".to_string()),
+                    }
+                }
+            } else {
+                self.query_ui(ui);
+            }
+
+            match self.message.as_ref() {
+                Ok(msg) => ui.text_colored([0.0, 1.0, 0.0, 1.0], msg),
+                Err(msg) => ui.text_colored([1.0, 0.0, 0.0, 1.0], msg),
+            }
+
+            ui.separator();
+            self.results_ui(ui);
+        });
+    }
+}
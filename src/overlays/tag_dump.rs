@@ -1,10 +1,20 @@
+use crate::commands::{parse_class, parse_split_tag, parse_tag};
+use crate::export;
+use crate::export::batch::{BatchOptions, BatchProgress, BatchSelection, BatchSummary};
+use crate::export::dump::DumpFormat;
+use crate::map::MapDataList;
+use crate::mesh;
 use crate::overlays::gui::OverlayProvider;
 use crate::packages::package_manager;
 use crate::resources::Resources;
 use destiny_pkg::TagHash;
-use imgui::Ui;
+use imgui::{ProgressBar, Ui};
 use std::fs::File;
 use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
 use tracing::error;
 use winit::window::Window;
 
@@ -15,6 +25,14 @@ pub struct TagDumper {
     message: Result<String, String>,
 
     use_full_hash: bool,
+    bake_transforms: bool,
+    dump_format: DumpFormat,
+
+    batch_mode: usize,
+    batch_query: String,
+    batch_depth: usize,
+    batch_structured: bool,
+    batch: Option<(Arc<BatchProgress>, JoinHandle<anyhow::Result<BatchSummary>>)>,
 }
 
 impl TagDumper {
@@ -25,37 +43,134 @@ impl TagDumper {
             tag_string: String::new(),
             message: Ok(String::new()),
             use_full_hash: true,
+            bake_transforms: true,
+            dump_format: DumpFormat::Yaml,
+            batch_mode: 0,
+            batch_query: String::new(),
+            batch_depth: 4,
+            batch_structured: false,
+            batch: None,
         }
     }
 
-    fn dump_entry(&self, tag: TagHash) -> Result<String, String> {
-        let entry_header = package_manager().get_entry(tag);
-        if let Ok(entry) = entry_header {
-            std::fs::create_dir("tags").ok();
-
-            let file_path = format!(
-                "tags/{tag}_{0}_{1}.bin",
-                entry.file_type, entry.file_subtype,
-            );
-            let mut file = File::create(&file_path).unwrap();
-            if file
-                .write_all(package_manager().read_tag(tag).unwrap().as_slice())
-                .is_err()
-            {
-                error!("Failed to write tag {file_path} to disk!");
-                Err("Failed to dump tag!".to_string())
-            } else {
-                Ok("Dumped!".to_string())
-            }
+    fn input_tag(&self) -> Option<TagHash> {
+        if self.use_full_hash {
+            parse_tag(&self.tag_string).ok()
         } else {
-            error!("Unable to find tag {tag}!");
-            Err("Failed to dump tag!".to_string())
+            parse_split_tag(&self.package_id, &self.entry_id).ok()
+        }
+    }
+}
+
+impl TagDumper {
+    fn batch_selection(&self) -> Result<BatchSelection, String> {
+        let query = self.batch_query.trim();
+        match self.batch_mode {
+            0 => u16::from_str_radix(query, 16)
+                .map(BatchSelection::Package)
+                .map_err(|_| "Malformed package ID.".to_string()),
+            1 => {
+                let mut parts = query.split(|c: char| c == '/' || c.is_whitespace());
+                let file_type = parts.next().and_then(|t| t.parse().ok());
+                let file_subtype = parts.next().map(|t| t.parse());
+                match (file_type, file_subtype) {
+                    (Some(file_type), None) => Ok(BatchSelection::FileType {
+                        file_type,
+                        file_subtype: None,
+                    }),
+                    (Some(file_type), Some(Ok(subtype))) => Ok(BatchSelection::FileType {
+                        file_type,
+                        file_subtype: Some(subtype),
+                    }),
+                    _ => Err("Malformed file type.".to_string()),
+                }
+            }
+            2 => parse_class(query)
+                .map(BatchSelection::Reference)
+                .map_err(|_| "Unknown class.".to_string()),
+            _ => parse_tag(query)
+                .map(|root| BatchSelection::Recursive {
+                    root,
+                    max_depth: self.batch_depth,
+                })
+                .map_err(|_| "Malformed input tag.".to_string()),
+        }
+    }
+
+    fn batch_ui(&mut self, ui: &Ui) {
+        if let Some((progress, handle)) = &self.batch {
+            ProgressBar::new(progress.fraction())
+                .overlay_text(format!(
+                    "{}/{} ({} failed)",
+                    progress.done.load(Ordering::Relaxed),
+                    progress.total.load(Ordering::Relaxed),
+                    progress.failed.load(Ordering::Relaxed)
+                ))
+                .build(ui);
+
+            if ui.button("Cancel") {
+                progress.cancel();
+            }
+
+            if handle.is_finished() {
+                let (_, handle) = self.batch.take().unwrap();
+                self.message = match handle.join() {
+                    Ok(Ok(summary)) => Ok(format!(
+                        "Dumped {} tags ({} failed), see {}",
+                        summary.dumped,
+                        summary.failed,
+                        summary.manifest.display()
+                    )),
+                    Ok(Err(e)) => {
+                        error!("Batch dump failed: {e:?}");
+                        Err(format!("Batch dump failed: {e}"))
+                    }
+                    Err(_) => Err("Batch dump panicked".to_string()),
+                };
+            }
+
+            return;
+        }
+
+        ui.combo_simple_string(
+            "Batch",
+            &mut self.batch_mode,
+            &["Package", "File type", "Class", "Tag + references"],
+        );
+        let hint = ["XXXX", "type[/subtype]", "808071e8 or name", "XXXXXXXX"][self.batch_mode];
+        ui.input_text("##batch_query", &mut self.batch_query)
+            .hint(hint)
+            .build();
+        if self.batch_mode == 3 {
+            ui.input_scalar("Max depth", &mut self.batch_depth)
+                .step(1)
+                .build();
+        }
+        ui.checkbox("Structured dumps", &mut self.batch_structured);
+
+        if ui.button("Dump batch") {
+            match self.batch_selection() {
+                Ok(selection) => {
+                    let options = BatchOptions {
+                        selection,
+                        output: PathBuf::from("dumps"),
+                        structured: self.batch_structured.then_some(self.dump_format),
+                    };
+                    let progress = Arc::new(BatchProgress::default());
+                    let handle = std::thread::spawn({
+                        let progress = progress.clone();
+                        move || export::batch::dump_batch(&options, &progress)
+                    });
+                    self.batch = Some((progress, handle));
+                }
+                Err(e) => self.message = Err(e),
+            }
         }
     }
 }
 
 impl OverlayProvider for TagDumper {
-    fn create_overlay(&mut self, ui: &mut Ui, _window: &Window, _resources: &mut Resources) {
+    fn create_overlay(&mut self, ui: &mut Ui, _window: &Window, resources: &mut Resources) {
         ui.window("Tag Dumper").build(|| {
             ui.group(|| {
                 ui.radio_button("Full hash", &mut self.use_full_hash, true);
@@ -78,23 +193,73 @@ impl OverlayProvider for TagDumper {
                 };
 
                 if ui.button("Dump!") || pressed_enter {
-                    if self.use_full_hash {
-                        let tag = u32::from_str_radix(&self.tag_string, 16);
+                    self.message = match self.input_tag() {
+                        Some(tag) => dump_tag(tag),
+                        None => Err("Malformed input tag.".to_string()),
+                    };
+                }
 
-                        if let Ok(tag) = tag {
-                            self.message = self.dump_entry(TagHash(u32::from_be(tag)));
-                        } else {
-                            self.message = Err("Malformed input tag.".to_string());
-                        }
-                    } else {
-                        let pkg = u16::from_str_radix(&self.package_id, 16);
-                        let entry = self.entry_id.parse();
-
-                        if let (Ok(pkg), Ok(entry)) = (pkg, entry) {
-                            self.message = self.dump_entry(TagHash::new(pkg, entry));
-                        } else {
-                            self.message = Err("Malformed input tag.".to_string());
+                ui.same_line();
+                if ui.button("Dump structured") {
+                    self.message = match self.input_tag() {
+                        Some(tag) => dump_tag_structured(tag, self.dump_format),
+                        None => Err("Malformed input tag.".to_string()),
+                    };
+                }
+
+                ui.same_line();
+                ui.radio_button("YAML", &mut self.dump_format, DumpFormat::Yaml);
+                ui.same_line();
+                ui.radio_button("JSON", &mut self.dump_format, DumpFormat::Json);
+
+                if ui.button("Export OBJ") {
+                    self.message = match self.input_tag() {
+                        Some(tag) => export::obj::export_tag(tag, format!("exports/{tag}.obj"))
+                            .map(|_| format!("Exported to exports/{tag}.obj"))
+                            .map_err(|e| {
+                                error!("Failed to export {tag}: {e}");
+                                format!("Failed to export tag: {e}")
+                            }),
+                        None => Err("Malformed input tag.".to_string()),
+                    };
+                }
+
+                ui.same_line();
+                if ui.button("Mesh report") {
+                    self.message = match self.input_tag() {
+                        Some(tag) => {
+                            write_report(format!("reports/{tag}.txt"), mesh::stats::tag_report(tag))
                         }
+                        None => Err("Malformed input tag.".to_string()),
+                    };
+                }
+
+                ui.separator();
+                self.batch_ui(ui);
+
+                ui.separator();
+                ui.checkbox("Bake world transforms", &mut self.bake_transforms);
+                if ui.button("Export current map (OBJ)") {
+                    let maps = resources.get::<MapDataList>().unwrap();
+                    if let Some(map) = maps.current_map() {
+                        let path = format!("exports/map_{}.obj", map.hash);
+                        self.message = export::obj::export_map(map, self.bake_transforms, &path)
+                            .map(|_| format!("Exported to {path}"))
+                            .map_err(|e| {
+                                error!("Failed to export map {}: {e}", map.hash);
+                                format!("Failed to export map: {e}")
+                            });
+                    }
+                }
+
+                ui.same_line();
+                if ui.button("Current map report") {
+                    let maps = resources.get::<MapDataList>().unwrap();
+                    if let Some(map) = maps.current_map() {
+                        self.message = write_report(
+                            format!("reports/map_{}.txt", map.hash),
+                            Ok(mesh::stats::map_report(map)),
+                        );
                     }
                 }
 
@@ -106,3 +271,54 @@ impl OverlayProvider for TagDumper {
         });
     }
 }
+
+fn write_report(path: String, report: anyhow::Result<String>) -> Result<String, String> {
+    let report = report.map_err(|e| {
+        error!("Failed to create report: {e}");
+        format!("Failed to create report: {e}")
+    })?;
+
+    std::fs::create_dir_all("reports").ok();
+    match std::fs::write(&path, report) {
+        Ok(_) => Ok(format!("Report written to {path}")),
+        Err(e) => {
+            error!("Failed to write report {path}: {e}");
+            Err(format!("Failed to write report: {e}"))
+        }
+    }
+}
+
+/// Writes the raw data of `tag` to the tags/ directory
+pub fn dump_tag(tag: TagHash) -> Result<String, String> {
+    let file_path = export::dump::raw_dump_path(tag).map_err(|e| {
+        error!("Unable to find tag {tag}: {e}");
+        "Failed to dump tag!".to_string()
+    })?;
+
+    std::fs::create_dir("tags").ok();
+    let data = package_manager().read_tag(tag).map_err(|e| {
+        error!("Failed to read tag {tag}: {e}");
+        format!("Failed to read tag: {e}")
+    })?;
+
+    File::create(&file_path)
+        .and_then(|mut file| file.write_all(&data))
+        .map_err(|e| {
+            error!("Failed to write tag {} to disk: {e}", file_path.display());
+            "Failed to dump tag!".to_string()
+        })?;
+
+    Ok("Dumped!".to_string())
+}
+
+/// Writes the fields of `tag` (or a heuristic view for unknown classes) to the tags/ directory
+pub fn dump_tag_structured(tag: TagHash, format: DumpFormat) -> Result<String, String> {
+    let path = export::dump::structured_dump_path(tag, format);
+    match export::dump::write_dump(tag, format, &path) {
+        Ok(_) => Ok(format!("Dumped to {}", path.display())),
+        Err(e) => {
+            error!("Failed to dump tag {tag}: {e:?}");
+            Err(format!("Failed to dump tag: {e}"))
+        }
+    }
+}
@@ -1,12 +1,29 @@
-use destiny_pkg::PackageManager;
+use anyhow::Context;
+use destiny_pkg::{PackageManager, TagHash};
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use parking_lot::RwLock;
+use std::path::Path;
 use std::sync::Arc;
 
+use crate::version;
+
 lazy_static! {
     pub static ref PACKAGE_MANAGER: RwLock<Option<Arc<PackageManager>>> = RwLock::new(None);
 }
 
+/// Sets up the global package manager for the directory containing `pkg_path`, using the current [version]
+pub fn initialize(pkg_path: &str) -> anyhow::Result<()> {
+    let _span = info_span!("Initializing package manager").entered();
+    let packages_dir = Path::new(pkg_path)
+        .parent()
+        .context("Package path has no parent directory")?;
+    let pm = PackageManager::new(packages_dir, version::current().package_version(), true)?;
+
+    *PACKAGE_MANAGER.write() = Some(Arc::new(pm));
+    Ok(())
+}
+
 pub fn package_manager_checked() -> anyhow::Result<Arc<PackageManager>> {
     PACKAGE_MANAGER
         .read()
@@ -18,3 +35,26 @@ pub fn package_manager_checked() -> anyhow::Result<Arc<PackageManager>> {
 pub fn package_manager() -> Arc<PackageManager> {
     package_manager_checked().unwrap()
 }
+
+/// Tags in a package whose file type and subtype match `filter`
+pub fn package_tags(pkg_id: u16, filter: impl Fn(u8, u8) -> bool) -> Vec<TagHash> {
+    let pm = package_manager();
+
+    // Entries are numbered contiguously, the first missing entry is the end of the package
+    (0..0x2000)
+        .map(|index| TagHash::new(pkg_id, index))
+        .map_while(|tag| pm.get_entry(tag).ok().map(|entry| (tag, entry)))
+        .filter(|(_, entry)| filter(entry.file_type, entry.file_subtype))
+        .map(|(tag, _)| tag)
+        .collect()
+}
+
+/// Tags in all packages whose file type and subtype match `filter`
+pub fn all_tags(filter: impl Fn(u8, u8) -> bool) -> Vec<TagHash> {
+    package_manager()
+        .package_paths
+        .keys()
+        .sorted()
+        .flat_map(|pkg_id| package_tags(*pkg_id, &filter))
+        .collect()
+}
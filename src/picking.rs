@@ -0,0 +1,514 @@
+//! CPU ray picking against map geometry and resource points
+
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use destiny_pkg::TagHash;
+use glam::{Mat4, Vec2, Vec3};
+use nohash_hasher::{IntMap, IntSet};
+
+use crate::camera::FpsCamera;
+use crate::map::{MapData, Unk8080714f};
+use crate::mesh::{self, MeshData};
+use crate::overlays::resource_nametags::ResourcePoint;
+use crate::packages::package_manager;
+use crate::statics::Unk808071a7;
+
+/// Screen-space size of a resource point icon, in pixels
+const RESOURCE_ICON_SIZE: f32 = 20.0;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Not guaranteed to be normalized after [Ray::transform]
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// Creates a ray from the camera through `cursor`, given in pixels
+    pub fn from_cursor(camera: &mut FpsCamera, cursor: Vec2, screen_size: Vec2) -> Ray {
+        let projection = Mat4::perspective_infinite_reverse_rh(
+            90f32.to_radians(),
+            screen_size.x / screen_size.y,
+            0.0001,
+        );
+        let proj_view_inv = (projection * camera.calculate_matrix()).inverse();
+
+        let ndc = Vec2::new(
+            (cursor.x / screen_size.x) * 2.0 - 1.0,
+            1.0 - (cursor.y / screen_size.y) * 2.0,
+        );
+
+        // Depth is reversed, so 1.0 is the near plane
+        let near = proj_view_inv.project_point3(ndc.extend(1.0));
+        let far = proj_view_inv.project_point3(ndc.extend(0.5));
+
+        Ray {
+            origin: camera.position,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    /// Transforms the ray by `m`, keeping distances along the ray the same as the original ray
+    pub fn transform(&self, m: Mat4) -> Ray {
+        Ray {
+            origin: m.transform_point3(self.origin),
+            direction: m.transform_vector3(self.direction),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Slab test, returns the distance to the entry point (or 0 if the origin is inside the box)
+    pub fn intersect_aabb(&self, min: Vec3, max: Vec3) -> Option<f32> {
+        let inv_dir = self.direction.recip();
+        let t0 = (min - self.origin) * inv_dir;
+        let t1 = (max - self.origin) * inv_dir;
+
+        let t_near = t0.min(t1).max_element();
+        let t_far = t0.max(t1).min_element();
+
+        if t_near > t_far || t_far < 0.0 {
+            None
+        } else {
+            Some(t_near.max(0.0))
+        }
+    }
+
+    /// Möller-Trumbore ray/triangle intersection, ignoring the winding order
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(q) * inv_det;
+        (t > 0.0).then_some(t)
+    }
+}
+
+/// Flattened triangle list of all the (highest detail) parts of a model
+pub struct PickMesh {
+    pub positions: Vec<Vec3>,
+    pub indices: Vec<u32>,
+    pub bb_min: Vec3,
+    pub bb_max: Vec3,
+}
+
+impl PickMesh {
+    pub fn from_meshes(meshes: &[MeshData]) -> PickMesh {
+        let mut positions = vec![];
+        let mut indices = vec![];
+        for m in meshes {
+            let base = positions.len() as u32;
+            positions.extend_from_slice(&m.positions);
+            for p in &m.parts {
+                indices.extend(p.indices.iter().map(|i| base + i));
+            }
+        }
+
+        let (bb_min, bb_max) = positions.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        );
+
+        PickMesh {
+            positions,
+            indices,
+            bb_min,
+            bb_max,
+        }
+    }
+
+    /// Returns the distance to the closest triangle hit by `ray`
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        ray.intersect_aabb(self.bb_min, self.bb_max)?;
+
+        self.indices
+            .chunks_exact(3)
+            .filter_map(|t| {
+                ray.intersect_triangle(
+                    *self.positions.get(t[0] as usize)?,
+                    *self.positions.get(t[1] as usize)?,
+                    *self.positions.get(t[2] as usize)?,
+                )
+            })
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PickKind {
+    Static,
+    Terrain,
+    ResourcePoint,
+}
+
+#[derive(Debug, Clone)]
+pub struct PickResult {
+    pub kind: PickKind,
+    /// Static model, terrain or entity tag
+    pub tag: TagHash,
+    /// Index into the transforms of a placement group for statics, or into the map's resource points
+    pub instance_index: usize,
+    /// Placement group the static instance belongs to
+    pub placement_group: Option<TagHash>,
+    pub position: Vec3,
+    pub distance: f32,
+}
+
+impl PickResult {
+    /// Returns true if both results refer to the same object, regardless of where it was hit
+    pub fn is_same_object(&self, other: &PickResult) -> bool {
+        self.kind == other.kind
+            && self.tag == other.tag
+            && self.instance_index == other.instance_index
+            && self.placement_group == other.placement_group
+    }
+}
+
+/// The last object that was picked in the viewport
+#[derive(Default)]
+pub struct SelectedObject(pub Option<PickResult>);
+
+type LoadedMesh = (PickKind, TagHash, Option<PickMesh>);
+
+/// CPU-side geometry of the statics and terrain that can be picked. Meshes are loaded on a
+/// background thread, anything that hasn't finished loading yet is skipped when picking
+pub struct PickingScene {
+    statics: IntMap<TagHash, Option<PickMesh>>,
+    terrain: IntMap<TagHash, Option<PickMesh>>,
+    /// Meshes that have been requested from the loading thread, but haven't been received yet
+    pending: IntSet<TagHash>,
+    requests: Sender<(PickKind, TagHash)>,
+    loaded: Receiver<LoadedMesh>,
+}
+
+impl Default for PickingScene {
+    /// Also starts the loading thread
+    fn default() -> PickingScene {
+        let (requests, request_rx) = mpsc::channel::<(PickKind, TagHash)>();
+        let (loaded_tx, loaded) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("Picking loader".to_string())
+            .spawn(move || {
+                // Runs until the scene is dropped
+                for (kind, tag) in request_rx {
+                    let mesh = load_pick_mesh(kind, tag)
+                        .map_err(|e| error!("Failed to load {kind:?} {tag} for picking: {e}"))
+                        .ok();
+                    if loaded_tx.send((kind, tag, mesh)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn the picking loader thread");
+
+        PickingScene {
+            statics: Default::default(),
+            terrain: Default::default(),
+            pending: Default::default(),
+            requests,
+            loaded,
+        }
+    }
+}
+
+impl PickingScene {
+    /// Queues the geometry of every static and terrain in `map`, so it's ready by the time the
+    /// user clicks on something
+    pub fn prefetch(&mut self, map: &MapData) {
+        for group in &map.placement_groups {
+            for tag in group.statics.iter() {
+                self.request(PickKind::Static, *tag);
+            }
+        }
+
+        for tag in &map.terrains {
+            self.request(PickKind::Terrain, *tag);
+        }
+    }
+
+    fn meshes(&self, kind: PickKind) -> &IntMap<TagHash, Option<PickMesh>> {
+        match kind {
+            PickKind::Terrain => &self.terrain,
+            _ => &self.statics,
+        }
+    }
+
+    fn request(&mut self, kind: PickKind, tag: TagHash) {
+        if !tag.is_valid() || self.meshes(kind).contains_key(&tag) || !self.pending.insert(tag) {
+            return;
+        }
+
+        // The loading thread only stops once the scene is dropped
+        self.requests.send((kind, tag)).ok();
+    }
+
+    /// Moves the meshes that finished loading into the scene
+    fn receive(&mut self) {
+        while let Ok((kind, tag, mesh)) = self.loaded.try_recv() {
+            self.pending.remove(&tag);
+            match kind {
+                PickKind::Terrain => self.terrain.insert(tag, mesh),
+                _ => self.statics.insert(tag, mesh),
+            };
+        }
+    }
+
+    /// The mesh of `tag`, or None if it failed to load or is still loading
+    fn mesh(&mut self, kind: PickKind, tag: TagHash) -> Option<&PickMesh> {
+        if !self.meshes(kind).contains_key(&tag) {
+            self.request(kind, tag);
+            return None;
+        }
+
+        self.meshes(kind).get(&tag)?.as_ref()
+    }
+
+    /// Finds the closest static or terrain hit by `ray`
+    pub fn pick_geometry(&mut self, map: &MapData, ray: &Ray) -> Option<PickResult> {
+        self.receive();
+        let mut closest: Option<PickResult> = None;
+
+        for group in &map.placement_groups {
+            for instance in &group.instances {
+                let Some(model_hash) = group.statics.get(instance.static_index as usize).cloned()
+                else {
+                    continue;
+                };
+
+                let start = instance.instance_start as usize;
+                let end = start + instance.instance_count as usize;
+                for (i, t) in group.transforms.range(start..end).enumerate() {
+                    let Ok(t) = t else {
+                        continue;
+                    };
+                    let Some(mesh) = self.mesh(PickKind::Static, model_hash) else {
+                        break;
+                    };
+
+                    let local_ray = ray.transform(t.transform().inverse());
+                    let Some(distance) = mesh.intersect(&local_ray) else {
+                        continue;
+                    };
+
+                    if closest.as_ref().map_or(true, |c| distance < c.distance) {
+                        closest = Some(PickResult {
+                            kind: PickKind::Static,
+                            tag: model_hash,
+                            instance_index: start + i,
+                            placement_group: Some(group.tag()),
+                            position: ray.at(distance),
+                            distance,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (i, t) in map.terrains.iter().enumerate() {
+            let Some(distance) = self
+                .mesh(PickKind::Terrain, *t)
+                .and_then(|m| m.intersect(ray))
+            else {
+                continue;
+            };
+
+            if closest.as_ref().map_or(true, |c| distance < c.distance) {
+                closest = Some(PickResult {
+                    kind: PickKind::Terrain,
+                    tag: *t,
+                    instance_index: i,
+                    placement_group: None,
+                    position: ray.at(distance),
+                    distance,
+                });
+            }
+        }
+
+        if !self.pending.is_empty() {
+            warn!(
+                "{} meshes are still loading and can't be picked yet",
+                self.pending.len()
+            );
+        }
+
+        closest
+    }
+}
+
+fn load_pick_mesh(kind: PickKind, tag: TagHash) -> anyhow::Result<PickMesh> {
+    Ok(match kind {
+        PickKind::Terrain => {
+            let terrain = package_manager().read_tag_struct::<Unk8080714f>(tag)?;
+            PickMesh::from_meshes(&[mesh::load_terrain(&terrain)?])
+        }
+        _ => {
+            let model = package_manager().read_tag_struct::<Unk808071a7>(tag)?;
+            PickMesh::from_meshes(&mesh::load_static(&model)?)
+        }
+    })
+}
+
+/// Finds the closest resource point icon under the cursor. Icons are drawn on top of the scene, so these take priority over geometry.
+///
+/// `filter` should match the resource points that are currently visible.
+pub fn pick_resource_point(
+    map: &MapData,
+    camera: &mut FpsCamera,
+    cursor: Vec2,
+    screen_size: Vec2,
+    filter: impl Fn(&ResourcePoint) -> bool,
+) -> Option<PickResult> {
+    let projection = Mat4::perspective_infinite_reverse_rh(
+        90f32.to_radians(),
+        screen_size.x / screen_size.y,
+        0.0001,
+    );
+    let proj_view = projection * camera.calculate_matrix();
+
+    let mut closest: Option<PickResult> = None;
+    for (i, (rp, _)) in map.resource_points.iter().enumerate() {
+        let position = rp.translation.truncate();
+        if (position - camera.position).dot(camera.front) <= 0.0 || !filter(rp) {
+            continue;
+        }
+
+        let projected = proj_view.project_point3(position);
+        let screen_point = Vec2::new(
+            ((projected.x + 1.0) * 0.5) * screen_size.x,
+            ((1.0 - projected.y) * 0.5) * screen_size.y,
+        );
+
+        // Icons are drawn with their top left corner at the projected point
+        let offset = cursor - screen_point;
+        if !(0.0..=RESOURCE_ICON_SIZE).contains(&offset.x)
+            || !(0.0..=RESOURCE_ICON_SIZE).contains(&offset.y)
+        {
+            continue;
+        }
+
+        let distance = position.distance(camera.position);
+        if closest.as_ref().map_or(true, |c| distance < c.distance) {
+            closest = Some(PickResult {
+                kind: PickKind::ResourcePoint,
+                tag: rp.entity,
+                instance_index: i,
+                placement_group: None,
+                position,
+                distance,
+            });
+        }
+    }
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: Vec3::from(origin),
+            direction: Vec3::from(direction),
+        }
+    }
+
+    const MIN: Vec3 = Vec3::splat(-1.0);
+    const MAX: Vec3 = Vec3::splat(1.0);
+
+    #[test]
+    fn aabb_hit() {
+        let r = ray([-5.0, 0.5, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(r.intersect_aabb(MIN, MAX), Some(4.0));
+
+        let r = ray([3.0, 3.0, 3.0], [-1.0, -1.0, -1.0]);
+        assert_eq!(r.intersect_aabb(MIN, MAX), Some(2.0));
+    }
+
+    #[test]
+    fn aabb_miss() {
+        let r = ray([-5.0, 2.0, 0.0], [1.0, 0.1, 0.0]);
+        assert_eq!(r.intersect_aabb(MIN, MAX), None);
+
+        // Box is behind the ray
+        let r = ray([5.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(r.intersect_aabb(MIN, MAX), None);
+    }
+
+    #[test]
+    fn aabb_parallel() {
+        // Parallel to the Y and Z slabs, inside them
+        let r = ray([-5.0, 0.5, -0.5], [1.0, 0.0, 0.0]);
+        assert_eq!(r.intersect_aabb(MIN, MAX), Some(4.0));
+
+        // Parallel to the Y slab, outside it
+        let r = ray([-5.0, 2.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(r.intersect_aabb(MIN, MAX), None);
+    }
+
+    #[test]
+    fn aabb_origin_inside() {
+        let r = ray([0.5, 0.0, 0.0], [0.0, 0.0, 1.0]);
+        assert_eq!(r.intersect_aabb(MIN, MAX), Some(0.0));
+    }
+
+    const A: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+    const B: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    const C: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+
+    #[test]
+    fn triangle_hit() {
+        let r = ray([0.25, 0.25, 2.0], [0.0, 0.0, -1.0]);
+        assert_eq!(r.intersect_triangle(A, B, C), Some(2.0));
+
+        // Back face
+        let r = ray([0.25, 0.25, -3.0], [0.0, 0.0, 1.0]);
+        assert_eq!(r.intersect_triangle(A, B, C), Some(3.0));
+    }
+
+    #[test]
+    fn triangle_miss() {
+        let r = ray([0.75, 0.75, 2.0], [0.0, 0.0, -1.0]);
+        assert_eq!(r.intersect_triangle(A, B, C), None);
+
+        let r = ray([-0.25, 0.25, 2.0], [0.0, 0.0, -1.0]);
+        assert_eq!(r.intersect_triangle(A, B, C), None);
+
+        // Triangle is behind the ray
+        let r = ray([0.25, 0.25, 2.0], [0.0, 0.0, 1.0]);
+        assert_eq!(r.intersect_triangle(A, B, C), None);
+    }
+
+    #[test]
+    fn triangle_parallel() {
+        let r = ray([-1.0, 0.25, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(r.intersect_triangle(A, B, C), None);
+    }
+
+    #[test]
+    fn triangle_origin_on_plane() {
+        // Starting on the triangle doesn't count as a hit
+        let r = ray([0.25, 0.25, 0.0], [0.0, 0.0, 1.0]);
+        assert_eq!(r.intersect_triangle(A, B, C), None);
+    }
+}
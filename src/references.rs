@@ -0,0 +1,379 @@
+//! Index of which tags reference which. References are found through known struct layouts where
+//! possible, otherwise by checking every aligned u32 (`TagHash`) and u64 (`TagHash64`) against the
+//! loaded packages. The index is cached on disk, as building it means reading every tag
+
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::Context;
+use binrw::{binrw, BinReaderExt, BinWriterExt};
+use destiny_pkg::TagHash;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use nohash_hasher::{IntMap, IntSet};
+use parking_lot::RwLock;
+use rayon::prelude::*;
+
+use crate::export::dump;
+use crate::map::Unk808091e0;
+use crate::packages::{all_tags, package_manager};
+use crate::util::fnv1a64;
+
+pub const INDEX_PATH: &str = "reference_index.bin";
+const INDEX_VERSION: u32 = 1;
+
+lazy_static! {
+    static ref REFERENCE_INDEX: RwLock<Option<Arc<ReferenceIndex>>> = RwLock::new(None);
+}
+
+/// The loaded index, if [load_or_build] or [set_index] has been called
+pub fn index() -> Option<Arc<ReferenceIndex>> {
+    REFERENCE_INDEX.read().clone()
+}
+
+pub fn set_index(index: ReferenceIndex) -> Arc<ReferenceIndex> {
+    let index = Arc::new(index);
+    *REFERENCE_INDEX.write() = Some(index.clone());
+    index
+}
+
+/// Loads the cached index, or builds (and caches) it if there is none for the current packages
+pub fn load_or_build(progress: &IndexProgress) -> anyhow::Result<Arc<ReferenceIndex>> {
+    match ReferenceIndex::load(INDEX_PATH) {
+        Ok(Some(index)) => return Ok(set_index(index)),
+        Ok(None) => info!("Reference index is out of date, rebuilding"),
+        Err(e) => info!("No usable reference index ({e}), building"),
+    }
+
+    rebuild(progress)
+}
+
+/// Builds the index from scratch and caches it
+pub fn rebuild(progress: &IndexProgress) -> anyhow::Result<Arc<ReferenceIndex>> {
+    let index = ReferenceIndex::build(progress);
+    if let Err(e) = index.save(INDEX_PATH) {
+        warn!("Failed to write reference index: {e}");
+    }
+
+    Ok(set_index(index))
+}
+
+#[derive(Default)]
+pub struct IndexProgress {
+    pub total: AtomicUsize,
+    pub done: AtomicUsize,
+}
+
+impl IndexProgress {
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            0.0
+        } else {
+            self.done.load(Ordering::Relaxed) as f32 / total as f32
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ReferenceIndex {
+    /// Tags referenced by a tag
+    forward: IntMap<TagHash, Vec<TagHash>>,
+    /// Tags referencing a tag
+    reverse: IntMap<TagHash, Vec<TagHash>>,
+}
+
+impl ReferenceIndex {
+    pub fn build(progress: &IndexProgress) -> ReferenceIndex {
+        let _span = info_span!("Building reference index").entered();
+        let tags = all_tags(|_, _| true);
+        progress.total.store(tags.len(), Ordering::Relaxed);
+
+        let edges: Vec<(TagHash, Vec<TagHash>)> = tags
+            .par_iter()
+            .map(|tag| {
+                let references = tag_references(*tag);
+                progress.done.fetch_add(1, Ordering::Relaxed);
+                (*tag, references)
+            })
+            .filter(|(_, references)| !references.is_empty())
+            .collect();
+
+        let index = Self::from_edges(
+            edges
+                .into_iter()
+                .flat_map(|(from, to)| to.into_iter().map(move |to| (from, to))),
+        );
+        info!(
+            "Indexed {} tags, {} references",
+            tags.len(),
+            index.forward.values().map(Vec::len).sum::<usize>()
+        );
+
+        index
+    }
+
+    fn from_edges(edges: impl Iterator<Item = (TagHash, TagHash)>) -> ReferenceIndex {
+        let mut index = ReferenceIndex::default();
+        for (from, to) in edges {
+            if from == to {
+                continue;
+            }
+
+            index.forward.entry(from).or_default().push(to);
+            index.reverse.entry(to).or_default().push(from);
+        }
+
+        for list in index.forward.values_mut().chain(index.reverse.values_mut()) {
+            list.sort_by_key(|t| t.0);
+            list.dedup();
+        }
+
+        index
+    }
+
+    /// Tags referenced by `tag`
+    pub fn references(&self, tag: TagHash) -> &[TagHash] {
+        self.forward.get(&tag).map_or(&[], Vec::as_slice)
+    }
+
+    /// Tags that reference `tag`
+    pub fn referenced_by(&self, tag: TagHash) -> &[TagHash] {
+        self.reverse.get(&tag).map_or(&[], Vec::as_slice)
+    }
+
+    /// Tags that reference `tag` directly or indirectly, matching `filter` (eg. all maps that use
+    /// a texture). Does not look past tags that match
+    pub fn find_referencing(&self, tag: TagHash, filter: impl Fn(TagHash) -> bool) -> Vec<TagHash> {
+        let mut seen: IntSet<TagHash> = IntSet::default();
+        let mut found = vec![];
+        let mut stack = vec![tag];
+        seen.insert(tag);
+        while let Some(tag) = stack.pop() {
+            for &parent in self.referenced_by(tag) {
+                if !seen.insert(parent) {
+                    continue;
+                }
+
+                if filter(parent) {
+                    found.push(parent);
+                } else {
+                    stack.push(parent);
+                }
+            }
+        }
+
+        found.sort_by_key(|t| t.0);
+        found
+    }
+
+    /// Maps (0x80807dae) that use `tag`
+    pub fn maps_using(&self, tag: TagHash) -> Vec<TagHash> {
+        self.find_referencing(tag, |t| {
+            package_manager()
+                .get_entry(t)
+                .is_ok_and(|e| e.reference == 0x80807dae)
+        })
+    }
+
+    /// Returns `None` if the index was built for a different set of packages
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Option<ReferenceIndex>> {
+        let data = std::fs::read(path)?;
+        Self::from_bytes(&data, packages_fingerprint())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let data = self.to_bytes(packages_fingerprint())?;
+        let path = path.as_ref();
+        std::fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Returns `None` if the data is from another index version, or `fingerprint` doesn't match
+    fn from_bytes(data: &[u8], fingerprint: u64) -> anyhow::Result<Option<ReferenceIndex>> {
+        let file: IndexFile = Cursor::new(data).read_le()?;
+        if file.version != INDEX_VERSION || file.fingerprint != fingerprint {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::from_edges(
+            file.edges
+                .into_iter()
+                .map(|[from, to]| (TagHash(from), TagHash(to))),
+        )))
+    }
+
+    fn to_bytes(&self, fingerprint: u64) -> anyhow::Result<Vec<u8>> {
+        let file = IndexFile {
+            version: INDEX_VERSION,
+            fingerprint,
+            edges: self
+                .forward
+                .iter()
+                .sorted_by_key(|(from, _)| from.0)
+                .flat_map(|(from, to)| to.iter().map(|to| [from.0, to.0]))
+                .collect(),
+        };
+
+        let mut data = Cursor::new(vec![]);
+        data.write_le(&file)?;
+        Ok(data.into_inner())
+    }
+}
+
+#[binrw]
+#[brw(magic = b"ALKREFS\0")]
+struct IndexFile {
+    version: u32,
+    fingerprint: u64,
+    #[bw(calc = edges.len() as u32)]
+    edge_count: u32,
+    #[br(count = edge_count)]
+    edges: Vec<[u32; 2]>,
+}
+
+/// Identifies the loaded package set, so an index is rebuilt after a game update
+fn packages_fingerprint() -> u64 {
+    let paths = package_manager()
+        .package_paths
+        .iter()
+        .sorted_by_key(|(id, _)| **id)
+        .map(|(id, path)| format!("{id:04x}:{path}"))
+        .join("\n");
+
+    fnv1a64(paths.as_bytes())
+}
+
+/// Tags referenced by `tag`, or nothing if it isn't a tag file (raw data, eg. textures and buffers)
+pub fn tag_references(tag: TagHash) -> Vec<TagHash> {
+    let Ok(entry) = package_manager().get_entry(tag) else {
+        return vec![];
+    };
+
+    // Headers of raw data files (eg. textures) point to their data through the entry reference
+    let reference = TagHash(entry.reference);
+    if entry.reference & 0xffff0000 != 0x80800000 {
+        return if dump::is_existing_tag(reference) {
+            vec![reference]
+        } else {
+            vec![]
+        };
+    }
+
+    let Ok(data) = package_manager().read_tag(tag) else {
+        return vec![];
+    };
+
+    let mut references = dump::scan_tag_references(&data);
+    references.extend(
+        data.chunks_exact(8)
+            .filter_map(|c| resolve_hash64(u64::from_le_bytes(c.try_into().unwrap()))),
+    );
+
+    // Map resources are the only link between a map and its contents, so don't rely on the
+    // heuristics for them
+    if entry.reference == 0x808091e0 {
+        if let Ok(resources) = package_manager().read_tag_struct::<Unk808091e0>(tag) {
+            references.extend(resources.map_resources.iter().filter_map(|r| {
+                if r.is_hash32 != 0 {
+                    Some(r.hash32)
+                } else {
+                    resolve_hash64(r.hash64.0)
+                }
+            }));
+        }
+    }
+
+    references.sort_by_key(|t| t.0);
+    references.dedup();
+    references
+}
+
+/// The tag a `TagHash64` refers to, if it exists
+pub fn resolve_hash64(hash: u64) -> Option<TagHash> {
+    package_manager().hash64_table.get(&hash).map(|e| e.hash32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(values: &[u32]) -> Vec<TagHash> {
+        values.iter().map(|v| TagHash(*v)).collect()
+    }
+
+    fn index(edges: &[(u32, u32)]) -> ReferenceIndex {
+        ReferenceIndex::from_edges(
+            edges
+                .iter()
+                .map(|(from, to)| (TagHash(*from), TagHash(*to))),
+        )
+    }
+
+    #[test]
+    fn edges() {
+        let index = index(&[(1, 3), (1, 2), (1, 1), (1, 2), (4, 2)]);
+
+        // Self references are left out, and duplicates are merged
+        assert_eq!(index.references(TagHash(1)), tags(&[2, 3]));
+        assert_eq!(index.referenced_by(TagHash(1)), tags(&[]));
+        assert_eq!(index.referenced_by(TagHash(2)), tags(&[1, 4]));
+        assert_eq!(index.references(TagHash(5)), tags(&[]));
+    }
+
+    #[test]
+    fn find_referencing() {
+        // Texture 1 is used by material 2, which is used by static 3 and map 11 directly. 3 is in
+        // placement group 4 of map 10, and references 5, which references 3 back. Map 10 is used
+        // by activity 12, which also counts as a match
+        let index = index(&[
+            (2, 1),
+            (3, 2),
+            (11, 2),
+            (4, 3),
+            (3, 5),
+            (5, 3),
+            (10, 4),
+            (12, 10),
+        ]);
+        let is_match = |t: TagHash| t.0 >= 10;
+
+        assert_eq!(
+            index.find_referencing(TagHash(1), is_match),
+            tags(&[10, 11])
+        );
+        assert_eq!(index.find_referencing(TagHash(5), is_match), tags(&[10]));
+        assert_eq!(index.find_referencing(TagHash(10), is_match), tags(&[12]));
+        assert_eq!(index.find_referencing(TagHash(12), is_match), tags(&[]));
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let index = index(&[(1, 2), (1, 3), (4, 2)]);
+        let data = index.to_bytes(0x1234).unwrap();
+
+        let loaded = ReferenceIndex::from_bytes(&data, 0x1234).unwrap().unwrap();
+        assert_eq!(loaded.references(TagHash(1)), tags(&[2, 3]));
+        assert_eq!(loaded.referenced_by(TagHash(2)), tags(&[1, 4]));
+
+        // Built for other packages
+        assert!(ReferenceIndex::from_bytes(&data, 0x4321).unwrap().is_none());
+        assert!(ReferenceIndex::from_bytes(&data[..4], 0x1234).is_err());
+    }
+
+    #[test]
+    fn file_version() {
+        let file = IndexFile {
+            version: INDEX_VERSION + 1,
+            fingerprint: 0x1234,
+            edges: vec![[1, 2]],
+        };
+        let mut data = Cursor::new(vec![]);
+        data.write_le(&file).unwrap();
+
+        assert!(ReferenceIndex::from_bytes(data.get_ref(), 0x1234)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,3 +1,4 @@
 pub mod externs;
+#[cfg(windows)]
 pub mod interpreter;
 pub mod opcodes;
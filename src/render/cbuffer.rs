@@ -147,6 +147,13 @@ impl<T> ConstantBuffer<T> {
         })
     }
 
+    /// Size of the buffer in bytes
+    pub fn size(&self) -> usize {
+        let mut desc = D3D11_BUFFER_DESC::default();
+        unsafe { self.buffer.GetDesc(&mut desc) };
+        desc.ByteWidth as usize
+    }
+
     pub fn buffer(&self) -> &ID3D11Buffer {
         &self.buffer
     }
@@ -1,10 +1,10 @@
+use std::fmt::Display;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crossbeam::channel::Sender;
 use destiny_pkg::TagHash;
-use nohash_hasher::IntMap;
-use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use nohash_hasher::{IntMap, IntSet};
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use windows::Win32::Graphics::Direct3D11::*;
 
 use crate::dxgi::DxgiFormat;
@@ -16,9 +16,10 @@ use crate::util::LockTracker;
 
 use super::drawcall::ShadingTechnique;
 use super::renderer::Renderer;
+use super::resource_mt::{self, LoadCallback, LoadPriority, BUFFER_QUEUE, TEXTURE_QUEUE};
 use super::shader::{load_pshader, load_vshader};
 use super::vertex_layout::OutputElement;
-use super::{resource_mt, DeviceContextSwapchain};
+use super::DeviceContextSwapchain;
 
 #[derive(Default)]
 pub struct RenderData {
@@ -31,9 +32,66 @@ pub struct RenderData {
     pub vertex_buffers: IntMap<TagHash, (ID3D11Buffer, u32)>,
     pub index_buffers: IntMap<TagHash, (ID3D11Buffer, DxgiFormat)>,
     pub input_layouts: IntMap<u64, ID3D11InputLayout>,
+
+    /// Number of loaded maps referencing each texture, buffer and material
+    pub refcounts: IntMap<TagHash, usize>,
+
+    /// Size in bytes of each loaded texture, buffer and shader
+    pub sizes: IntMap<TagHash, usize>,
+    /// Frame each texture and buffer was last drawn in
+    pub last_used: IntMap<TagHash, u64>,
+    /// Evicted textures and buffers that have been requested again
+    pub pending_reloads: IntSet<TagHash>,
+    /// Index of the frame being drawn, see [RenderData::last_used]
+    pub frame: u64,
+    /// Number of resources evicted since startup
+    pub evicted: usize,
+
+    /// Reason each resource failed to load, see [RenderDataManager::fail]
+    pub failures: IntMap<TagHash, String>,
+    /// Bound in place of textures that failed to load
+    pub error_texture: Option<Texture>,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct CacheUsage {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Memory used by each of the caches in [RenderData]
+#[derive(Default, Clone, Copy)]
+pub struct MemoryUsage {
+    pub textures: CacheUsage,
+    pub vertex_buffers: CacheUsage,
+    pub index_buffers: CacheUsage,
+    pub materials: CacheUsage,
+    pub shaders: CacheUsage,
+}
+
+impl MemoryUsage {
+    /// Size of the caches that can be evicted (textures and buffers)
+    pub fn evictable(&self) -> usize {
+        self.textures.bytes + self.vertex_buffers.bytes + self.index_buffers.bytes
+    }
+
+    pub fn total(&self) -> usize {
+        self.evictable() + self.materials.bytes + self.shaders.bytes
+    }
 }
 
 impl RenderData {
+    /// Returns the texture, or the error texture if it failed to load
+    pub fn texture_or_error(&self, tag: TagHash) -> Option<&Texture> {
+        self.textures.get(&tag).or_else(|| {
+            if self.failures.contains_key(&tag) {
+                self.error_texture.as_ref()
+            } else {
+                None
+            }
+        })
+    }
+
     // Get the shading technique for a material based on it's pixel shader output signature
     pub fn material_shading_technique(&self, material: TagHash) -> Option<ShadingTechnique> {
         let pixel_shader = self.materials.get(&material)?.pixel_shader;
@@ -44,25 +102,172 @@ impl RenderData {
             Some(ShadingTechnique::Deferred)
         }
     }
+
+    /// Marks textures and buffers as drawn in the current frame. Returns the textures and buffers
+    /// that need to be loaded again, leaving out the ones that failed to load or are already queued
+    pub fn touch(
+        &mut self,
+        textures: &IntSet<TagHash>,
+        buffers: &IntSet<TagHash>,
+    ) -> (Vec<TagHash>, Vec<TagHash>) {
+        let mut reload_textures = vec![];
+        let mut reload_buffers = vec![];
+        for t in textures {
+            self.last_used.insert(*t, self.frame);
+            if !self.textures.contains_key(t)
+                && !self.failures.contains_key(t)
+                && self.pending_reloads.insert(*t)
+            {
+                reload_textures.push(*t);
+            }
+        }
+
+        for b in buffers {
+            self.last_used.insert(*b, self.frame);
+            if !self.vertex_buffers.contains_key(b)
+                && !self.index_buffers.contains_key(b)
+                && !self.failures.contains_key(b)
+                && self.pending_reloads.insert(*b)
+            {
+                reload_buffers.push(*b);
+            }
+        }
+
+        (reload_textures, reload_buffers)
+    }
+
+    /// Adds a reference to each resource, and to the textures of each material
+    pub fn acquire(&mut self, tags: &IntSet<TagHash>) {
+        for tag in self.with_material_textures(tags) {
+            *self.refcounts.entry(tag).or_default() += 1;
+        }
+    }
+
+    /// Removes a reference from each resource (and material texture), unloading the ones that are no longer referenced.
+    /// Returns the number of resources that were unloaded
+    pub fn release(&mut self, tags: &IntSet<TagHash>) -> usize {
+        let mut unloaded = 0;
+        for tag in self.with_material_textures(tags) {
+            let Some(count) = self.refcounts.get_mut(&tag) else {
+                continue;
+            };
+
+            *count -= 1;
+            if *count == 0 {
+                self.refcounts.remove(&tag);
+                if self.remove(tag) {
+                    unloaded += 1;
+                }
+            }
+        }
+
+        unloaded
+    }
+
+    fn with_material_textures(&self, tags: &IntSet<TagHash>) -> IntSet<TagHash> {
+        let mut all = tags.clone();
+        for m in tags.iter().filter_map(|t| self.materials.get(t)) {
+            all.extend(
+                m.ps_textures
+                    .iter()
+                    .chain(m.vs_textures.iter())
+                    .map(|t| t.texture)
+                    .filter(|t| t.is_valid()),
+            );
+        }
+
+        all
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            textures: self.cache_usage(self.textures.keys()),
+            vertex_buffers: self.cache_usage(self.vertex_buffers.keys()),
+            index_buffers: self.cache_usage(self.index_buffers.keys()),
+            materials: CacheUsage {
+                count: self.materials.len(),
+                bytes: self.materials.values().map(|m| m.size()).sum(),
+            },
+            shaders: self.cache_usage(self.vshaders.keys().chain(self.pshaders.keys())),
+        }
+    }
+
+    fn cache_usage<'a>(&self, tags: impl Iterator<Item = &'a TagHash>) -> CacheUsage {
+        let mut usage = CacheUsage::default();
+        for t in tags {
+            usage.count += 1;
+            usage.bytes += self.sizes.get(t).copied().unwrap_or_default();
+        }
+
+        usage
+    }
+
+    /// Evicts the least recently drawn textures and buffers until they fit in `budget` bytes.
+    /// Resources drawn in the current frame are never evicted. Returns the number of evicted resources
+    pub fn evict(&mut self, budget: usize) -> usize {
+        let mut size = self.memory_usage().evictable();
+        if size <= budget {
+            return 0;
+        }
+
+        let mut candidates: Vec<(u64, TagHash)> = self
+            .textures
+            .keys()
+            .chain(self.vertex_buffers.keys())
+            .chain(self.index_buffers.keys())
+            .map(|t| (self.last_used.get(t).copied().unwrap_or_default(), *t))
+            .filter(|(frame, _)| *frame < self.frame)
+            .collect();
+        candidates.sort_unstable_by_key(|(frame, t)| (*frame, t.0));
+
+        let mut evicted = 0;
+        for (_, tag) in candidates {
+            if size <= budget {
+                break;
+            }
+
+            size -= self.sizes.get(&tag).copied().unwrap_or_default();
+            self.remove(tag);
+            evicted += 1;
+        }
+
+        self.evicted += evicted;
+        evicted
+    }
+
+    fn remove(&mut self, tag: TagHash) -> bool {
+        self.sizes.remove(&tag);
+        self.last_used.remove(&tag);
+        self.textures.remove(&tag).is_some()
+            | self.vertex_buffers.remove(&tag).is_some()
+            | self.index_buffers.remove(&tag).is_some()
+            | self.materials.remove(&tag).is_some()
+    }
 }
 
 pub struct RenderDataManager {
-    tx_textures: Sender<TagHash>,
-    tx_buffers: Sender<TagHash>,
-    // tx_shaders: Sender<TagHash>,
     render_data: Arc<RwLock<RenderData>>,
+    /// Stack of dependency sets, see [RenderDataManager::track]
+    tracking: Mutex<Vec<IntSet<TagHash>>>,
+    /// Priority given to texture and buffer requests, see [RenderDataManager::with_priority]
+    priority: Mutex<LoadPriority>,
 }
 
 impl RenderDataManager {
     pub fn new(dcs: Arc<DeviceContextSwapchain>) -> Self {
-        let render_data = Arc::new(RwLock::new(RenderData::default()));
-        let tx_textures = resource_mt::thread_textures(dcs.clone(), render_data.clone());
-        let tx_buffers = resource_mt::thread_buffers(dcs.clone(), render_data.clone());
+        let error_texture = error_texture(&dcs)
+            .map_err(|e| error!("Failed to create error texture: {e}"))
+            .ok();
+        let render_data = Arc::new(RwLock::new(RenderData {
+            error_texture,
+            ..Default::default()
+        }));
+        resource_mt::spawn_loaders(dcs, render_data.clone());
 
         Self {
-            tx_textures,
-            tx_buffers,
             render_data,
+            tracking: Mutex::new(vec![]),
+            priority: Mutex::new(LoadPriority::Background),
         }
     }
 
@@ -118,18 +323,107 @@ impl RenderDataManager {
         l
     }
 
+    /// Runs `f`, returning every texture, buffer and material requested through this manager while it ran.
+    /// Calls can be nested, in which case the outer call also receives the resources of the inner ones
+    pub fn track<R>(&self, f: impl FnOnce() -> R) -> (R, IntSet<TagHash>) {
+        self.tracking.lock().push(IntSet::default());
+        let result = f();
+        let tracked = self.tracking.lock().pop().unwrap_or_default();
+
+        (result, tracked)
+    }
+
+    /// Records a resource as a dependency of whatever is currently being tracked
+    pub fn record(&self, tag: TagHash) {
+        if !tag.is_valid() {
+            return;
+        }
+
+        for set in self.tracking.lock().iter_mut() {
+            set.insert(tag);
+        }
+    }
+
+    /// Records why a resource failed to load. Textures that failed are replaced by the error texture
+    pub fn fail(&self, tag: TagHash, reason: impl Display) {
+        let reason = format!("{reason:#}");
+        error!("Failed to load {tag}: {reason}");
+        self.data_mut().failures.insert(tag, reason);
+    }
+
+    /// Runs `f`, requesting every texture and buffer it loads with the given priority
+    pub fn with_priority<R>(&self, priority: LoadPriority, f: impl FnOnce() -> R) -> R {
+        let previous = std::mem::replace(&mut *self.priority.lock(), priority);
+        let result = f();
+        *self.priority.lock() = previous;
+
+        result
+    }
+
+    /// Updates the priority of queued texture and buffer requests, see [resource_mt::LoadQueue::prioritize]
+    pub fn prioritize(&self, priorities: &IntMap<TagHash, LoadPriority>) {
+        TEXTURE_QUEUE.prioritize(priorities);
+        BUFFER_QUEUE.prioritize(priorities);
+    }
+
+    /// Cancels the queued requests for the given resources that are no longer referenced by a loaded map
+    pub fn cancel_unreferenced(&self, tags: &IntSet<TagHash>) -> usize {
+        let unreferenced: IntSet<TagHash> = {
+            let data = self.data();
+            tags.iter()
+                .filter(|t| !data.refcounts.contains_key(*t))
+                .cloned()
+                .collect()
+        };
+
+        TEXTURE_QUEUE.cancel(|t| unreferenced.contains(&t))
+            + BUFFER_QUEUE.cancel(|t| unreferenced.contains(&t))
+    }
+
+    /// Marks textures and buffers as drawn in the current frame, and requests the ones that were evicted to be loaded again
+    pub fn touch(&self, textures: &IntSet<TagHash>, buffers: &IntSet<TagHash>) {
+        let (reload_textures, reload_buffers) = self.data_mut().touch(textures, buffers);
+
+        self.with_priority(LoadPriority::Visible, || {
+            for t in reload_textures {
+                self.load_texture_with(t, self.reload_callback());
+            }
+
+            for b in reload_buffers {
+                self.load_buffer_with(b, self.reload_callback());
+            }
+        });
+    }
+
+    fn reload_callback(&self) -> LoadCallback {
+        let render_data = self.render_data.clone();
+        Box::new(move |tag, _| {
+            render_data.write().pending_reloads.remove(&tag);
+        })
+    }
+
     /// Load a Texture2D, Texture2D or TextureCube from a hash
     pub fn load_texture(&self, texture: TagHash) {
-        self.tx_textures
-            .send(texture)
-            .expect("Failed to send load texture request");
+        self.record(texture);
+        TEXTURE_QUEUE.push(texture, *self.priority.lock(), None);
+    }
+
+    /// Like [RenderDataManager::load_texture], calling `callback` once the request has been handled
+    pub fn load_texture_with(&self, texture: TagHash, callback: LoadCallback) {
+        self.record(texture);
+        TEXTURE_QUEUE.push(texture, *self.priority.lock(), Some(callback));
     }
 
     /// Load a vertex or index buffer from a hash
     pub fn load_buffer(&self, buffer: TagHash) {
-        self.tx_buffers
-            .send(buffer)
-            .expect("Failed to send load buffer request");
+        self.record(buffer);
+        BUFFER_QUEUE.push(buffer, *self.priority.lock(), None);
+    }
+
+    /// Like [RenderDataManager::load_buffer], calling `callback` once the request has been handled
+    pub fn load_buffer_with(&self, buffer: TagHash, callback: LoadCallback) {
+        self.record(buffer);
+        BUFFER_QUEUE.push(buffer, *self.priority.lock(), Some(callback));
     }
 
     // pub fn load_sampler(&self, dcs: &DeviceContextSwapchain, hash: TagHash) {
@@ -158,13 +452,31 @@ impl RenderDataManager {
             return None;
         }
 
-        Some(self.data_mut().vshaders.entry(hash).or_insert_with(|| {
-            let shader_header_ref = package_manager().get_entry(hash).unwrap().reference;
-            let shader_data = package_manager().read_tag(shader_header_ref).unwrap();
-            let v = load_vshader(dcs, &shader_data).unwrap();
-            (v.0, v.1, shader_data)
-        }))
-        .cloned()
+        if let Some(v) = self.data().vshaders.get(&hash) {
+            return Some(v.clone());
+        }
+
+        let result = (|| -> anyhow::Result<_> {
+            let shader_header_ref = package_manager().get_entry(hash)?.reference;
+            let shader_data = package_manager().read_tag(shader_header_ref)?;
+            let (shader, inputs) = load_vshader(dcs, &shader_data)?;
+
+            Ok((shader, inputs, shader_data))
+        })();
+
+        match result {
+            Ok(v) => {
+                let mut data = self.data_mut();
+                data.sizes.insert(hash, v.2.len());
+                data.vshaders.insert(hash, v.clone());
+
+                Some(v)
+            }
+            Err(e) => {
+                self.fail(hash, e);
+                None
+            }
+        }
     }
 
     pub fn load_pshader(&self, dcs: &DeviceContextSwapchain, hash: TagHash) {
@@ -172,11 +484,26 @@ impl RenderDataManager {
             return;
         }
 
-        self.data_mut().pshaders.entry(hash).or_insert_with(|| {
-            let shader_header_ref = package_manager().get_entry(hash).unwrap().reference;
-            let shader_data = package_manager().read_tag(shader_header_ref).unwrap();
-            load_pshader(dcs, &shader_data).unwrap()
-        });
+        if self.data().pshaders.contains_key(&hash) {
+            return;
+        }
+
+        let result = (|| -> anyhow::Result<_> {
+            let shader_header_ref = package_manager().get_entry(hash)?.reference;
+            let shader_data = package_manager().read_tag(shader_header_ref)?;
+            let shader = load_pshader(dcs, &shader_data)?;
+
+            Ok((shader, shader_data.len()))
+        })();
+
+        match result {
+            Ok((shader, size)) => {
+                let mut data = self.data_mut();
+                data.sizes.insert(hash, size);
+                data.pshaders.insert(hash, shader);
+            }
+            Err(e) => self.fail(hash, e),
+        }
     }
 
     pub fn load_material(&self, renderer: &Renderer, material: TagHash) {
@@ -184,16 +511,79 @@ impl RenderDataManager {
             return;
         }
 
-        self.data_mut()
-            .materials
-            .entry(material)
-            .or_insert_with(|| {
-                Material::load(
-                    renderer,
-                    package_manager().read_tag_struct(material).unwrap(),
-                    material,
-                    false,
-                )
-            });
+        self.record(material);
+        if self.data().materials.contains_key(&material) {
+            return;
+        }
+
+        let result = package_manager()
+            .read_tag_struct(material)
+            .map_err(anyhow::Error::from)
+            .and_then(|mat| Material::load(renderer, mat, material, false));
+
+        match result {
+            Ok(mat) => {
+                self.data_mut().materials.entry(material).or_insert(mat);
+            }
+            Err(e) => self.fail(material, e),
+        }
+    }
+}
+
+/// Magenta and black checkerboard
+fn error_texture(dcs: &DeviceContextSwapchain) -> anyhow::Result<Texture> {
+    const SIZE: usize = 64;
+    const CHECKER_SIZE: usize = 8;
+
+    let mut data = Vec::with_capacity(SIZE * SIZE * 4);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if (x / CHECKER_SIZE + y / CHECKER_SIZE) % 2 == 0 {
+                data.extend_from_slice(&[255, 0, 255, 255]);
+            } else {
+                data.extend_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+    }
+
+    Texture::load_2d_raw(
+        dcs,
+        SIZE as u32,
+        SIZE as u32,
+        &data,
+        DxgiFormat::R8G8B8A8_UNORM,
+        Some("Error texture"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touch_requeues_evicted_resources_once() {
+        let mut data = RenderData::default();
+        let textures = IntSet::from_iter([TagHash(1)]);
+        let buffers = IntSet::from_iter([TagHash(2)]);
+
+        assert_eq!(
+            data.touch(&textures, &buffers),
+            (vec![TagHash(1)], vec![TagHash(2)])
+        );
+        // Still being reloaded
+        assert_eq!(data.touch(&textures, &buffers), (vec![], vec![]));
+    }
+
+    #[test]
+    fn touch_skips_failed_resources() {
+        let mut data = RenderData::default();
+        data.failures.insert(TagHash(1), "Failed".to_string());
+        data.failures.insert(TagHash(2), "Failed".to_string());
+        let textures = IntSet::from_iter([TagHash(1), TagHash(3)]);
+        let buffers = IntSet::from_iter([TagHash(2)]);
+
+        assert_eq!(data.touch(&textures, &buffers), (vec![TagHash(3)], vec![]));
+        assert!(!data.pending_reloads.contains(&TagHash(1)));
+        assert_eq!(data.last_used.get(&TagHash(1)), Some(&data.frame));
     }
 }
@@ -3,13 +3,15 @@
 use std::{io::Cursor, sync::Arc};
 
 use glam::Mat4;
-use windows::Win32::Graphics::{Direct3D11::*, Dxgi::Common::*};
+use windows::Win32::Graphics::{
+    Direct3D::D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST, Direct3D11::*, Dxgi::Common::*,
+};
 
 use crate::{dxgi::DxgiFormat, render::shader, texture::Texture};
 
+use super::drawcall::ShaderStages;
 use super::{ConstantBuffer, DeviceContextSwapchain};
 
-#[allow(unused)]
 pub struct ErrorRenderer {
     vertex_buffer: ID3D11Buffer,
     vertex_count: usize,
@@ -20,6 +22,9 @@ pub struct ErrorRenderer {
     pshader: ID3D11PixelShader,
 
     scope: ConstantBuffer<AlkScopeError>,
+
+    /// Model matrices queued for the current frame, see [ErrorRenderer::push]
+    transforms: Vec<Mat4>,
 }
 
 impl ErrorRenderer {
@@ -124,55 +129,55 @@ impl ErrorRenderer {
             vshader,
             pshader,
             scope: ConstantBuffer::create(dcs, None).unwrap(),
+            transforms: vec![],
         }
     }
 
-    // TODO(cohae): Get this to work with the new renderer
-    // pub fn draw(
-    //     &self,
-    //     renderer: &mut Renderer,
-    //     transform: ID3D11Buffer,
-    //     proj_view: Mat4,
-    //     view: Mat4,
-    // ) {
-    //     self.scope
-    //         .write(&AlkScopeError {
-    //             proj_view,
-    //             view,
-    //             model: transform,
-    //         })
-    //         .unwrap();
-
-    //     unsafe {
-    //         dcs.context().IASetVertexBuffers(
-    //             0,
-    //             1,
-    //             Some([Some(self.vertex_buffer.clone())].as_ptr()),
-    //             Some([6 * 4].as_ptr()),
-    //             Some(&0),
-    //         );
-
-    //         dcs.context
-    //             .IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
-
-    //         dcs.context
-    //             .VSSetConstantBuffers(7, Some(&[Some(self.scope.buffer().clone())]));
-
-    //         dcs.context().IASetInputLayout(&self.vertex_layout);
-    //         dcs.context().VSSetShader(&self.vshader, None);
-
-    //         dcs.context().PSSetShader(&self.pshader, None);
-
-    // TODO(cohae): use new texture bind API
-    //         dcs.context
-    //             .PSSetShaderResources(0, Some(&[Some(self.texture.view.clone())]));
-
-    //         dcs.context().Draw(self.vertex_count as u32, 0);
-    //     }
-    // }
+    /// Queues the error mesh to be drawn with the given transform this frame
+    pub fn push(&mut self, transform: Mat4) {
+        self.transforms.push(transform);
+    }
+
+    /// Draws and clears the queued error meshes. Expects the gbuffer render targets to be bound
+    pub fn draw_queued(&mut self, dcs: &DeviceContextSwapchain, proj_view: Mat4, view: Mat4) {
+        for model in self.transforms.drain(..) {
+            if self
+                .scope
+                .write(&AlkScopeError {
+                    proj_view,
+                    view,
+                    model,
+                })
+                .is_err()
+            {
+                continue;
+            }
+
+            self.scope.bind(7, ShaderStages::VERTEX);
+            unsafe {
+                dcs.context().IASetVertexBuffers(
+                    0,
+                    1,
+                    Some([Some(self.vertex_buffer.clone())].as_ptr()),
+                    Some([6 * 4].as_ptr()),
+                    Some(&0),
+                );
+
+                dcs.context()
+                    .IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+
+                dcs.context().IASetInputLayout(&self.vertex_layout);
+                dcs.context().VSSetShader(&self.vshader, None);
+                dcs.context().PSSetShader(&self.pshader, None);
+                dcs.context()
+                    .PSSetShaderResources(0, Some(&[Some(self.texture.view.clone())]));
+
+                dcs.context().Draw(self.vertex_count as u32, 0);
+            }
+        }
+    }
 }
 
-#[allow(unused)]
 #[repr(C)]
 struct AlkScopeError {
     pub proj_view: Mat4,
@@ -1,28 +1,55 @@
+//! Direct3D 11 rendering. Everything except the shader bytecode and scope layouts is only built on
+//! Windows
+
 pub mod bytecode;
+#[cfg(windows)]
 pub mod cbuffer;
+#[cfg(windows)]
 pub mod color;
+#[cfg(windows)]
 pub mod data;
+#[cfg(windows)]
 pub mod dcs;
+#[cfg(windows)]
 pub mod debug;
+#[cfg(windows)]
 pub mod drawcall;
+#[cfg(windows)]
 pub mod entity;
+#[cfg(windows)]
 pub mod error;
+#[cfg(windows)]
 pub mod gbuffer;
+#[cfg(windows)]
 pub mod renderer;
+#[cfg(windows)]
 pub mod resource_mt;
 pub mod scopes;
 pub mod shader;
+#[cfg(windows)]
 pub mod static_instanced;
+#[cfg(windows)]
 pub mod static_render;
+#[cfg(windows)]
 pub mod terrain;
+#[cfg(windows)]
 mod vertex_buffers;
+#[cfg(windows)]
 pub mod vertex_layout;
 
+#[cfg(windows)]
 pub use cbuffer::ConstantBuffer;
+#[cfg(windows)]
 pub use data::RenderData;
+#[cfg(windows)]
 pub use dcs::DeviceContextSwapchain;
+#[cfg(windows)]
 pub use entity::EntityRenderer;
+#[cfg(windows)]
 pub use gbuffer::GBuffer;
+#[cfg(windows)]
 pub use static_instanced::InstancedRenderer;
+#[cfg(windows)]
 pub use static_render::StaticModel;
+#[cfg(windows)]
 pub use terrain::TerrainRenderer;
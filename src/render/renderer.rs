@@ -1,6 +1,7 @@
 use std::{sync::Arc, time::Instant};
 
 use glam::Mat4;
+use nohash_hasher::IntSet;
 use windows::Win32::Graphics::Direct3D::D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP;
 use windows::Win32::Graphics::Direct3D11::*;
 use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT;
@@ -18,6 +19,7 @@ use crate::{camera::FpsCamera, resources::Resources};
 use super::data::RenderDataManager;
 use super::debug::{DebugShapeRenderer, DebugShapes};
 use super::drawcall::Transparency;
+use super::error::ErrorRenderer;
 use super::scopes::ScopeUnk8;
 use super::{
     drawcall::{DrawCall, ShadingTechnique, SortValue3d},
@@ -272,7 +274,7 @@ impl Renderer {
         alpha_blending: bool,
         compositor_mode: usize,
         blend_override: usize,
-        lights: (ID3D11Buffer, usize),
+        lights: Option<(ID3D11Buffer, usize)>,
         evaluate_bytecode: bool,
     ) {
         if self.state != RendererState::Recording {
@@ -282,6 +284,8 @@ impl Renderer {
         self.draw_queue
             .sort_unstable_by(|(o1, _), (o2, _)| o1.cmp(o2));
 
+        self.touch_resources(resources);
+
         self.update_buffers(resources)
             .expect("Renderer::update_buffers");
 
@@ -322,6 +326,21 @@ impl Renderer {
             let (s, d) = self.draw_queue[i].clone();
             self.draw(s, &d);
         }
+
+        if let Some(mut error_renderer) = resources.get_mut::<ErrorRenderer>() {
+            let mut camera = resources.get_mut::<FpsCamera>().unwrap();
+            let projection = Mat4::perspective_infinite_reverse_rh(
+                90f32.to_radians(),
+                self.window_size.0 as f32 / self.window_size.1 as f32,
+                0.0001,
+            );
+            let view = camera.calculate_matrix();
+
+            unsafe {
+                self.dcs.context().RSSetState(&self.rasterizer_state);
+            }
+            error_renderer.draw_queued(&self.dcs, projection * view, view);
+        }
         //endregion
 
         self.gbuffer.depth.copy_depth(self.dcs.context());
@@ -453,6 +472,44 @@ impl Renderer {
         self.state = RendererState::Awaiting;
     }
 
+    /// Marks the textures and buffers used by the recorded drawcalls as used in this frame, so they aren't evicted
+    fn touch_resources(&self, resources: &Resources) {
+        let mut textures = IntSet::default();
+        if let Some(cubemap) = resources.get::<CurrentCubemap>().unwrap().1 {
+            textures.insert(cubemap);
+        }
+
+        let mut buffers = IntSet::default();
+        {
+            let mut data = self.render_data.data_mut();
+            data.frame += 1;
+
+            for (sort, drawcall) in &self.draw_queue {
+                for material in [Some(sort.material().into()), drawcall.variant_material]
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Some(mat) = data.materials.get(&material) {
+                        textures.extend(
+                            mat.ps_textures
+                                .iter()
+                                .chain(mat.vs_textures.iter())
+                                .map(|t| t.texture)
+                                .filter(|t| t.is_valid()),
+                        );
+                    }
+                }
+
+                buffers.extend(drawcall.vertex_buffers.iter().filter(|b| b.is_valid()));
+                if drawcall.index_buffer.is_valid() {
+                    buffers.insert(drawcall.index_buffer);
+                }
+            }
+        }
+
+        self.render_data.touch(&textures, &buffers);
+    }
+
     fn draw(&mut self, sort: SortValue3d, drawcall: &DrawCall) {
         let render_data = self.render_data.data();
         if let Some(mat) = render_data.materials.get(&sort.material().into()) {
@@ -472,8 +529,9 @@ impl Renderer {
             if mat.bind(&self.dcs, &render_data).is_err() {
                 // return;
             }
-        } else {
-            // return;
+        } else if render_data.failures.contains_key(&sort.material().into()) {
+            // Don't draw with whatever material happens to be bound
+            return;
         }
 
         if let Some(variant_material) = drawcall.variant_material {
@@ -572,7 +630,7 @@ impl Renderer {
         resources: &Resources,
         draw_lights: bool,
         compositor_mode: usize,
-        lights: (ID3D11Buffer, usize),
+        lights: Option<(ID3D11Buffer, usize)>,
     ) {
         unsafe {
             self.dcs.context().OMSetBlendState(
@@ -629,7 +687,10 @@ impl Renderer {
                     camera_dir: camera.front.extend(1.0),
                     time: self.start_time.elapsed().as_secs_f32(),
                     mode: compositor_mode as u32,
-                    light_count: if draw_lights { lights.1 as u32 } else { 0 },
+                    light_count: match &lights {
+                        Some((_, count)) if draw_lights => *count as u32,
+                        _ => 0,
+                    },
                 };
                 self.scope_alk_composite.write(&compositor_options).unwrap();
                 self.scope_alk_composite.bind(0, ShaderStages::all());
@@ -642,7 +703,7 @@ impl Renderer {
 
             self.dcs
                 .context()
-                .PSSetConstantBuffers(1, Some(&[Some(lights.0)]));
+                .PSSetConstantBuffers(1, Some(&[lights.map(|(buffer, _)| buffer)]));
 
             self.dcs.context().RSSetViewports(Some(&[D3D11_VIEWPORT {
                 TopLeftX: 0.0,
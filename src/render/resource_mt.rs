@@ -1,9 +1,12 @@
 use anyhow::Context;
-use crossbeam::channel::{self as mpsc, Receiver};
 use destiny_pkg::TagHash;
-use parking_lot::RwLock;
+use lazy_static::lazy_static;
+use nohash_hasher::IntMap;
+use parking_lot::{Condvar, Mutex, RwLock};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
 use std::time::Instant;
-use std::{sync::Arc};
 use windows::Win32::Graphics::Direct3D11::{
     D3D11_BIND_INDEX_BUFFER, D3D11_BIND_VERTEX_BUFFER, D3D11_BUFFER_DESC, D3D11_SUBRESOURCE_DATA,
     D3D11_USAGE_IMMUTABLE,
@@ -18,198 +21,549 @@ use crate::{
 
 use super::{DeviceContextSwapchain, RenderData};
 
-#[derive(PartialEq, Eq, Clone)]
-pub enum LoadingThreadState {
-    Idle,
-    Loading {
-        start_time: Instant,
-        remaining: usize,
-    },
-}
-
-pub static STATUS_TEXTURES: RwLock<LoadingThreadState> = RwLock::new(LoadingThreadState::Idle);
-pub static STATUS_BUFFERS: RwLock<LoadingThreadState> = RwLock::new(LoadingThreadState::Idle);
-// pub static STATUS_SHADERS: RwLock<LoadingThreadState> = RwLock::new(LoadingThreadState::Idle);
-
-fn update_status(state: &RwLock<LoadingThreadState>, remaining: usize) {
-    let status = state.read().clone();
-    if remaining == 0 {
-        *state.write() = LoadingThreadState::Idle;
-    } else {
-        match status {
-            LoadingThreadState::Idle => {
-                *state.write() = LoadingThreadState::Loading {
-                    start_time: Instant::now(),
-                    remaining,
+lazy_static! {
+    pub static ref TEXTURE_QUEUE: LoadQueue = LoadQueue::new("Texture");
+    pub static ref BUFFER_QUEUE: LoadQueue = LoadQueue::new("Buffer");
+}
+
+/// Determines the order in which requests are loaded. Requests with the same priority are loaded
+/// in the order they were made
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadPriority {
+    /// Needed by something that was drawn this frame
+    Visible,
+    /// Used by something at the given distance from the camera, closer is loaded first
+    Distance(f32),
+    /// Not needed for drawing yet
+    Background,
+}
+
+impl LoadPriority {
+    /// Lower ranks are loaded first
+    fn rank(&self) -> (u8, f32) {
+        match self {
+            LoadPriority::Visible => (0, 0.0),
+            LoadPriority::Distance(d) => (1, *d),
+            LoadPriority::Background => (2, 0.0),
+        }
+    }
+
+    fn cmp_rank(&self, other: &LoadPriority) -> Ordering {
+        let (rank, distance) = self.rank();
+        let (other_rank, other_distance) = other.rank();
+        rank.cmp(&other_rank)
+            .then_with(|| distance.total_cmp(&other_distance))
+    }
+
+    pub fn is_higher_than(&self, other: &LoadPriority) -> bool {
+        self.cmp_rank(other) == Ordering::Less
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadOutcome {
+    Loaded,
+    Failed,
+    /// The request was cancelled before a worker picked it up
+    Cancelled,
+}
+
+/// Called from the loading thread once a request has been handled
+pub type LoadCallback = Box<dyn FnOnce(TagHash, LoadOutcome) + Send>;
+
+struct HeapEntry {
+    tag: TagHash,
+    priority: LoadPriority,
+    sequence: u64,
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap pops the greatest entry first, so both comparisons are reversed
+        other
+            .priority
+            .cmp_rank(&self.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+struct PendingRequest {
+    priority: LoadPriority,
+    /// Sequence of the heap entry for the current priority, older entries are stale
+    sequence: u64,
+    callbacks: Vec<LoadCallback>,
+}
+
+#[derive(Clone, Default)]
+pub struct QueueStats {
+    pub workers: usize,
+    pub queued: usize,
+    pub in_flight: usize,
+    /// Requests handled since the queue was last idle
+    pub loaded: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    /// Time at which the queue stopped being idle
+    pub start_time: Option<Instant>,
+}
+
+impl QueueStats {
+    pub fn is_idle(&self) -> bool {
+        self.queued == 0 && self.in_flight == 0
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Can contain stale entries for requests that were reprioritized, cancelled or already
+    /// taken by a worker. `pending` is the source of truth, see [PendingRequest::sequence]
+    heap: BinaryHeap<HeapEntry>,
+    pending: IntMap<TagHash, PendingRequest>,
+    next_sequence: u64,
+    stats: QueueStats,
+}
+
+impl QueueState {
+    /// (Re)queues the pending request for `tag` with `priority`
+    fn push_entry(&mut self, tag: TagHash, priority: LoadPriority) {
+        let Some(request) = self.pending.get_mut(&tag) else {
+            return;
+        };
+
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        request.priority = priority;
+        request.sequence = sequence;
+        self.heap.push(HeapEntry {
+            tag,
+            priority,
+            sequence,
+        });
+    }
+
+    fn finish_batch_if_idle(&mut self) {
+        if self.pending.is_empty() && self.stats.in_flight == 0 {
+            self.heap.clear();
+            self.stats = QueueStats {
+                workers: self.stats.workers,
+                ..Default::default()
+            };
+        }
+    }
+}
+
+/// Priority queue of load requests shared by a pool of loading threads. A tag that is requested
+/// again while still queued is only loaded once, with the highest priority it was requested with
+/// (or the one it was last given through [LoadQueue::prioritize])
+pub struct LoadQueue {
+    pub name: &'static str,
+    state: Mutex<QueueState>,
+    available: Condvar,
+}
+
+impl LoadQueue {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            state: Mutex::new(QueueState::default()),
+            available: Condvar::new(),
+        }
+    }
+
+    pub fn push(&self, tag: TagHash, priority: LoadPriority, callback: Option<LoadCallback>) {
+        let mut state = self.state.lock();
+        if state.stats.start_time.is_none() {
+            state.stats.start_time = Some(Instant::now());
+        }
+
+        let requeue = match state.pending.get_mut(&tag) {
+            Some(request) => {
+                request.callbacks.extend(callback);
+                priority.is_higher_than(&request.priority)
+            }
+            None => {
+                state.pending.insert(
+                    tag,
+                    PendingRequest {
+                        priority,
+                        sequence: 0,
+                        callbacks: callback.into_iter().collect(),
+                    },
+                );
+                true
+            }
+        };
+
+        if requeue {
+            state.push_entry(tag, priority);
+            self.available.notify_one();
+        }
+    }
+
+    /// Blocks until a request is available
+    fn pop(&self) -> (TagHash, Vec<LoadCallback>) {
+        let mut state = self.state.lock();
+        loop {
+            while let Some(entry) = state.heap.pop() {
+                let current = state
+                    .pending
+                    .get(&entry.tag)
+                    .map_or(false, |r| r.sequence == entry.sequence);
+
+                if current {
+                    let request = state.pending.remove(&entry.tag).unwrap();
+                    state.stats.in_flight += 1;
+                    return (entry.tag, request.callbacks);
                 }
             }
-            LoadingThreadState::Loading { start_time, .. } => {
-                *state.write() = LoadingThreadState::Loading {
-                    start_time,
-                    remaining,
+
+            self.available.wait(&mut state);
+        }
+    }
+
+    fn finish(&self, tag: TagHash, callbacks: Vec<LoadCallback>, outcome: LoadOutcome) {
+        {
+            let mut state = self.state.lock();
+            state.stats.in_flight -= 1;
+            match outcome {
+                LoadOutcome::Loaded => state.stats.loaded += 1,
+                LoadOutcome::Failed => state.stats.failed += 1,
+                LoadOutcome::Cancelled => state.stats.cancelled += 1,
+            }
+            state.finish_batch_if_idle();
+        }
+
+        for callback in callbacks {
+            callback(tag, outcome);
+        }
+    }
+
+    /// Replaces the priority of queued requests, so requests that are no longer needed soon can
+    /// move down the queue. Requests missing from `priorities` keep their priority
+    pub fn prioritize(&self, priorities: &IntMap<TagHash, LoadPriority>) {
+        let mut state = self.state.lock();
+        let changed: Vec<(TagHash, LoadPriority)> = state
+            .pending
+            .iter()
+            .filter_map(|(tag, request)| {
+                let priority = priorities.get(tag)?;
+                (priority.cmp_rank(&request.priority) != Ordering::Equal)
+                    .then_some((*tag, *priority))
+            })
+            .collect();
+
+        for (tag, priority) in changed {
+            state.push_entry(tag, priority);
+        }
+    }
+
+    /// Removes every queued request matching `predicate`, returning the number of cancelled requests.
+    /// Requests that are already being loaded are not affected
+    pub fn cancel(&self, predicate: impl Fn(TagHash) -> bool) -> usize {
+        let mut cancelled = vec![];
+        {
+            let mut state = self.state.lock();
+            let tags: Vec<TagHash> = state
+                .pending
+                .keys()
+                .filter(|t| predicate(**t))
+                .cloned()
+                .collect();
+
+            for tag in tags {
+                if let Some(request) = state.pending.remove(&tag) {
+                    cancelled.push((tag, request.callbacks));
                 }
             }
+
+            state.stats.cancelled += cancelled.len();
+            state.finish_batch_if_idle();
+        }
+
+        let count = cancelled.len();
+        for (tag, callbacks) in cancelled {
+            for callback in callbacks {
+                callback(tag, LoadOutcome::Cancelled);
+            }
+        }
+
+        count
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let state = self.state.lock();
+        QueueStats {
+            queued: state.pending.len(),
+            ..state.stats.clone()
         }
     }
 }
 
-fn spawn_thread_textures(
+type LoadFn = fn(&DeviceContextSwapchain, &RwLock<RenderData>, TagHash) -> anyhow::Result<()>;
+
+fn spawn_workers(
+    queue: &'static LoadQueue,
+    count: usize,
     dcs: Arc<DeviceContextSwapchain>,
     data: Arc<RwLock<RenderData>>,
-    rx: Receiver<TagHash>,
-    name: &'static str,
+    load: LoadFn,
 ) {
-    std::thread::Builder::new()
-        .name(name.to_string())
-        .spawn(move || {
-            while let Ok(hash) = rx.recv() {
-                if hash.is_valid() && !data.read().textures.contains_key(&hash) {
-                    match Texture::load(&dcs, hash) {
-                        Ok(t) => {
-                            data.write().textures.insert(hash, t);
-                        }
-                        Err(e) => error!("Failed to load texture {hash}: {e}"),
-                    }
-                }
+    let count = count.max(1);
+    queue.state.lock().stats.workers = count;
 
-                update_status(&STATUS_TEXTURES, rx.len());
-            }
+    for i in 0..count {
+        let dcs = dcs.clone();
+        let data = data.clone();
+        std::thread::Builder::new()
+            .name(format!("{} loader {}", queue.name, i + 1))
+            .spawn(move || loop {
+                let (hash, callbacks) = queue.pop();
+                let outcome = match load(&dcs, &data, hash) {
+                    Ok(()) => {
+                        data.write().failures.remove(&hash);
+                        LoadOutcome::Loaded
+                    }
+                    Err(e) => {
+                        let reason = format!("{e:#}");
+                        error!(
+                            "Failed to load {} {hash}: {reason}",
+                            queue.name.to_lowercase()
+                        );
+                        data.write().failures.insert(hash, reason);
+                        LoadOutcome::Failed
+                    }
+                };
 
-            info!("Thread '{}' exited", name);
-        })
-        .unwrap();
+                queue.finish(hash, callbacks, outcome);
+            })
+            .unwrap();
+    }
 }
 
-pub fn thread_textures(
-    dcs: Arc<DeviceContextSwapchain>,
-    data: Arc<RwLock<RenderData>>,
-) -> mpsc::Sender<TagHash> {
-    let (tx, rx) = mpsc::unbounded::<TagHash>();
+fn load_texture(
+    dcs: &DeviceContextSwapchain,
+    data: &RwLock<RenderData>,
+    hash: TagHash,
+) -> anyhow::Result<()> {
+    if !hash.is_valid() || data.read().textures.contains_key(&hash) {
+        return Ok(());
+    }
 
-    spawn_thread_textures(dcs.clone(), data.clone(), rx.clone(), "Texture loader 1");
-    spawn_thread_textures(dcs, data, rx, "Texture loader 2");
+    let t = Texture::load(dcs, hash)?;
+    let mut data = data.write();
+    data.sizes.insert(hash, t.size);
+    data.textures.insert(hash, t);
 
-    tx
+    Ok(())
 }
 
-fn spawn_thread_buffers(
-    dcs: Arc<DeviceContextSwapchain>,
-    data: Arc<RwLock<RenderData>>,
-    rx: Receiver<TagHash>,
-    name: &'static str,
-) {
-    std::thread::Builder::new()
-        .name(name.to_string())
-        .spawn(move || {
-            while let Ok(hash) = rx.recv() {
-                if hash.is_valid() {
-                    info!("Loadin buffer {hash}");
-                    if let Ok(entry) = package_manager().get_entry(hash) {
-                        match (entry.file_type, entry.file_subtype) {
-                            // Vertex buffer
-                            (32, 4) => {
-                                if data.read().vertex_buffers.contains_key(&hash) {
-                                    continue;
-                                }
-
-                                match package_manager().read_tag(entry.reference) {
-                                    Ok(vertex_data) => {
-                                        let vertex_buffer_header = package_manager()
-                                            .read_tag_struct::<VertexBufferHeader>(hash)
-                                            .unwrap();
-
-                                        let vertex_buffer = unsafe {
-                                            dcs.device
-                                                .CreateBuffer(
-                                                    &D3D11_BUFFER_DESC {
-                                                        ByteWidth: vertex_data.len() as _,
-                                                        Usage: D3D11_USAGE_IMMUTABLE,
-                                                        BindFlags: D3D11_BIND_VERTEX_BUFFER,
-                                                        ..Default::default()
-                                                    },
-                                                    Some(&D3D11_SUBRESOURCE_DATA {
-                                                        pSysMem: vertex_data.as_ptr() as _,
-                                                        ..Default::default()
-                                                    }),
-                                                )
-                                                .context("Failed to create vertex buffer")
-                                                .unwrap()
-                                        };
-
-                                        data.write().vertex_buffers.insert(
-                                            hash,
-                                            (vertex_buffer, vertex_buffer_header.stride as u32),
-                                        );
-                                    }
-                                    Err(e) => error!("Failed to load vertex buffer {hash}: {e}"),
-                                }
-                            }
-                            // Index buffer
-                            (32, 6) => {
-                                if data.read().index_buffers.contains_key(&hash) {
-                                    continue;
-                                }
-
-                                match package_manager().read_tag(entry.reference) {
-                                    Ok(index_data) => {
-                                        let index_buffer_header = package_manager()
-                                            .read_tag_struct::<IndexBufferHeader>(hash)
-                                            .unwrap();
-
-                                        let index_buffer = unsafe {
-                                            dcs.device
-                                                .CreateBuffer(
-                                                    &D3D11_BUFFER_DESC {
-                                                        ByteWidth: index_data.len() as _,
-                                                        Usage: D3D11_USAGE_IMMUTABLE,
-                                                        BindFlags: D3D11_BIND_INDEX_BUFFER,
-                                                        ..Default::default()
-                                                    },
-                                                    Some(&D3D11_SUBRESOURCE_DATA {
-                                                        pSysMem: index_data.as_ptr() as _,
-                                                        ..Default::default()
-                                                    }),
-                                                )
-                                                .context("Failed to create index buffer")
-                                                .unwrap()
-                                        };
-
-                                        data.write().index_buffers.insert(
-                                            hash,
-                                            (
-                                                index_buffer,
-                                                if index_buffer_header.is_32bit {
-                                                    DxgiFormat::R32_UINT
-                                                } else {
-                                                    DxgiFormat::R16_UINT
-                                                },
-                                            ),
-                                        );
-                                    }
-                                    Err(e) => error!("Failed to load vertex buffer {hash}: {e}"),
-                                }
-                            }
-                            u => panic!("Unsupported mt loader buffer type {u:?}"),
-                        }
-                    }
-                }
+fn load_buffer(
+    dcs: &DeviceContextSwapchain,
+    data: &RwLock<RenderData>,
+    hash: TagHash,
+) -> anyhow::Result<()> {
+    if !hash.is_valid() {
+        return Ok(());
+    }
 
-                update_status(&STATUS_BUFFERS, rx.len());
+    let entry = package_manager().get_entry(hash)?;
+    match (entry.file_type, entry.file_subtype) {
+        // Vertex buffer
+        (32, 4) => {
+            if data.read().vertex_buffers.contains_key(&hash) {
+                return Ok(());
             }
 
-            info!("Thread '{}' exited", name);
-        })
-        .unwrap();
+            let vertex_data = package_manager().read_tag(entry.reference)?;
+            let vertex_buffer_header =
+                package_manager().read_tag_struct::<VertexBufferHeader>(hash)?;
+
+            let vertex_buffer = unsafe {
+                dcs.device
+                    .CreateBuffer(
+                        &D3D11_BUFFER_DESC {
+                            ByteWidth: vertex_data.len() as _,
+                            Usage: D3D11_USAGE_IMMUTABLE,
+                            BindFlags: D3D11_BIND_VERTEX_BUFFER,
+                            ..Default::default()
+                        },
+                        Some(&D3D11_SUBRESOURCE_DATA {
+                            pSysMem: vertex_data.as_ptr() as _,
+                            ..Default::default()
+                        }),
+                    )
+                    .context("Failed to create vertex buffer")?
+            };
+
+            let mut data = data.write();
+            data.sizes.insert(hash, vertex_data.len());
+            data.vertex_buffers
+                .insert(hash, (vertex_buffer, vertex_buffer_header.stride as u32));
+        }
+        // Index buffer
+        (32, 6) => {
+            if data.read().index_buffers.contains_key(&hash) {
+                return Ok(());
+            }
+
+            let index_data = package_manager().read_tag(entry.reference)?;
+            let index_buffer_header =
+                package_manager().read_tag_struct::<IndexBufferHeader>(hash)?;
+
+            let index_buffer = unsafe {
+                dcs.device
+                    .CreateBuffer(
+                        &D3D11_BUFFER_DESC {
+                            ByteWidth: index_data.len() as _,
+                            Usage: D3D11_USAGE_IMMUTABLE,
+                            BindFlags: D3D11_BIND_INDEX_BUFFER,
+                            ..Default::default()
+                        },
+                        Some(&D3D11_SUBRESOURCE_DATA {
+                            pSysMem: index_data.as_ptr() as _,
+                            ..Default::default()
+                        }),
+                    )
+                    .context("Failed to create index buffer")?
+            };
+
+            let mut data = data.write();
+            data.sizes.insert(hash, index_data.len());
+            data.index_buffers.insert(
+                hash,
+                (
+                    index_buffer,
+                    if index_buffer_header.is_32bit {
+                        DxgiFormat::R32_UINT
+                    } else {
+                        DxgiFormat::R16_UINT
+                    },
+                ),
+            );
+        }
+        u => anyhow::bail!("Unsupported buffer type {u:?}"),
+    }
+
+    Ok(())
 }
 
-pub fn thread_buffers(
-    dcs: Arc<DeviceContextSwapchain>,
-    data: Arc<RwLock<RenderData>>,
-) -> mpsc::Sender<TagHash> {
-    let (tx, rx) = mpsc::unbounded::<TagHash>();
+/// Starts the texture and buffer loading threads, with the worker counts from the loader config
+pub fn spawn_loaders(dcs: Arc<DeviceContextSwapchain>, data: Arc<RwLock<RenderData>>) {
+    let (texture_workers, buffer_workers) =
+        crate::config::with(|c| (c.loader.texture_workers, c.loader.buffer_workers));
+
+    spawn_workers(
+        &TEXTURE_QUEUE,
+        texture_workers,
+        dcs.clone(),
+        data.clone(),
+        load_texture,
+    );
+    spawn_workers(&BUFFER_QUEUE, buffer_workers, dcs, data, load_buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop() -> Option<LoadCallback> {
+        Some(Box::new(|_, _| {}))
+    }
+
+    fn pop_all(queue: &LoadQueue) -> Vec<u32> {
+        let mut tags = vec![];
+        while queue.stats().queued > 0 {
+            tags.push(queue.pop().0 .0);
+        }
+        tags
+    }
+
+    #[test]
+    fn priority_order() {
+        let queue = LoadQueue::new("Test");
+        queue.push(TagHash(1), LoadPriority::Background, None);
+        queue.push(TagHash(2), LoadPriority::Distance(10.0), None);
+        queue.push(TagHash(3), LoadPriority::Visible, None);
+        queue.push(TagHash(4), LoadPriority::Distance(5.0), None);
+        queue.push(TagHash(5), LoadPriority::Visible, None);
+
+        // Equal priorities are loaded in request order
+        assert_eq!(pop_all(&queue), [3, 5, 4, 2, 1]);
+    }
+
+    #[test]
+    fn dedup() {
+        let queue = LoadQueue::new("Test");
+        queue.push(TagHash(1), LoadPriority::Background, noop());
+        queue.push(TagHash(2), LoadPriority::Distance(1.0), None);
+        queue.push(TagHash(1), LoadPriority::Visible, noop());
+        // Lower priority requests don't lower the priority of a queued request
+        queue.push(TagHash(1), LoadPriority::Background, None);
+        assert_eq!(queue.stats().queued, 2);
+
+        let (tag, callbacks) = queue.pop();
+        assert_eq!(tag, TagHash(1));
+        assert_eq!(callbacks.len(), 2);
+        assert_eq!(pop_all(&queue), [2]);
+    }
 
-    spawn_thread_buffers(dcs.clone(), data.clone(), rx.clone(), "Buffer loader 1");
-    // spawn_thread_buffers(dcs, data, rx, "Buffer loader 2");
+    #[test]
+    fn prioritize() {
+        let queue = LoadQueue::new("Test");
+        queue.push(TagHash(1), LoadPriority::Visible, None);
+        queue.push(TagHash(2), LoadPriority::Distance(5.0), None);
+        queue.push(TagHash(3), LoadPriority::Background, None);
 
-    tx
+        queue.prioritize(&IntMap::from_iter([
+            (TagHash(1), LoadPriority::Distance(10.0)),
+            (TagHash(3), LoadPriority::Visible),
+        ]));
+        assert_eq!(pop_all(&queue), [3, 2, 1]);
+    }
+
+    #[test]
+    fn prioritize_back_and_forth() {
+        let queue = LoadQueue::new("Test");
+        queue.push(TagHash(1), LoadPriority::Distance(5.0), None);
+        queue.push(TagHash(2), LoadPriority::Distance(5.0), None);
+
+        // Leaves a stale entry for tag 1 with the priority it ends up with again
+        queue.prioritize(&IntMap::from_iter([(TagHash(1), LoadPriority::Background)]));
+        queue.prioritize(&IntMap::from_iter([(
+            TagHash(1),
+            LoadPriority::Distance(5.0),
+        )]));
+        assert_eq!(pop_all(&queue), [2, 1]);
+    }
+
+    #[test]
+    fn cancel() {
+        let outcomes: Arc<Mutex<Vec<(TagHash, LoadOutcome)>>> = Arc::default();
+        let queue = LoadQueue::new("Test");
+        for i in 1..=3 {
+            let outcomes = outcomes.clone();
+            let callback: LoadCallback =
+                Box::new(move |tag, outcome| outcomes.lock().push((tag, outcome)));
+            queue.push(TagHash(i), LoadPriority::Visible, Some(callback));
+        }
+
+        assert_eq!(queue.cancel(|t| t == TagHash(2)), 1);
+        assert_eq!(*outcomes.lock(), [(TagHash(2), LoadOutcome::Cancelled)]);
+        assert_eq!(queue.stats().cancelled, 1);
+        assert_eq!(pop_all(&queue), [1, 3]);
+    }
 }
@@ -1,19 +1,34 @@
+#[cfg(windows)]
 use crate::dxbc::{get_input_signature, get_output_signature, DxbcHeader, DxbcInputType};
+use crate::packages::package_manager;
+#[cfg(windows)]
 use crate::render::vertex_layout::InputElement;
+#[cfg(windows)]
+use anyhow::Context;
+#[cfg(windows)]
 use binrw::BinReaderExt;
+use destiny_pkg::TagHash;
+#[cfg(windows)]
 use itertools::Itertools;
+#[cfg(windows)]
 use std::io::Cursor;
+#[cfg(windows)]
 use windows::{
     core::PCSTR,
     Win32::Graphics::{
-        Direct3D::Fxc::{D3DCompile, D3DCOMPILE_DEBUG, D3DCOMPILE_SKIP_OPTIMIZATION},
+        Direct3D::Fxc::{
+            D3DCompile, D3DDisassemble, D3DCOMPILE_DEBUG, D3DCOMPILE_SKIP_OPTIMIZATION,
+        },
         Direct3D11::{ID3D11PixelShader, ID3D11VertexShader},
     },
 };
 
+#[cfg(windows)]
 use super::vertex_layout::OutputElement;
+#[cfg(windows)]
 use super::DeviceContextSwapchain;
 
+#[cfg(windows)]
 pub fn compile_hlsl(source: &str, entrypoint: &str, target: &str) -> Result<Vec<u8>, String> {
     let mut shader = None;
     let mut errors = None;
@@ -71,13 +86,43 @@ pub fn compile_hlsl(source: &str, entrypoint: &str, target: &str) -> Result<Vec<
     Ok(vs_blob.to_vec())
 }
 
+/// Disassembles DXBC bytecode. This does not need a device, so it can be used headless
+#[cfg(windows)]
+pub fn disassemble(data: &[u8]) -> anyhow::Result<String> {
+    let blob = unsafe { D3DDisassemble(data.as_ptr() as _, data.len(), 0, PCSTR::null())? };
+
+    let text = unsafe {
+        std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize())
+    };
+
+    Ok(String::from_utf8_lossy(text)
+        .trim_end_matches('\0')
+        .to_string())
+}
+
+/// The disassembler is part of the D3D compiler (d3dcompiler_47.dll), which only exists on Windows
+#[cfg(not(windows))]
+pub fn disassemble(_data: &[u8]) -> anyhow::Result<String> {
+    anyhow::bail!(
+        "Disassembling shaders needs the D3D shader compiler, which is only available on Windows"
+    )
+}
+
+/// Reads the DXBC bytecode of a shader referenced by a material
+pub fn read_shader_data(tag: TagHash) -> anyhow::Result<Vec<u8>> {
+    let entry = package_manager().get_entry(tag)?;
+    Ok(package_manager().read_tag(entry.reference)?)
+}
+
+#[cfg(windows)]
 pub fn load_vshader(
     dcs: &DeviceContextSwapchain,
     data: &[u8],
 ) -> anyhow::Result<(ID3D11VertexShader, Vec<InputElement>)> {
     let mut vs_cur = Cursor::new(&data);
-    let dxbc_header: DxbcHeader = vs_cur.read_le().unwrap();
-    let input_sig = get_input_signature(&mut vs_cur, &dxbc_header).unwrap();
+    let dxbc_header: DxbcHeader = vs_cur.read_le().context("Failed to read DXBC header")?;
+    let input_sig =
+        get_input_signature(&mut vs_cur, &dxbc_header).context("Failed to read input signature")?;
 
     let base_layout = input_sig
         .elements
@@ -91,13 +136,15 @@ pub fn load_vshader(
     ))
 }
 
+#[cfg(windows)]
 pub fn load_pshader(
     dcs: &DeviceContextSwapchain,
     data: &[u8],
 ) -> anyhow::Result<(ID3D11PixelShader, Vec<OutputElement>)> {
     let mut vs_cur = Cursor::new(&data);
-    let dxbc_header: DxbcHeader = vs_cur.read_le().unwrap();
-    let output_sig = get_output_signature(&mut vs_cur, &dxbc_header).unwrap();
+    let dxbc_header: DxbcHeader = vs_cur.read_le().context("Failed to read DXBC header")?;
+    let output_sig = get_output_signature(&mut vs_cur, &dxbc_header)
+        .context("Failed to read output signature")?;
 
     let base_layout = output_sig
         .elements
@@ -2,10 +2,9 @@ use crate::entity::{EPrimitiveType};
 use crate::render::vertex_buffers::load_vertex_buffers;
 use crate::statics::{Unk80807193, Unk80807194, Unk8080719a, Unk8080719b, Unk808071a7};
 
-use anyhow::{ensure};
+use anyhow::{ensure, Context};
 use destiny_pkg::TagHash;
 use glam::{Mat4, Vec3};
-use itertools::Itertools;
 
 use crate::packages::package_manager;
 
@@ -61,7 +60,9 @@ impl StaticModel {
         _model_hash: TagHash,
     ) -> anyhow::Result<StaticModel> {
         let pm = package_manager();
-        let header: Unk80807194 = pm.read_tag_struct(model.unk8).unwrap();
+        let header: Unk80807194 = pm
+            .read_tag_struct(model.unk8)
+            .with_context(|| format!("Failed to read static mesh data {}", model.unk8))?;
 
         ensure!(header.mesh_groups.len() == model.materials.len());
 
@@ -183,8 +184,9 @@ impl StaticModel {
             decal_parts: model
                 .unk20
                 .iter()
-                .map(|m| StaticOverlayModel::load(m.clone(), device, renderer).unwrap())
-                .collect_vec(),
+                .map(|m| StaticOverlayModel::load(m.clone(), device, renderer))
+                .collect::<anyhow::Result<_>>()
+                .context("Failed to load decal parts")?,
             buffers,
             model,
             parts: header.parts.to_vec(),
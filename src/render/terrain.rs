@@ -105,6 +105,12 @@ impl TerrainRenderer {
         }
 
         for p in &terrain.mesh_parts {
+            anyhow::ensure!(
+                (p.group_index as usize) < terrain.mesh_groups.len(),
+                "Mesh part uses group {}, but the terrain only has {} groups",
+                p.group_index,
+                terrain.mesh_groups.len()
+            );
             renderer.render_data.load_material(renderer, p.material);
         }
 
@@ -174,7 +180,7 @@ impl TerrainRenderer {
                     },
                 );
             } else {
-                panic!("Could not get terrain mesh group {}", part.group_index)
+                anyhow::bail!("Could not get terrain mesh group {}", part.group_index)
             }
         }
 
@@ -3,6 +3,7 @@ use crate::entity::VertexBufferHeader;
 use crate::packages::package_manager;
 use crate::render::renderer::Renderer;
 use crate::render::vertex_layout;
+use anyhow::Context;
 use destiny_pkg::TagHash;
 use itertools::Itertools;
 use std::collections::hash_map::DefaultHasher;
@@ -21,7 +22,9 @@ pub fn load_vertex_buffers(
     let mut buffer_strides = vec![];
     for b in buffers {
         if b.is_valid() {
-            let vertex_header: VertexBufferHeader = package_manager().read_tag_struct(*b).unwrap();
+            let vertex_header: VertexBufferHeader = package_manager()
+                .read_tag_struct(*b)
+                .with_context(|| format!("Failed to read vertex buffer header {b}"))?;
             buffer_strides.push(vertex_header.stride as usize);
         } else {
             buffer_strides.push(0);
@@ -41,7 +44,10 @@ pub fn load_vertex_buffers(
         renderer.render_data.load_material(renderer, material);
 
         let render_data = renderer.render_data.data();
-        let mat = &render_data.materials[&material];
+        let mat = render_data
+            .materials
+            .get(&material)
+            .with_context(|| format!("Material {material} failed to load"))?;
 
         (mat.vertex_shader, mat.pixel_shader)
     };
@@ -49,7 +55,7 @@ pub fn load_vertex_buffers(
     let vshader = renderer
         .render_data
         .load_vshader(&renderer.dcs, material_vshader)
-        .unwrap();
+        .with_context(|| format!("Vertex shader {material_vshader} failed to load"))?;
     renderer
         .render_data
         .load_pshader(&renderer.dcs, material_pshader);
@@ -109,16 +115,13 @@ pub fn load_vertex_buffers(
         //         .collect_vec()
         // );
 
-        let input_layout = {
-            let vs_data = &renderer.render_data.data().vshaders[&material_vshader].2;
-            unsafe {
-                renderer
-                    .dcs
-                    .device
-                    .CreateInputLayout(&layout_converted, vs_data)
-            }
-            .unwrap()
-        };
+        let input_layout = unsafe {
+            renderer
+                .dcs
+                .device
+                .CreateInputLayout(&layout_converted, &vshader.2)
+        }
+        .context("Failed to create input layout")?;
 
         renderer
             .render_data
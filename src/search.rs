@@ -0,0 +1,230 @@
+//! Finds tags by their hash, class or file type, or by their contents
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use destiny_pkg::TagHash;
+use itertools::Itertools;
+use nohash_hasher::{IntMap, IntSet};
+use rayon::prelude::*;
+
+use crate::packages::{all_tags, package_manager};
+use crate::references::resolve_hash64;
+
+/// Offsets kept per tag for content searches, matches past this are only counted
+pub const MAX_OFFSETS: usize = 64;
+
+#[derive(Debug, Clone)]
+pub enum SearchQuery {
+    Tag(TagHash),
+    Tag64(u64),
+    /// Tags of a reference class
+    Class(u32),
+    FileType {
+        file_type: u8,
+        file_subtype: Option<u8>,
+    },
+    /// A byte sequence anywhere in the tag
+    Bytes(Vec<u8>),
+    /// An aligned f32 within `tolerance` of `value`
+    Float {
+        value: f32,
+        tolerance: f32,
+    },
+    /// Any of these aligned u32 values, eg. the DestinyHashes of strings matching a name
+    Hashes(Vec<u32>),
+}
+
+impl SearchQuery {
+    /// Whether the query has to read every tag
+    pub fn is_content(&self) -> bool {
+        matches!(
+            self,
+            SearchQuery::Bytes(_) | SearchQuery::Float { .. } | SearchQuery::Hashes(_)
+        )
+    }
+}
+
+pub struct SearchResult {
+    pub tag: TagHash,
+    /// Where the content matched, empty for other queries
+    pub offsets: Vec<usize>,
+    pub match_count: usize,
+}
+
+impl SearchResult {
+    fn tag(tag: TagHash) -> SearchResult {
+        SearchResult {
+            tag,
+            offsets: vec![],
+            match_count: 1,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SearchProgress {
+    pub total: AtomicUsize,
+    pub done: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl SearchProgress {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            0.0
+        } else {
+            self.done.load(Ordering::Relaxed) as f32 / total as f32
+        }
+    }
+}
+
+/// Runs `query`. Content searches only look at tag files (tags with a reference class), not at
+/// raw data such as textures and vertex buffers
+pub fn search(query: &SearchQuery, progress: &SearchProgress) -> anyhow::Result<Vec<SearchResult>> {
+    let results = match query {
+        SearchQuery::Tag(tag) => {
+            package_manager().get_entry(*tag)?;
+            vec![SearchResult::tag(*tag)]
+        }
+        SearchQuery::Tag64(hash) => {
+            let Some(tag) = resolve_hash64(*hash) else {
+                anyhow::bail!("TagHash64 {hash:016X} does not exist");
+            };
+            vec![SearchResult::tag(tag)]
+        }
+        SearchQuery::Class(reference) => package_manager()
+            .get_all_by_reference(*reference)
+            .into_iter()
+            .map(|(tag, _)| SearchResult::tag(tag))
+            .collect(),
+        SearchQuery::FileType {
+            file_type,
+            file_subtype,
+        } => all_tags(|t, st| t == *file_type && file_subtype.map_or(true, |s| s == st))
+            .into_iter()
+            .map(SearchResult::tag)
+            .collect(),
+        _ => search_content(query, progress),
+    };
+
+    Ok(results)
+}
+
+fn search_content(query: &SearchQuery, progress: &SearchProgress) -> Vec<SearchResult> {
+    let _span = info_span!("Searching tag contents").entered();
+    let tags = all_tags(|_, _| true);
+    progress.total.store(tags.len(), Ordering::Relaxed);
+
+    let hashes: IntSet<u32> = match query {
+        SearchQuery::Hashes(h) => h.iter().copied().collect(),
+        _ => IntSet::default(),
+    };
+
+    let mut results: Vec<SearchResult> = tags
+        .par_iter()
+        .filter_map(|tag| {
+            if progress.is_cancelled() {
+                return None;
+            }
+
+            let result = search_tag(*tag, query, &hashes);
+            progress.done.fetch_add(1, Ordering::Relaxed);
+            result
+        })
+        .collect();
+    results.sort_by_key(|r| (r.tag.pkg_id(), r.tag.0));
+
+    results
+}
+
+fn search_tag(tag: TagHash, query: &SearchQuery, hashes: &IntSet<u32>) -> Option<SearchResult> {
+    let entry = package_manager().get_entry(tag).ok()?;
+    if entry.reference & 0xffff0000 != 0x80800000 {
+        return None;
+    }
+
+    let data = package_manager().read_tag(tag).ok()?;
+    let words = || {
+        data.chunks_exact(4)
+            .enumerate()
+            .map(|(i, c)| (i * 4, u32::from_le_bytes(c.try_into().unwrap())))
+    };
+
+    let matches: Vec<usize> = match query {
+        SearchQuery::Bytes(pattern) if !pattern.is_empty() => data
+            .windows(pattern.len())
+            .positions(|w| w == pattern.as_slice())
+            .collect(),
+        SearchQuery::Float { value, tolerance } => words()
+            .filter(|(_, w)| {
+                let f = f32::from_bits(*w);
+                f.is_finite() && (f - value).abs() <= *tolerance
+            })
+            .map(|(offset, _)| offset)
+            .collect(),
+        SearchQuery::Hashes(_) => words()
+            .filter(|(_, w)| hashes.contains(w))
+            .map(|(offset, _)| offset)
+            .collect(),
+        _ => vec![],
+    };
+
+    if matches.is_empty() {
+        return None;
+    }
+
+    Some(SearchResult {
+        tag,
+        match_count: matches.len(),
+        offsets: matches.into_iter().take(MAX_OFFSETS).collect(),
+    })
+}
+
+/// Parses a byte pattern written as hex, with or without spaces (eg. `de ad be ef`, `0xdeadbeef`)
+pub fn parse_bytes(s: &str) -> anyhow::Result<Vec<u8>> {
+    let hex: String = s
+        .split_whitespace()
+        .map(|p| p.trim_start_matches("0x"))
+        .collect();
+    let bytes = hex::decode(&hex).map_err(|e| anyhow::anyhow!("Invalid byte pattern: {e}"))?;
+    anyhow::ensure!(!bytes.is_empty(), "Empty byte pattern");
+    Ok(bytes)
+}
+
+/// Parses a u32 in decimal, or in hex when prefixed with `0x`
+pub fn parse_u32(s: &str) -> anyhow::Result<u32> {
+    let s = s.trim();
+    match s.strip_prefix("0x") {
+        Some(hex) => Ok(u32::from_str_radix(hex, 16)?),
+        None => Ok(s.parse()?),
+    }
+}
+
+/// DestinyHashes of the strings equal to `name` (case insensitive), or of those containing it if
+/// there are none
+pub fn string_hashes(strings: &IntMap<u32, String>, name: &str) -> Vec<u32> {
+    let name = name.trim().to_lowercase();
+    let exact = strings
+        .iter()
+        .filter(|(_, s)| s.to_lowercase() == name)
+        .map(|(h, _)| *h)
+        .collect_vec();
+    if !exact.is_empty() {
+        return exact;
+    }
+
+    strings
+        .iter()
+        .filter(|(_, s)| s.to_lowercase().contains(&name))
+        .map(|(h, _)| *h)
+        .collect()
+}
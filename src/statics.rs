@@ -1,23 +1,38 @@
-use binrw::BinRead;
+use binrw::{BinRead, BinWrite};
 use destiny_pkg::TagHash;
+use glam::{Mat4, Quat, Vec3};
+use serde::{Serialize, Serializer};
 use std::io::SeekFrom;
 
 use crate::entity::{ELodCategory, EPrimitiveType};
 use crate::types::Vector2;
 use crate::{
-    structure::TablePointer,
+    structure::{serialize_tag, serialize_tags, tag_string, LazyTablePointer, TablePointer},
     types::{Vector3, Vector4},
 };
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct Unk80807194 {
     pub file_size: u64,
     pub mesh_groups: TablePointer<Unk8080719b>,
     pub parts: TablePointer<Unk8080719a>,
+    /// Index buffer, vertex buffer, vertex buffer 2 and an unknown value
+    #[serde(serialize_with = "serialize_buffers")]
     pub buffers: TablePointer<(TagHash, TagHash, TagHash, u32)>,
 }
 
-#[derive(BinRead, Debug, Clone)]
+fn serialize_buffers<S: Serializer>(
+    buffers: &TablePointer<(TagHash, TagHash, TagHash, u32)>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        buffers
+            .iter()
+            .map(|(a, b, c, d)| (tag_string(*a), tag_string(*b), tag_string(*c), d)),
+    )
+}
+
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk8080719a {
     pub index_start: u32,
     pub index_count: u32,
@@ -27,7 +42,7 @@ pub struct Unk8080719a {
     pub primitive_type: EPrimitiveType,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk8080719b {
     pub part_index: u16,
     pub unk2: u16,
@@ -35,16 +50,17 @@ pub struct Unk8080719b {
     pub unk6: u16,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, BinWrite, Debug, Clone, Serialize)]
 pub struct Unk8080966d {
-    #[br(seek_before(SeekFrom::Current(0x40)))]
-    pub transforms: TablePointer<Unk808071a3>,
+    #[brw(seek_before(SeekFrom::Current(0x40)))]
+    pub transforms: LazyTablePointer<Unk808071a3>,
     pub unk50: u64,
+    #[serde(serialize_with = "serialize_tags")]
     pub statics: TablePointer<TagHash>,
     pub instances: TablePointer<Unk80807190>,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, BinWrite, Debug, Clone, Serialize)]
 pub struct Unk80807190 {
     pub instance_count: u16,
     pub instance_start: u16,
@@ -52,7 +68,7 @@ pub struct Unk80807190 {
     pub unk6: u16,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, BinWrite, Debug, Clone, Serialize)]
 pub struct Unk808071a3 {
     pub rotation: Vector4, // TODO(cohae): Quat type? (alias?)
     pub translation: Vector3,
@@ -61,11 +77,29 @@ pub struct Unk808071a3 {
     pub unk2c: u32,
 }
 
-#[derive(BinRead, Debug, Clone)]
+impl Unk808071a3 {
+    /// Instance to world transform
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(
+            Vec3::splat(self.scale.x),
+            Quat::from_xyzw(
+                self.rotation.x,
+                self.rotation.y,
+                self.rotation.z,
+                self.rotation.w,
+            ),
+            Vec3::new(self.translation.x, self.translation.y, self.translation.z),
+        )
+    }
+}
+
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk808071a7 {
     pub file_size: u64,
+    #[serde(serialize_with = "serialize_tag")]
     pub unk8: TagHash,
     pub unkc: u32,
+    #[serde(serialize_with = "serialize_tags")]
     pub materials: TablePointer<TagHash>,
     pub unk20: TablePointer<Unk80807193>, // Translucent meshes?
     pub unk30: [u32; 2],
@@ -78,15 +112,19 @@ pub struct Unk808071a7 {
     pub texture_coordinate_offset: Vector2,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80807193 {
     pub unk0: u16,
     pub unk2: u16,
     pub unk4: u32,
+    #[serde(serialize_with = "serialize_tag")]
     pub index_buffer: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_buffer: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub vertex_buffer2: TagHash,
     pub index_start: u32,
     pub index_count: u32,
+    #[serde(serialize_with = "serialize_tag")]
     pub material: TagHash,
 }
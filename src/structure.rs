@@ -1,36 +1,203 @@
-use binrw::{BinRead, BinReaderExt, BinResult, Endian};
-use destiny_pkg::TagHash;
+use binrw::{BinRead, BinReaderExt, BinResult, BinWrite, Endian, NullString};
+use destiny_pkg::{TagHash, TagHash64};
+use serde::ser::{Error as _, SerializeSeq, SerializeStruct};
+use serde::{Serialize, Serializer};
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Formatter, Write};
-use std::io::{Read, Seek, SeekFrom};
-use std::ops::Deref;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write as IoWrite};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut, Range};
 use std::slice::Iter;
+use std::sync::Arc;
 
 use crate::packages::package_manager;
 
-// pub type TablePointer32<T> = _TablePointer<i32, u32, T>;
+pub type TablePointer32<T> = _TablePointer<i32, u32, T>;
 pub type TablePointer64<T> = _TablePointer<i64, u64, T>;
 pub type TablePointer<T> = TablePointer64<T>;
 
-// pub type RelPointer32<T = ()> = _RelPointer<i32, T>;
+pub type RelPointer32<T = ()> = _RelPointer<i32, T>;
 pub type RelPointer64<T = ()> = _RelPointer<i64, T>;
 pub type RelPointer<T = ()> = RelPointer64<T>;
 
+pub type LazyTablePointer32<T> = _LazyTablePointer<i32, u32, T>;
+pub type LazyTablePointer64<T> = _LazyTablePointer<i64, u64, T>;
+pub type LazyTablePointer<T> = LazyTablePointer64<T>;
+
+pub type LazyRelPointer32<T = ()> = _LazyRelPointer<i32, T>;
+pub type LazyRelPointer64<T = ()> = _LazyRelPointer<i64, T>;
+pub type LazyRelPointer<T = ()> = LazyRelPointer64<T>;
+
+/// Relative offset of a pointer or table, counted from the position of the offset itself
+pub trait PointerOffset:
+    for<'a> BinRead<Args<'a> = ()> + for<'a> BinWrite<Args<'a> = ()> + Copy + Into<i64> + TryFrom<i64>
+{
+}
+impl PointerOffset for i32 {}
+impl PointerOffset for i64 {}
+
+/// Element count of a table
+pub trait TableCount:
+    for<'a> BinRead<Args<'a> = ()> + for<'a> BinWrite<Args<'a> = ()> + Copy + Into<u64> + TryFrom<u64>
+{
+}
+impl TableCount for u32 {}
+impl TableCount for u64 {}
+
+/// Tag data shared between the lazy tables and pointers read from it
+pub type TagBuffer = Arc<Vec<u8>>;
+
+thread_local! {
+    /// Buffers of the tags being read on this thread, innermost last. See [read_shared]
+    static TAG_BUFFERS: RefCell<Vec<TagBuffer>> = RefCell::new(vec![]);
+}
+
+/// Reads a tag struct through [read_shared]. Lazy tables and pointers can only be used in
+/// structs read this way
+pub fn read_tag_shared<T>(tag: TagHash) -> anyhow::Result<T>
+where
+    T: BinRead,
+    for<'a> T::Args<'a>: Default,
+{
+    let buffer = Arc::new(package_manager().read_tag(tag)?);
+    Ok(read_shared(&buffer, 0, Endian::Little)?)
+}
+
+/// Reads a struct at `pos` in `buffer`, making the buffer available to the lazy tables and
+/// pointers in it
+pub fn read_shared<T>(buffer: &TagBuffer, pos: u64, endian: Endian) -> BinResult<T>
+where
+    T: BinRead,
+    for<'a> T::Args<'a>: Default,
+{
+    TAG_BUFFERS.with(|b| b.borrow_mut().push(buffer.clone()));
+    let mut cur = Cursor::new(buffer.as_slice());
+    let result = cur
+        .seek(SeekFrom::Start(pos))
+        .map_err(binrw::Error::from)
+        .and_then(|_| cur.read_type(endian));
+    TAG_BUFFERS.with(|b| b.borrow_mut().pop());
+
+    result
+}
+
+/// Like [read_shared], but also returns the byte ranges that were read, in the order they were
+/// read. Data read through lazy tables and pointers, or from other tags, isn't included
+pub fn read_shared_traced<T>(buffer: &TagBuffer, endian: Endian) -> (BinResult<T>, Vec<Range<u64>>)
+where
+    T: BinRead,
+    for<'a> T::Args<'a>: Default,
+{
+    TAG_BUFFERS.with(|b| b.borrow_mut().push(buffer.clone()));
+    let mut reader = ReadTracker {
+        inner: Cursor::new(buffer.as_slice()),
+        reads: vec![],
+    };
+    let result = reader.read_type(endian);
+    TAG_BUFFERS.with(|b| b.borrow_mut().pop());
+
+    (result, reader.reads)
+}
+
+struct ReadTracker<R> {
+    inner: R,
+    reads: Vec<Range<u64>>,
+}
+
+impl<R: Read + Seek> Read for ReadTracker<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let pos = self.inner.stream_position()?;
+        let read = self.inner.read(buf)?;
+        if read != 0 {
+            self.reads.push(pos..pos + read as u64);
+        }
+
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for ReadTracker<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Returns the buffer `reader` is reading from, if it's being read through [read_shared]
+fn shared_buffer<R: Read + Seek>(reader: &mut R) -> BinResult<TagBuffer> {
+    let pos = reader.stream_position()?;
+    let Some(buffer) = TAG_BUFFERS.with(|b| b.borrow().last().cloned()) else {
+        return Err(binrw::Error::AssertFail {
+            pos,
+            message: "Lazy tables and pointers can only be read through structure::read_shared"
+                .to_string(),
+        });
+    };
+
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(pos))?;
+    if len != buffer.len() as u64 {
+        return Err(binrw::Error::AssertFail {
+            pos,
+            message: format!(
+                "Lazy table or pointer read from a stream of {len} bytes, but the shared tag buffer is {} bytes",
+                buffer.len()
+            ),
+        });
+    }
+
+    Ok(buffer)
+}
+
+/// Writes `value` over a copy of `original`, the tag data it was read from. Unchanged fields are
+/// written back byte-identically, tables that changed size are moved to the end of the tag
+pub fn write_tag_struct<T>(original: &[u8], value: &T) -> BinResult<Vec<u8>>
+where
+    T: BinWrite,
+    for<'a> T::Args<'a>: Default,
+{
+    let mut cur = Cursor::new(original.to_vec());
+    value.write_options(&mut cur, Endian::Little, Default::default())?;
+    Ok(cur.into_inner())
+}
+
+/// Writes the offset to `address`, relative to the current position
+fn write_offset<O: PointerOffset, W: IoWrite + Seek>(
+    writer: &mut W,
+    endian: Endian,
+    address: u64,
+) -> BinResult<()> {
+    let pos = writer.stream_position()?;
+    let relative = address as i64 - pos as i64;
+    let Ok(offset) = O::try_from(relative) else {
+        return Err(binrw::Error::AssertFail {
+            pos,
+            message: format!(
+                "Offset {relative} to 0x{address:x} doesn't fit in a {}",
+                std::any::type_name::<O>()
+            ),
+        });
+    };
+
+    offset.write_options(writer, endian, ())
+}
+
 #[derive(Clone)]
-pub struct _TablePointer<O: Into<i64>, C: Into<u64>, T: BinRead> {
+pub struct _TablePointer<O: PointerOffset, C: TableCount, T: BinRead> {
     offset_base: u64,
     offset: O,
     count: C,
 
+    /// Header the table was read with, reused when the table is relocated
+    header: Option<ArrayHeader>,
+    element_size: u64,
+
     data: Vec<T>,
 }
 
-impl<'a, O: Into<i64>, C: Into<u64>, T: BinRead> BinRead for _TablePointer<O, C, T>
+impl<'a, O: PointerOffset, C: TableCount, T: BinRead> BinRead for _TablePointer<O, C, T>
 where
-    C: BinRead + Copy,
-    O: BinRead + Copy,
-    C::Args<'a>: Default + Clone,
-    O::Args<'a>: Default + Clone,
     T::Args<'a>: Default + Clone,
 {
     type Args<'b> = ();
@@ -49,13 +216,28 @@ where
         let seek64: i64 = offset.into();
         reader.seek(SeekFrom::Start(offset_base))?;
         reader.seek(SeekFrom::Current(seek64))?;
-        // TODO(cohae): Check array header
-        reader.seek(SeekFrom::Current(16))?;
 
         let count64: u64 = count.into();
-        let mut data = Vec::with_capacity(count64 as _);
-        for _ in 0..count64 {
+        // Empty tables don't necessarily point to an array header
+        let mut header = None;
+        if count64 != 0 {
+            let header_pos = reader.stream_position()?;
+            let h: ArrayHeader = reader.read_type(endian)?;
+            h.validate::<T>(header_pos, count64)?;
+            header = Some(h);
+        }
+
+        let mut data = vec![];
+        let mut element_size = 0;
+        for i in 0..count64 {
+            let element_pos = reader.stream_position()?;
             data.push(reader.read_type(endian)?);
+
+            if i == 0 {
+                element_size = reader.stream_position()? - element_pos;
+                check_table_bounds::<T, _>(reader, element_pos, element_size, count64)?;
+                data.reserve_exact(count64 as usize - 1);
+            }
         }
 
         reader.seek(SeekFrom::Start(offset_save))?;
@@ -64,12 +246,14 @@ where
             offset_base,
             offset,
             count,
+            header,
+            element_size,
             data,
         })
     }
 }
 
-impl<O: Into<i64> + Copy, C: Into<u64> + Copy, T: BinRead> _TablePointer<O, C, T> {
+impl<O: PointerOffset, C: TableCount, T: BinRead> _TablePointer<O, C, T> {
     pub fn iter(&self) -> Iter<'_, T> {
         self.data.iter()
     }
@@ -81,18 +265,111 @@ impl<O: Into<i64> + Copy, C: Into<u64> + Copy, T: BinRead> _TablePointer<O, C, T
     pub fn data(&self) -> &[T] {
         &self.data
     }
+
+    /// Elements can be added or removed, the table is relocated when it's written with a different size
+    pub fn data_mut(&mut self) -> &mut Vec<T> {
+        &mut self.data
+    }
+
+    /// Address of the array header the table was read from
+    fn address(&self) -> u64 {
+        (self.offset_base as i64 + self.offset.into()) as u64
+    }
 }
 
-impl<O: Into<i64> + Copy, C: Into<u64> + Copy, T: BinRead> Deref for _TablePointer<O, C, T> {
+impl<O: PointerOffset, C: TableCount, T: BinRead> Deref for _TablePointer<O, C, T> {
     type Target = [T];
     fn deref(&self) -> &Self::Target {
         &self.data
     }
 }
 
-impl<'a, O: Into<i64> + Copy, C: Into<u64> + Copy, T: BinRead> IntoIterator
-    for &'a _TablePointer<O, C, T>
+impl<O: PointerOffset, C: TableCount, T: BinRead> DerefMut for _TablePointer<O, C, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+impl<O: PointerOffset, C: TableCount, T: BinRead + BinWrite> BinWrite for _TablePointer<O, C, T>
+where
+    for<'a> <T as BinWrite>::Args<'a>: Default,
 {
+    type Args<'b> = ();
+
+    fn write_options<W: IoWrite + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        let count = self.data.len() as u64;
+        let Ok(count_c) = C::try_from(count) else {
+            return Err(binrw::Error::AssertFail {
+                pos: writer.stream_position()?,
+                message: format!("Table of {count} elements is too large for its count"),
+            });
+        };
+        count_c.write_options(writer, endian, ())?;
+
+        if count == 0 {
+            return self.offset.write_options(writer, endian, ());
+        }
+
+        // Tables that kept their size are written over their original elements
+        if count == self.count.into() {
+            write_offset::<O, _>(writer, endian, self.address())?;
+            let offset_save = writer.stream_position()?;
+
+            writer.seek(SeekFrom::Start(self.address() + 16))?;
+            for e in &self.data {
+                e.write_options(writer, endian, Default::default())?;
+            }
+
+            writer.seek(SeekFrom::Start(offset_save))?;
+            return Ok(());
+        }
+
+        let element_size = if self.element_size != 0 {
+            self.element_size
+        } else {
+            let mut scratch = Cursor::new(vec![]);
+            self.data[0].write_options(&mut scratch, endian, Default::default())?;
+            scratch.position()
+        };
+
+        // Reserve space for the new table at the end of the tag, so that relocated tables of the
+        // elements end up after it
+        let offset_pos = writer.stream_position()?;
+        let end = writer.seek(SeekFrom::End(0))?;
+        let header_pos = (end + 15) & !15;
+        let table_end = header_pos + 16 + element_size * count;
+        writer.write_all(&vec![0u8; (table_end - end) as usize])?;
+
+        writer.seek(SeekFrom::Start(offset_pos))?;
+        write_offset::<O, _>(writer, endian, header_pos)?;
+        let offset_save = writer.stream_position()?;
+
+        writer.seek(SeekFrom::Start(header_pos))?;
+        ArrayHeader {
+            count,
+            class_hash: self
+                .header
+                .map(|h| h.class_hash)
+                .or_else(expected_class::<T>)
+                .unwrap_or(0),
+            unkc: self.header.map(|h| h.unkc).unwrap_or(0),
+        }
+        .write_options(writer, endian, ())?;
+        for e in &self.data {
+            e.write_options(writer, endian, Default::default())?;
+        }
+
+        writer.seek(SeekFrom::Start(offset_save))?;
+        Ok(())
+    }
+}
+
+impl<'a, O: PointerOffset, C: TableCount, T: BinRead> IntoIterator for &'a _TablePointer<O, C, T> {
     type Item = &'a T;
     type IntoIter = Iter<'a, T>;
 
@@ -101,9 +378,7 @@ impl<'a, O: Into<i64> + Copy, C: Into<u64> + Copy, T: BinRead> IntoIterator
     }
 }
 
-impl<O: Into<i64> + Copy, C: Into<u64> + Copy, T: BinRead + Debug> Debug
-    for _TablePointer<O, C, T>
-{
+impl<O: PointerOffset, C: TableCount, T: BinRead + Debug> Debug for _TablePointer<O, C, T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
             "TablePointer(address=0x{:x}, count={}",
@@ -118,18 +393,108 @@ impl<O: Into<i64> + Copy, C: Into<u64> + Copy, T: BinRead + Debug> Debug
     }
 }
 
+/// Tables are serialized as a sequence of their elements
+impl<O: PointerOffset, C: TableCount, T: BinRead + Serialize> Serialize for _TablePointer<O, C, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.data)
+    }
+}
+
+/// Header in front of the elements of every table
+#[derive(BinRead, BinWrite, Debug, Clone, Copy)]
+pub struct ArrayHeader {
+    pub count: u64,
+    /// Class of the elements
+    pub class_hash: u32,
+    pub unkc: u32,
+}
+
+impl ArrayHeader {
+    /// Checks the header against the table pointer that points to it, and against the class of `T`
+    /// when its name is a class hash (`Unk808071a3` is expected to be class 0x808071a3)
+    fn validate<T>(&self, pos: u64, count: u64) -> BinResult<()> {
+        if self.count != count {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!(
+                    "Table of {} has {count} elements, but its array header at 0x{pos:x} says {}",
+                    type_name_short::<T>(),
+                    self.count
+                ),
+            });
+        }
+
+        if let Some(expected) = expected_class::<T>() {
+            // Tables of primitives and unnamed classes have no meaningful class hash
+            if self.class_hash & 0xffff0000 == 0x80800000 && self.class_hash != expected {
+                return Err(binrw::Error::AssertFail {
+                    pos,
+                    message: format!(
+                        "Expected a table of class {expected:08x} ({}), but the array header at 0x{pos:x} has class {:08x}",
+                        type_name_short::<T>(),
+                        self.class_hash
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Makes sure a table with elements of `element_size` bytes fits in the tag, before its elements are read
+fn check_table_bounds<T, R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    element_size: u64,
+    count: u64,
+) -> BinResult<()> {
+    let pos = reader.stream_position()?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(pos))?;
+
+    let end = element_size
+        .checked_mul(count)
+        .and_then(|size| size.checked_add(start));
+    match end {
+        Some(end) if end <= len => Ok(()),
+        _ => Err(binrw::Error::AssertFail {
+            pos: start,
+            message: format!(
+                "Table of {count} {} ({element_size} bytes each) at 0x{start:x} doesn't fit in the tag (0x{len:x} bytes), is the element size right?",
+                type_name_short::<T>()
+            ),
+        }),
+    }
+}
+
+/// Name of `T` without its module path or generic arguments
+fn type_name_short<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Class hash encoded in the name of `T`, for structs named after their class (eg. `Unk808071a3`)
+fn expected_class<T>() -> Option<u32> {
+    let hex = type_name_short::<T>().strip_prefix("Unk")?;
+    if hex.len() != 8 {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok()
+}
+
 #[derive(Clone, Copy)]
-pub struct _RelPointer<O: Into<i64>, T: BinRead> {
+pub struct _RelPointer<O: PointerOffset, T: BinRead> {
     offset_base: u64,
     offset: O,
 
     data: T,
 }
 
-impl<'a, O: Into<i64>, T: BinRead> BinRead for _RelPointer<O, T>
+impl<'a, O: PointerOffset, T: BinRead> BinRead for _RelPointer<O, T>
 where
-    O: BinRead + Copy,
-    O::Args<'a>: Default + Clone,
     T::Args<'a>: Default + Clone,
 {
     type Args<'b> = ();
@@ -160,14 +525,46 @@ where
     }
 }
 
-impl<O: Into<i64> + Copy, T: BinRead> Deref for _RelPointer<O, T> {
+impl<O: PointerOffset, T: BinRead> Deref for _RelPointer<O, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         &self.data
     }
 }
 
-impl<O: Into<i64> + Copy, T: BinRead + Debug> Debug for _RelPointer<O, T> {
+impl<O: PointerOffset, T: BinRead> DerefMut for _RelPointer<O, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+impl<O: PointerOffset, T: BinRead + BinWrite> BinWrite for _RelPointer<O, T>
+where
+    for<'a> <T as BinWrite>::Args<'a>: Default,
+{
+    type Args<'b> = ();
+
+    /// The data is written back to the address it was read from
+    fn write_options<W: IoWrite + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        let address = (self.offset_base as i64 + self.offset.into()) as u64;
+        write_offset::<O, _>(writer, endian, address)?;
+        let offset_save = writer.stream_position()?;
+
+        writer.seek(SeekFrom::Start(address))?;
+        self.data
+            .write_options(writer, endian, Default::default())?;
+
+        writer.seek(SeekFrom::Start(offset_save))?;
+        Ok(())
+    }
+}
+
+impl<O: PointerOffset, T: BinRead + Debug> Debug for _RelPointer<O, T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
             "RelPointer(address=0x{:x}",
@@ -181,12 +578,382 @@ impl<O: Into<i64> + Copy, T: BinRead + Debug> Debug for _RelPointer<O, T> {
     }
 }
 
-impl<O: Into<i64> + Copy, T: BinRead + Debug> From<_RelPointer<O, T>> for SeekFrom {
+impl<O: PointerOffset, T: BinRead + Serialize> Serialize for _RelPointer<O, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.data.serialize(serializer)
+    }
+}
+
+impl<O: PointerOffset, T: BinRead + Debug> From<_RelPointer<O, T>> for SeekFrom {
     fn from(val: _RelPointer<O, T>) -> Self {
         SeekFrom::Start((val.offset_base as i64 + val.offset.into()) as u64)
     }
 }
 
+/// Like [_TablePointer], but only keeps the position of the elements. Elements are read from the
+/// shared tag buffer when accessed, see [read_tag_shared]
+pub struct _LazyTablePointer<O: PointerOffset, C: TableCount, T: BinRead> {
+    buffer: TagBuffer,
+    endian: Endian,
+    offset_base: u64,
+    offset: O,
+    count: C,
+
+    /// Position of the first element
+    start: u64,
+    element_size: u64,
+    /// Serialized elements replaced through [_LazyTablePointer::set], by index
+    patches: BTreeMap<usize, Vec<u8>>,
+
+    _marker: PhantomData<T>,
+}
+
+impl<O: PointerOffset, C: TableCount, T: BinRead> Clone for _LazyTablePointer<O, C, T> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            endian: self.endian,
+            offset_base: self.offset_base,
+            offset: self.offset,
+            count: self.count,
+            start: self.start,
+            element_size: self.element_size,
+            patches: self.patches.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, O: PointerOffset, C: TableCount, T: BinRead> BinRead for _LazyTablePointer<O, C, T>
+where
+    T::Args<'a>: Default + Clone,
+{
+    type Args<'b> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        let buffer = shared_buffer(reader)?;
+        let count: C = reader.read_type(endian)?;
+        let offset_base = reader.stream_position()?;
+        let offset: O = reader.read_type(endian)?;
+
+        let offset_save = reader.stream_position()?;
+
+        let count64: u64 = count.into();
+        let mut start = 0;
+        let mut element_size = 0;
+        if count64 != 0 {
+            let seek64: i64 = offset.into();
+            reader.seek(SeekFrom::Start(offset_base))?;
+            reader.seek(SeekFrom::Current(seek64))?;
+
+            let header_pos = reader.stream_position()?;
+            let header: ArrayHeader = reader.read_type(endian)?;
+            header.validate::<T>(header_pos, count64)?;
+
+            // The element size is measured by reading the first element
+            start = reader.stream_position()?;
+            let _first: T = reader.read_type(endian)?;
+            element_size = reader.stream_position()? - start;
+            check_table_bounds::<T, _>(reader, start, element_size, count64)?;
+        }
+
+        reader.seek(SeekFrom::Start(offset_save))?;
+
+        Ok(_LazyTablePointer {
+            buffer,
+            endian,
+            offset_base,
+            offset,
+            count,
+            start,
+            element_size,
+            patches: BTreeMap::new(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<O: PointerOffset, C: TableCount, T: BinRead> _LazyTablePointer<O, C, T>
+where
+    for<'a> T::Args<'a>: Default,
+{
+    pub fn len(&self) -> usize {
+        self.count.into() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn check_index(&self, index: usize) -> BinResult<()> {
+        if index >= self.len() {
+            return Err(binrw::Error::AssertFail {
+                pos: self.start,
+                message: format!(
+                    "Index {index} is out of bounds for a table of {} {}",
+                    self.len(),
+                    type_name_short::<T>()
+                ),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reads the element at `index`. Elements replaced through [_LazyTablePointer::set] are read
+    /// from their own data, so they can't contain lazy tables or pointers
+    pub fn get(&self, index: usize) -> BinResult<T> {
+        self.check_index(index)?;
+        if let Some(data) = self.patches.get(&index) {
+            return Cursor::new(data).read_type(self.endian);
+        }
+
+        read_shared(
+            &self.buffer,
+            self.start + index as u64 * self.element_size,
+            self.endian,
+        )
+    }
+
+    pub fn iter(&self) -> LazyTableIter<'_, O, C, T> {
+        self.range(0..self.len())
+    }
+
+    /// Iterates over the elements in `range`. Indices past the end of the table yield an error
+    pub fn range(&self, range: Range<usize>) -> LazyTableIter<'_, O, C, T> {
+        LazyTableIter { table: self, range }
+    }
+
+    /// Reads every element
+    pub fn to_vec(&self) -> BinResult<Vec<T>> {
+        self.iter().collect()
+    }
+}
+
+impl<O: PointerOffset, C: TableCount, T: BinRead + BinWrite> _LazyTablePointer<O, C, T>
+where
+    for<'a> <T as BinRead>::Args<'a>: Default,
+    for<'a> <T as BinWrite>::Args<'a>: Default,
+{
+    /// Replaces the element at `index`, which is written over the original element when the table
+    /// is written. Lazy tables can't be resized, so the element has to keep its size
+    pub fn set(&mut self, index: usize, value: &T) -> BinResult<()> {
+        self.check_index(index)?;
+
+        let mut data = Cursor::new(vec![]);
+        value.write_options(&mut data, self.endian, Default::default())?;
+        let data = data.into_inner();
+        if data.len() as u64 != self.element_size {
+            return Err(binrw::Error::AssertFail {
+                pos: self.start + index as u64 * self.element_size,
+                message: format!(
+                    "{} is written as {} bytes, but the elements of the table are {} bytes",
+                    type_name_short::<T>(),
+                    data.len(),
+                    self.element_size
+                ),
+            });
+        }
+
+        self.patches.insert(index, data);
+        Ok(())
+    }
+}
+
+impl<O: PointerOffset, C: TableCount, T: BinRead> Debug for _LazyTablePointer<O, C, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "LazyTablePointer(address=0x{:x}, count={}, element_size={})",
+            self.offset_base as i64 + self.offset.into(),
+            self.count.into(),
+            self.element_size,
+        ))
+    }
+}
+
+/// Elements are read as they're serialized, a read error fails the serialization
+impl<O: PointerOffset, C: TableCount, T: BinRead + Serialize> Serialize
+    for _LazyTablePointer<O, C, T>
+where
+    for<'a> T::Args<'a>: Default,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for element in self.iter() {
+            seq.serialize_element(&element.map_err(S::Error::custom)?)?;
+        }
+
+        seq.end()
+    }
+}
+
+/// Only the pointer and the elements replaced through [_LazyTablePointer::set] are written. The
+/// other elements are expected to still be in place, see [write_tag_struct]
+impl<O: PointerOffset, C: TableCount, T: BinRead> BinWrite for _LazyTablePointer<O, C, T> {
+    type Args<'b> = ();
+
+    fn write_options<W: IoWrite + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        self.count.write_options(writer, endian, ())?;
+        let count: u64 = self.count.into();
+        if count == 0 {
+            return self.offset.write_options(writer, endian, ());
+        }
+
+        write_offset::<O, _>(
+            writer,
+            endian,
+            (self.offset_base as i64 + self.offset.into()) as u64,
+        )?;
+
+        if !self.patches.is_empty() {
+            let offset_save = writer.stream_position()?;
+            for (index, data) in &self.patches {
+                writer.seek(SeekFrom::Start(
+                    self.start + *index as u64 * self.element_size,
+                ))?;
+                writer.write_all(data)?;
+            }
+            writer.seek(SeekFrom::Start(offset_save))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct LazyTableIter<'a, O: PointerOffset, C: TableCount, T: BinRead> {
+    table: &'a _LazyTablePointer<O, C, T>,
+    range: Range<usize>,
+}
+
+impl<'a, O: PointerOffset, C: TableCount, T: BinRead> Iterator for LazyTableIter<'a, O, C, T>
+where
+    for<'b> T::Args<'b>: Default,
+{
+    type Item = BinResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(|i| self.table.get(i))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a, O: PointerOffset, C: TableCount, T: BinRead> ExactSizeIterator
+    for LazyTableIter<'a, O, C, T>
+where
+    for<'b> T::Args<'b>: Default,
+{
+}
+
+/// Like [_RelPointer], but only reads the data it points to when accessed
+pub struct _LazyRelPointer<O: PointerOffset, T: BinRead> {
+    buffer: TagBuffer,
+    endian: Endian,
+    offset_base: u64,
+    offset: O,
+
+    _marker: PhantomData<T>,
+}
+
+impl<O: PointerOffset, T: BinRead> Clone for _LazyRelPointer<O, T> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            endian: self.endian,
+            offset_base: self.offset_base,
+            offset: self.offset,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<O: PointerOffset, T: BinRead> BinRead for _LazyRelPointer<O, T> {
+    type Args<'b> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        let buffer = shared_buffer(reader)?;
+        let offset_base = reader.stream_position()?;
+        let offset: O = reader.read_type(endian)?;
+
+        Ok(_LazyRelPointer {
+            buffer,
+            endian,
+            offset_base,
+            offset,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<O: PointerOffset, T: BinRead> _LazyRelPointer<O, T> {
+    /// Position of the data in the tag
+    pub fn address(&self) -> u64 {
+        (self.offset_base as i64 + self.offset.into()) as u64
+    }
+}
+
+impl<O: PointerOffset, T: BinRead> _LazyRelPointer<O, T>
+where
+    for<'a> T::Args<'a>: Default,
+{
+    /// Reads the data this points to
+    pub fn get(&self) -> BinResult<T> {
+        read_shared(&self.buffer, self.address(), self.endian)
+    }
+}
+
+impl<O: PointerOffset, T: BinRead> Debug for _LazyRelPointer<O, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "LazyRelPointer(address=0x{:x})",
+            self.offset_base as i64 + self.offset.into(),
+        ))
+    }
+}
+
+impl<O: PointerOffset, T: BinRead + Serialize> Serialize for _LazyRelPointer<O, T>
+where
+    for<'a> T::Args<'a>: Default,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<O: PointerOffset, T: BinRead> BinWrite for _LazyRelPointer<O, T> {
+    type Args<'b> = ();
+
+    fn write_options<W: IoWrite + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        write_offset::<O, _>(writer, endian, self.address())
+    }
+}
+
+impl<O: PointerOffset, T: BinRead> From<_LazyRelPointer<O, T>> for SeekFrom {
+    fn from(val: _LazyRelPointer<O, T>) -> Self {
+        SeekFrom::Start((val.offset_base as i64 + val.offset.into()) as u64)
+    }
+}
+
 #[derive(BinRead)]
 pub struct CafeMarker(#[br(assert(self_0 == 0xcafe))] u16);
 
@@ -205,11 +972,26 @@ impl Debug for DeadBeefMarker {
     }
 }
 
+impl Serialize for CafeMarker {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_struct("CafeMarker")
+    }
+}
+
+impl Serialize for DeadBeefMarker {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit_struct("DeadBeefMarker")
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct ResourcePointer {
     pub offset: u64,
     pub resource_type: u32,
     pub is_valid: bool,
+
+    /// Offset invalid pointers were read with (0 or i64::MAX)
+    invalid_offset: i64,
 }
 
 impl BinRead for ResourcePointer {
@@ -227,6 +1009,7 @@ impl BinRead for ResourcePointer {
                 offset: 0,
                 resource_type: u32::MAX,
                 is_valid: false,
+                invalid_offset: offset,
             });
         }
 
@@ -242,10 +1025,29 @@ impl BinRead for ResourcePointer {
             offset: offset_base.saturating_add_signed(offset),
             resource_type,
             is_valid: true,
+            invalid_offset: 0,
         })
     }
 }
 
+impl BinWrite for ResourcePointer {
+    type Args<'b> = ();
+
+    /// Only the pointer is written, the resource type is part of the resource it points to
+    fn write_options<W: IoWrite + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        if !self.is_valid {
+            return self.invalid_offset.write_options(writer, endian, ());
+        }
+
+        write_offset::<i64, _>(writer, endian, self.offset)
+    }
+}
+
 impl Debug for ResourcePointer {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
@@ -255,6 +1057,20 @@ impl Debug for ResourcePointer {
     }
 }
 
+/// Invalid pointers are serialized as `None`
+impl Serialize for ResourcePointer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !self.is_valid {
+            return serializer.serialize_none();
+        }
+
+        let mut s = serializer.serialize_struct("ResourcePointer", 2)?;
+        s.serialize_field("offset", &format!("0x{:x}", self.offset))?;
+        s.serialize_field("resource_type", &format!("{:08x}", self.resource_type))?;
+        s.end()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Tag<T: BinRead>(pub T, TagHash);
 
@@ -264,9 +1080,9 @@ impl<T: BinRead> Tag<T> {
     }
 }
 
-impl<'a, T: BinRead> BinRead for Tag<T>
+impl<T: BinRead> BinRead for Tag<T>
 where
-    T::Args<'a>: Default + Clone,
+    for<'a> T::Args<'a>: Default,
 {
     type Args<'b> = ();
 
@@ -277,17 +1093,29 @@ where
     ) -> BinResult<Self> {
         let taghash: TagHash = reader.read_type(endian)?;
         Ok(Tag(
-            package_manager()
-                .read_tag_struct(taghash)
-                .map_err(|e| binrw::Error::Custom {
-                    pos: reader.stream_position().unwrap(),
-                    err: Box::new(e),
-                })?,
+            read_tag_shared(taghash).map_err(|e| binrw::Error::Custom {
+                pos: reader.stream_position().unwrap(),
+                err: Box::new(e),
+            })?,
             taghash,
         ))
     }
 }
 
+/// Only the tag hash is written, the referenced tag is a separate file
+impl<T: BinRead> BinWrite for Tag<T> {
+    type Args<'b> = ();
+
+    fn write_options<W: IoWrite + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        self.1 .0.write_options(writer, endian, ())
+    }
+}
+
 impl<T: BinRead> Deref for Tag<T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
@@ -300,3 +1128,500 @@ impl<T: BinRead + Debug> Debug for Tag<T> {
         self.0.fmt(f)
     }
 }
+
+impl<T: BinRead + Serialize> Serialize for Tag<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_tag_data(self.1, &self.0, serializer)
+    }
+}
+
+/// Serializes a referenced tag as its hash and its data
+fn serialize_tag_data<T: Serialize, S: Serializer>(
+    tag: TagHash,
+    data: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut s = serializer.serialize_struct("Tag", 2)?;
+    s.serialize_field("tag", &tag_string(tag))?;
+    s.serialize_field("data", data)?;
+    s.end()
+}
+
+/// Like [Tag], but only reads the referenced tag when [LazyTag::load] is called
+pub struct LazyTag<T: BinRead>(TagHash, PhantomData<T>);
+
+impl<T: BinRead> Clone for LazyTag<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: BinRead> Copy for LazyTag<T> {}
+
+impl<T: BinRead> LazyTag<T>
+where
+    for<'a> T::Args<'a>: Default,
+{
+    pub fn tag(&self) -> TagHash {
+        self.0
+    }
+
+    pub fn load(&self) -> anyhow::Result<T> {
+        read_tag_shared(self.0)
+    }
+}
+
+impl<T: BinRead> BinRead for LazyTag<T> {
+    type Args<'b> = ();
+
+    fn read_options<R: Read + Seek>(
+        reader: &mut R,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<Self> {
+        Ok(LazyTag(reader.read_type(endian)?, PhantomData))
+    }
+}
+
+impl<T: BinRead> BinWrite for LazyTag<T> {
+    type Args<'b> = ();
+
+    fn write_options<W: IoWrite + Seek>(
+        &self,
+        writer: &mut W,
+        endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> BinResult<()> {
+        self.0 .0.write_options(writer, endian, ())
+    }
+}
+
+impl<T: BinRead> Debug for LazyTag<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("LazyTag({})", self.0))
+    }
+}
+
+/// The referenced tag is loaded to serialize it, like a [Tag]
+impl<T: BinRead + Serialize> Serialize for LazyTag<T>
+where
+    for<'a> T::Args<'a>: Default,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = self
+            .load()
+            .map_err(|e| S::Error::custom(format_args!("Failed to load {}: {e:#}", self.0)))?;
+        serialize_tag_data(self.0, &data, serializer)
+    }
+}
+
+/// String form of a tag hash, `None` for invalid hashes
+pub fn tag_string(tag: TagHash) -> Option<String> {
+    tag.is_valid().then(|| tag.to_string())
+}
+
+// Tag hashes don't implement `Serialize`, fields holding them are serialized through these with
+// `#[serde(serialize_with = "..")]`
+
+pub fn serialize_tag<S: Serializer>(tag: &TagHash, serializer: S) -> Result<S::Ok, S::Error> {
+    tag_string(*tag).serialize(serializer)
+}
+
+pub fn serialize_tags<'a, I, S>(tags: &'a I, serializer: S) -> Result<S::Ok, S::Error>
+where
+    I: ?Sized,
+    &'a I: IntoIterator<Item = &'a TagHash>,
+    S: Serializer,
+{
+    serializer.collect_seq(tags.into_iter().map(|t| tag_string(*t)))
+}
+
+pub fn serialize_tag64<S: Serializer>(tag: &TagHash64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:016X}", tag.0))
+}
+
+/// serde only implements `Serialize` for arrays of up to 32 elements
+pub fn serialize_array<T: Serialize, const N: usize, S: Serializer>(
+    array: &[T; N],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(array)
+}
+
+pub fn serialize_string_pointer<O: PointerOffset, S: Serializer>(
+    pointer: &_RelPointer<O, NullString>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&pointer.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DestinyHash, IVector2, Vector2, Vector3, Vector4};
+    use binrw::BinWriterExt;
+
+    /// Array header followed by `elements`
+    fn array(count: u64, elements: &[u32]) -> Vec<u8> {
+        array_of_class(count, 0, elements)
+    }
+
+    fn array_of_class(count: u64, class_hash: u32, elements: &[u32]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&class_hash.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for e in elements {
+            data.extend_from_slice(&e.to_le_bytes());
+        }
+        data
+    }
+
+    fn table32(elements: &[u32]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&(elements.len() as u32).to_le_bytes());
+        data.extend_from_slice(&4i32.to_le_bytes());
+        data.extend(array(elements.len() as u64, elements));
+        data
+    }
+
+    #[test]
+    fn table_pointer_32() {
+        let data = table32(&[10, 20, 30]);
+        let table: TablePointer32<u32> = Cursor::new(&data).read_le().unwrap();
+        assert_eq!(table.data(), &[10, 20, 30]);
+    }
+
+    #[test]
+    fn traced_reads() {
+        let data = Arc::new(table32(&[10, 20]));
+        let (table, reads) = read_shared_traced::<TablePointer32<u32>>(&data, Endian::Little);
+        assert_eq!(table.unwrap().data(), &[10, 20]);
+        assert_eq!(reads[..2], [0..4, 4..8]);
+        assert_eq!(reads.last().unwrap().end, data.len() as u64);
+    }
+
+    #[test]
+    fn table_pointer_64() {
+        let mut data = vec![];
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&8i64.to_le_bytes());
+        data.extend(array(2, &[1, 2]));
+
+        let table: TablePointer64<u32> = Cursor::new(&data).read_le().unwrap();
+        assert_eq!(table.data(), &[1, 2]);
+    }
+
+    #[test]
+    fn table_pointer_count_mismatch() {
+        let mut data = vec![];
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&4i32.to_le_bytes());
+        data.extend(array(3, &[1, 2, 3]));
+
+        assert!(Cursor::new(&data).read_le::<TablePointer32<u32>>().is_err());
+    }
+
+    #[derive(BinRead, Debug)]
+    struct Unk80801234 {
+        value: u32,
+    }
+
+    fn table32_of_class(class_hash: u32, elements: &[u32]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&(elements.len() as u32).to_le_bytes());
+        data.extend_from_slice(&4i32.to_le_bytes());
+        data.extend(array_of_class(elements.len() as u64, class_hash, elements));
+        data
+    }
+
+    #[test]
+    fn table_pointer_class() {
+        let data = table32_of_class(0x80801234, &[1, 2]);
+        let table: TablePointer32<Unk80801234> = Cursor::new(&data).read_le().unwrap();
+        assert_eq!(table.iter().map(|e| e.value).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[test]
+    fn table_pointer_class_mismatch() {
+        let data = table32_of_class(0x80805678, &[1, 2]);
+        let error = Cursor::new(&data)
+            .read_le::<TablePointer32<Unk80801234>>()
+            .unwrap_err();
+
+        let message = error.to_string();
+        assert!(
+            message.contains("Expected a table of class 80801234 (Unk80801234)"),
+            "{message}"
+        );
+        assert!(message.contains("has class 80805678"), "{message}");
+    }
+
+    #[test]
+    fn table_pointer_out_of_bounds() {
+        let mut data = table32(&[1, 2, 3]);
+        data.truncate(data.len() - 4);
+
+        assert!(Cursor::new(&data).read_le::<TablePointer32<u32>>().is_err());
+    }
+
+    #[test]
+    fn table_pointer_round_trip() {
+        let data = table32(&[10, 20, 30]);
+        let mut table: TablePointer32<u32> = Cursor::new(&data).read_le().unwrap();
+        assert_eq!(write_tag_struct(&data, &table).unwrap(), data);
+
+        table[1] = 25;
+        let patched = write_tag_struct(&data, &table).unwrap();
+        assert_eq!(patched.len(), data.len());
+        let table: TablePointer32<u32> = Cursor::new(&patched).read_le().unwrap();
+        assert_eq!(table.data(), &[10, 25, 30]);
+    }
+
+    #[test]
+    fn table_pointer_relocation() {
+        let data = table32(&[10, 20, 30]);
+        let mut table: TablePointer32<u32> = Cursor::new(&data).read_le().unwrap();
+        table.data_mut().push(40);
+
+        let relocated = write_tag_struct(&data, &table).unwrap();
+        // Original data is left in place, the new table is aligned after it
+        assert_eq!(relocated.len(), 48 + 16 + 4 * 4);
+        assert_eq!(&relocated[8..data.len()], &data[8..]);
+
+        let table: TablePointer32<u32> = Cursor::new(&relocated).read_le().unwrap();
+        assert_eq!(table.data(), &[10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn rel_pointer_32_backwards() {
+        let mut data = vec![];
+        data.extend_from_slice(&0xdeadbeefu32.to_le_bytes());
+        data.extend_from_slice(&(-4i32).to_le_bytes());
+
+        let mut cur = Cursor::new(&data);
+        cur.seek(SeekFrom::Start(4)).unwrap();
+        let pointer: RelPointer32<u32> = cur.read_le().unwrap();
+        assert_eq!(*pointer, 0xdeadbeef);
+        assert_eq!(cur.stream_position().unwrap(), 8);
+    }
+
+    #[test]
+    fn rel_pointer_64() {
+        let mut data = vec![];
+        data.extend_from_slice(&8i64.to_le_bytes());
+        data.extend_from_slice(&0x1234u32.to_le_bytes());
+
+        let pointer: RelPointer64<u32> = Cursor::new(&data).read_le().unwrap();
+        assert_eq!(*pointer, 0x1234);
+    }
+
+    #[test]
+    fn lazy_table_pointer_32() {
+        let buffer = Arc::new(table32(&[5, 6, 7]));
+        let table: LazyTablePointer32<u32> = read_shared(&buffer, 0, Endian::Little).unwrap();
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get(1).unwrap(), 6);
+        assert!(table.get(3).is_err());
+        assert_eq!(
+            table.range(1..3).collect::<BinResult<Vec<_>>>().unwrap(),
+            vec![6, 7]
+        );
+    }
+
+    #[test]
+    fn lazy_rel_pointer() {
+        let mut data = vec![];
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&8i64.to_le_bytes());
+        data.extend_from_slice(&0x1234u32.to_le_bytes());
+        let buffer = Arc::new(data);
+
+        let pointer: LazyRelPointer<u32> = read_shared(&buffer, 4, Endian::Little).unwrap();
+        assert_eq!(pointer.address(), 12);
+        assert_eq!(pointer.get().unwrap(), 0x1234);
+
+        // The shared buffer is only available through read_shared
+        let mut cur = Cursor::new(buffer.as_slice());
+        cur.seek(SeekFrom::Start(4)).unwrap();
+        assert!(cur.read_le::<LazyRelPointer<u32>>().is_err());
+    }
+
+    #[derive(BinRead)]
+    struct LazyFields {
+        tag: LazyTag<u32>,
+        pointer: LazyRelPointer32<u32>,
+    }
+
+    #[test]
+    fn lazy_tag() {
+        let mut data = vec![];
+        data.extend_from_slice(&0x80a01234u32.to_le_bytes());
+        data.extend_from_slice(&4i32.to_le_bytes());
+        data.extend_from_slice(&0xdeadbeefu32.to_le_bytes());
+        let buffer = Arc::new(data);
+
+        // The referenced tag isn't read until it's loaded
+        let fields: LazyFields = read_shared(&buffer, 0, Endian::Little).unwrap();
+        assert_eq!(fields.tag.tag(), TagHash(0x80a01234));
+        assert_eq!(fields.pointer.get().unwrap(), 0xdeadbeef);
+        assert_eq!(write_tag_struct(&buffer, &fields.tag).unwrap(), *buffer);
+    }
+
+    #[test]
+    fn lazy_table_set() {
+        let buffer = Arc::new(table32(&[5, 6, 7]));
+        let mut table: LazyTablePointer32<u32> = read_shared(&buffer, 0, Endian::Little).unwrap();
+        assert_eq!(write_tag_struct(&buffer, &table).unwrap(), *buffer);
+
+        table.set(1, &25).unwrap();
+        assert_eq!(table.to_vec().unwrap(), vec![5, 25, 7]);
+        assert!(table.set(3, &8).is_err());
+
+        let patched = Arc::new(write_tag_struct(&buffer, &table).unwrap());
+        assert_eq!(patched.len(), buffer.len());
+        let table: LazyTablePointer32<u32> = read_shared(&patched, 0, Endian::Little).unwrap();
+        assert_eq!(table.to_vec().unwrap(), vec![5, 25, 7]);
+    }
+
+    /// `data` with a resource of class 0x808071b3 at 0x10, and a pointer to it at 0
+    fn resource_pointer_data(offset: i64) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0x808071b3u32.to_le_bytes());
+        data.extend_from_slice(&0x1234u32.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn resource_pointer_round_trip() {
+        let data = resource_pointer_data(16);
+        let pointer: ResourcePointer = Cursor::new(&data).read_le().unwrap();
+        assert!(pointer.is_valid);
+        assert_eq!(pointer.offset, 16);
+        assert_eq!(pointer.resource_type, 0x808071b3);
+        assert_eq!(write_tag_struct(&data, &pointer).unwrap(), data);
+    }
+
+    #[test]
+    fn resource_pointer_invalid_round_trip() {
+        for offset in [0, i64::MAX] {
+            let data = resource_pointer_data(offset);
+            let pointer: ResourcePointer = Cursor::new(&data).read_le().unwrap();
+            assert!(!pointer.is_valid);
+            assert_eq!(write_tag_struct(&data, &pointer).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn rel_pointer_round_trip() {
+        let mut data = vec![];
+        data.extend_from_slice(&0xdeadbeefu32.to_le_bytes());
+        data.extend_from_slice(&(-4i32).to_le_bytes());
+        data.extend_from_slice(&8i64.to_le_bytes());
+        data.extend_from_slice(&0x1234u32.to_le_bytes());
+
+        let mut cur = Cursor::new(&data);
+        cur.seek(SeekFrom::Start(4)).unwrap();
+        let pointers: (RelPointer32<u32>, RelPointer64<u32>) = cur.read_le().unwrap();
+        assert_eq!((*pointers.0, *pointers.1), (0xdeadbeef, 0x1234));
+
+        let mut out = Cursor::new(data.clone());
+        out.seek(SeekFrom::Start(4)).unwrap();
+        out.write_le(&pointers).unwrap();
+        assert_eq!(out.into_inner(), data);
+    }
+
+    #[test]
+    fn tag_round_trip() {
+        let data = 0x80a01234u32.to_le_bytes().to_vec();
+        // Reading a tag goes through the package manager, so only the write is tested
+        let tag = Tag(0u32, TagHash(0x80a01234));
+        assert_eq!(write_tag_struct(&data, &tag).unwrap(), data);
+        assert_eq!(
+            write_tag_struct(&data, &LazyTag::<u32>(tag.tag(), PhantomData)).unwrap(),
+            data
+        );
+    }
+
+    #[derive(BinRead, BinWrite)]
+    struct Primitives {
+        hash: DestinyHash,
+        none: DestinyHash,
+        ivec2: IVector2,
+        vec2: Vector2,
+        vec3: Vector3,
+        vec4: Vector4,
+    }
+
+    #[test]
+    fn primitives_round_trip() {
+        let mut data = vec![];
+        data.extend_from_slice(&0x12345678u32.to_le_bytes());
+        data.extend_from_slice(&0x811c9dc5u32.to_le_bytes());
+        data.extend_from_slice(&(-3i32).to_le_bytes());
+        data.extend_from_slice(&7i32.to_le_bytes());
+        for f in [1.5f32, -2.25, 0.1, 1e-8, -0.0, 3.75, 1e10, -1e-3, f32::MAX] {
+            data.extend_from_slice(&f.to_le_bytes());
+        }
+
+        let value: Primitives = Cursor::new(&data).read_le().unwrap();
+        assert!(value.none.is_none());
+        assert_eq!(value.hash, DestinyHash(0x12345678));
+        assert_eq!((value.ivec2.x, value.ivec2.y), (-3, 7));
+        assert_eq!(
+            (value.vec2.x, value.vec3.z, value.vec4.w),
+            (1.5, -0.0, f32::MAX)
+        );
+        assert_eq!(write_tag_struct(&data, &value).unwrap(), data);
+    }
+
+    fn yaml(s: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[derive(BinRead, Serialize)]
+    struct Serialized {
+        hashes: [DestinyHash; 2],
+        table: TablePointer32<u32>,
+        lazy_table: LazyTablePointer32<u32>,
+    }
+
+    #[test]
+    fn serialize_tables() {
+        let mut data = vec![];
+        data.extend_from_slice(&0x12345678u32.to_le_bytes());
+        data.extend_from_slice(&0x811c9dc5u32.to_le_bytes());
+        // Both tables point to the same elements
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&12i32.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&4i32.to_le_bytes());
+        data.extend(array(2, &[5, 6]));
+        let buffer = Arc::new(data);
+
+        // Lazy tables are read as they're serialized
+        let value: Serialized = read_shared(&buffer, 0, Endian::Little).unwrap();
+        assert_eq!(
+            serde_yaml::to_value(&value).unwrap(),
+            yaml("{ hashes: [305419896, null], table: [5, 6], lazy_table: [5, 6] }")
+        );
+    }
+
+    #[test]
+    fn serialize_resource_pointer() {
+        let pointer: ResourcePointer = Cursor::new(resource_pointer_data(16)).read_le().unwrap();
+        assert_eq!(
+            serde_yaml::to_value(pointer).unwrap(),
+            yaml("{ offset: '0x10', resource_type: 808071b3 }")
+        );
+
+        let pointer: ResourcePointer = Cursor::new(resource_pointer_data(0)).read_le().unwrap();
+        assert_eq!(
+            serde_yaml::to_value(pointer).unwrap(),
+            serde_yaml::Value::Null
+        );
+    }
+}
@@ -1,24 +1,41 @@
-use crate::structure::{RelPointer, TablePointer};
+use crate::packages::package_manager;
+use crate::structure::{serialize_tag, RelPointer, TablePointer};
 use crate::types::DestinyHash;
-use binrw::BinRead;
+use binrw::{BinRead, BinReaderExt};
 use destiny_pkg::TagHash;
+use nohash_hasher::IntMap;
+use serde::Serialize;
+use std::io::{Cursor, Read, Seek, SeekFrom};
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct StringSetHeader {
     pub file_size: u64,
     pub string_hashes: TablePointer<DestinyHash>,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_english: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk1: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_german: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_french: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk4: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk5: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_italian: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk7: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk8: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk9: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk10: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_polish: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub language_unk12: TagHash,
 }
 
@@ -87,3 +104,46 @@ pub fn decode_text(data: &[u8], cipher: u16) -> String {
 
     result
 }
+
+/// Loads all english strings from the global packages, keyed by string hash
+pub fn load_global_strings() -> anyhow::Result<IntMap<u32, String>> {
+    let _span = info_span!("Loading global strings").entered();
+    let mut stringmap: IntMap<u32, String> = Default::default();
+    let all_global_packages = [
+        0x019a, 0x01cf, 0x01fe, 0x0211, 0x0238, 0x03ab, 0x03d1, 0x03ed, 0x03f5, 0x06dc,
+    ];
+
+    for (t, _) in package_manager()
+        .get_all_by_reference(0x80809a88)
+        .into_iter()
+        .filter(|(t, _)| all_global_packages.contains(&t.pkg_id()))
+    {
+        let textset_header: StringSetHeader = package_manager().read_tag_struct(t)?;
+
+        let data = package_manager().read_tag(textset_header.language_english)?;
+        let mut cur = Cursor::new(&data);
+        let text_data: StringData = cur.read_le()?;
+
+        for (combination, hash) in text_data
+            .string_combinations
+            .iter()
+            .zip(textset_header.string_hashes.iter())
+        {
+            let mut final_string = String::new();
+
+            for ip in 0..combination.part_count {
+                cur.seek(combination.data.into())?;
+                cur.seek(SeekFrom::Current(ip * 0x20))?;
+                let part: StringPart = cur.read_le()?;
+                cur.seek(part.data.into())?;
+                let mut data = vec![0u8; part.byte_length as usize];
+                cur.read_exact(&mut data)?;
+                final_string += &decode_text(&data, part.cipher_shift);
+            }
+
+            stringmap.insert(hash.0, final_string);
+        }
+    }
+
+    Ok(stringmap)
+}
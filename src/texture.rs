@@ -1,25 +1,43 @@
 use crate::dxgi::DxgiFormat;
 use crate::packages::package_manager;
+#[cfg(windows)]
 use crate::render::drawcall::ShaderStages;
+#[cfg(windows)]
 use crate::render::DeviceContextSwapchain;
-use crate::structure::{CafeMarker, TablePointer};
+use crate::structure::{serialize_tag, CafeMarker, TablePointer};
 use crate::types::IVector2;
+use crate::version;
 use anyhow::Context;
-use binrw::BinRead;
+use binrw::{binread, BinRead};
 use destiny_pkg::TagHash;
+use serde::Serialize;
 use std::io::SeekFrom;
+#[cfg(windows)]
 use windows::Win32::Graphics::Direct3D::{
     WKPDID_D3DDebugObjectName, D3D11_SRV_DIMENSION_TEXTURE2D, D3D11_SRV_DIMENSION_TEXTURE3D,
     D3D11_SRV_DIMENSION_TEXTURECUBE,
 };
+#[cfg(windows)]
 use windows::Win32::Graphics::Direct3D11::*;
+#[cfg(windows)]
 use windows::Win32::Graphics::Direct3D11::{
     ID3D11ShaderResourceView, ID3D11Texture2D, ID3D11Texture3D,
 };
+#[cfg(windows)]
 use windows::Win32::Graphics::Dxgi::Common::*;
 
-#[derive(BinRead, Debug)]
+/// Offsets that differ between versions, see [crate::version::StructLayouts]
+#[derive(Debug)]
+pub struct TextureHeaderLayout {
+    pub large_buffer: u64,
+}
+
+#[binread]
+#[derive(Debug)]
 pub struct TextureHeader {
+    #[br(temp, try_calc(version::layouts().map(|l| &l.texture_header)))]
+    layout: &'static TextureHeaderLayout,
+
     pub data_size: u32,
     pub format: DxgiFormat,
     pub _unk8: u32,
@@ -31,7 +49,7 @@ pub struct TextureHeader {
     pub depth: u16,
     pub array_size: u16,
 
-    #[br(seek_before = SeekFrom::Start(0x24))]
+    #[br(seek_before = SeekFrom::Start(layout.large_buffer))]
     #[br(map(|v: u32| (v != u32::MAX).then_some(TagHash(v))))]
     pub large_buffer: Option<TagHash>,
 }
@@ -52,74 +70,90 @@ pub struct TexturePlateTransform {
 }
 
 /// Ref: 0x808072d2
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Debug, Serialize)]
 pub struct TexturePlateSet {
     pub file_size: u64,
     pub _unk: [u32; 7],
+    #[serde(serialize_with = "serialize_tag")]
     pub diffuse: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub normal: TagHash,
+    #[serde(serialize_with = "serialize_tag")]
     pub gstack: TagHash,
 }
 
+/// Reads the header and pixel data of a texture, without creating any GPU resources.
+/// Returns the header, the data and the number of mip levels contained in the data
+pub fn load_texture_data(hash: TagHash) -> anyhow::Result<(TextureHeader, Vec<u8>, usize)> {
+    let texture_header_ref = package_manager().get_entry(hash)?.reference;
+
+    let texture: TextureHeader = package_manager().read_tag_struct(hash)?;
+    let mut texture_data = if let Some(t) = texture.large_buffer {
+        package_manager()
+            .read_tag(t)
+            .context("Failed to read texture data")?
+    } else {
+        package_manager()
+            .read_tag(texture_header_ref)
+            .context("Failed to read texture data")?
+            .to_vec()
+    };
+
+    let mut mips = 1;
+    if texture.large_buffer.is_some() {
+        let ab = package_manager()
+            .read_tag(texture_header_ref)
+            .context("Failed to read texture data")?
+            .to_vec();
+
+        texture_data.extend(ab);
+
+        let mut dim = texture.width.min(texture.width) as usize;
+        mips = 0;
+        while dim > 1 {
+            dim >>= 1;
+            mips += 1;
+        }
+
+        let mut required_mip_bytes = 0;
+        for i in 0..mips {
+            let width = texture.width >> i;
+            let height = texture.height >> i;
+            let size = texture
+                .format
+                .calculate_pitch(width as usize, height as usize);
+            if (required_mip_bytes + size.1) > texture_data.len() {
+                mips = i + 1;
+                break;
+            }
+            required_mip_bytes += size.1;
+        }
+    }
+
+    Ok((texture, texture_data, mips))
+}
+
+#[cfg(windows)]
 pub enum TextureHandle {
     Texture2D(ID3D11Texture2D),
     TextureCube(ID3D11Texture2D),
     Texture3D(ID3D11Texture3D),
 }
 
+#[cfg(windows)]
 pub struct Texture {
     pub view: ID3D11ShaderResourceView,
     pub handle: TextureHandle,
     pub format: DxgiFormat,
+    /// Size of the pixel data in bytes
+    pub size: usize,
 }
 
+#[cfg(windows)]
 impl Texture {
     pub fn load(dcs: &DeviceContextSwapchain, hash: TagHash) -> anyhow::Result<Texture> {
         let _span = debug_span!("Load texture", %hash).entered();
-        let texture_header_ref = package_manager().get_entry(hash)?.reference;
-
-        let texture: TextureHeader = package_manager().read_tag_struct(hash)?;
-        let mut texture_data = if let Some(t) = texture.large_buffer {
-            package_manager()
-                .read_tag(t)
-                .context("Failed to read texture data")?
-        } else {
-            package_manager()
-                .read_tag(texture_header_ref)
-                .context("Failed to read texture data")?
-                .to_vec()
-        };
-
-        let mut mips = 1;
-        if texture.large_buffer.is_some() {
-            let ab = package_manager()
-                .read_tag(texture_header_ref)
-                .context("Failed to read texture data")?
-                .to_vec();
-
-            texture_data.extend(ab);
-
-            let mut dim = texture.width.min(texture.width) as usize;
-            mips = 0;
-            while dim > 1 {
-                dim >>= 1;
-                mips += 1;
-            }
-
-            let mut required_mip_bytes = 0;
-            for i in 0..mips {
-                let width = texture.width >> i;
-                let height = texture.height >> i;
-                let size = texture
-                    .format
-                    .calculate_pitch(width as usize, height as usize);
-                if (required_mip_bytes + size.1) > texture_data.len() {
-                    mips = i + 1;
-                    break;
-                }
-                required_mip_bytes += size.1;
-            }
-        }
+        let (texture, texture_data, mips) = load_texture_data(hash)?;
 
         let (tex, view) = unsafe {
             if texture.depth > 1 {
@@ -299,6 +333,7 @@ impl Texture {
             handle: tex,
             view,
             format: texture.format,
+            size: texture_data.len(),
         })
     }
 
@@ -365,6 +400,7 @@ impl Texture {
                 handle: TextureHandle::Texture2D(tex),
                 view,
                 format,
+                size: data.len(),
             })
         }
     }
@@ -1,9 +1,10 @@
-use binrw::BinRead;
+use binrw::{BinRead, BinWrite};
 use bytemuck::{Pod, Zeroable};
 use glam::{Vec3, Vec3A};
+use serde::{Serialize, Serializer};
 use std::fmt::{Debug, Formatter, Write};
 
-#[derive(BinRead, Copy, Clone, PartialEq)]
+#[derive(BinRead, BinWrite, Copy, Clone, PartialEq)]
 pub struct DestinyHash(pub u32);
 
 impl From<DestinyHash> for u32 {
@@ -38,21 +39,28 @@ impl Debug for DestinyHash {
     }
 }
 
-#[derive(BinRead, Debug, Clone)]
+/// `NONE` is serialized as `None`
+impl Serialize for DestinyHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (!self.is_none()).then_some(self.0).serialize(serializer)
+    }
+}
+
+#[derive(BinRead, BinWrite, Debug, Clone, Serialize)]
 pub struct IVector2 {
     pub x: i32,
     pub y: i32,
 }
 
 #[repr(C)]
-#[derive(BinRead, Copy, Clone, Default, Pod, Zeroable)]
+#[derive(BinRead, BinWrite, Copy, Clone, Default, Pod, Zeroable, Serialize)]
 pub struct Vector2 {
     pub x: f32,
     pub y: f32,
 }
 
 #[repr(C)]
-#[derive(BinRead, Copy, Clone, Default, Pod, Zeroable)]
+#[derive(BinRead, BinWrite, Copy, Clone, Default, Pod, Zeroable, Serialize)]
 pub struct Vector3 {
     pub x: f32,
     pub y: f32,
@@ -60,7 +68,7 @@ pub struct Vector3 {
 }
 
 #[repr(C)]
-#[derive(BinRead, Copy, Clone, Default, Pod, Zeroable)]
+#[derive(BinRead, BinWrite, Copy, Clone, Default, Pod, Zeroable, Serialize)]
 pub struct Vector4 {
     pub x: f32,
     pub y: f32,
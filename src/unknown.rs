@@ -1,15 +1,16 @@
-use crate::structure::TablePointer;
+use crate::structure::{tag_string, TablePointer};
 use crate::types::DestinyHash;
 use binrw::BinRead;
 use destiny_pkg::TagHash;
+use serde::{Serialize, Serializer};
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80804f72 {
     pub file_size: u64,
     pub unk8: TablePointer<Unk80804f74>,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80804f74 {
     pub unk0: DestinyHash,
     pub unk4: DestinyHash,
@@ -18,10 +19,21 @@ pub struct Unk80804f74 {
     pub unk20: u64,
 }
 
-#[derive(BinRead, Debug, Clone)]
+#[derive(BinRead, Debug, Clone, Serialize)]
 pub struct Unk80804f76 {
+    #[serde(serialize_with = "serialize_tag_pair")]
     pub unk0: (TagHash, DestinyHash),
+    #[serde(serialize_with = "serialize_tag_pair")]
     pub unk8: (TagHash, DestinyHash),
+    #[serde(serialize_with = "serialize_tag_pair")]
     pub unk10: (TagHash, DestinyHash),
+    #[serde(serialize_with = "serialize_tag_pair")]
     pub unk18: (TagHash, DestinyHash),
 }
+
+fn serialize_tag_pair<S: Serializer>(
+    pair: &(TagHash, DestinyHash),
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    (tag_string(pair.0), pair.1).serialize(serializer)
+}
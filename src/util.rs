@@ -66,3 +66,10 @@ macro_rules! caller_frame {
 
 #[allow(unused)]
 pub(crate) use caller_frame;
+
+/// FNV-1a, for hashes that have to stay the same between runs (eg. to compare dumps)
+pub fn fnv1a64(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,160 @@
+//! Game/package versions, and the registry of struct layouts that differ between them. Only
+//! pre-Beyond Light packages are supported for now, other versions are detected and rejected
+
+use std::fmt::Display;
+
+use destiny_pkg::PackageVersion;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use strum::EnumIter;
+
+use crate::material::MaterialLayout;
+use crate::texture::TextureHeaderLayout;
+
+// Named after the matching destiny-pkg PackageVersion variants
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, EnumIter)]
+pub enum GameVersion {
+    Destiny2PreBeyondLight,
+    Destiny2BeyondLight,
+    Destiny2Lightfall,
+}
+
+impl GameVersion {
+    /// Supported versions, newest first (the order used for auto-detection). Every version in here
+    /// needs an entry in [LAYOUTS]
+    pub const ALL: &'static [GameVersion] = &[GameVersion::Destiny2PreBeyondLight];
+
+    /// Versions whose packages can be opened, but whose struct layouts aren't known yet. Only used
+    /// to give a clear error when detecting them
+    pub const UNSUPPORTED: &'static [GameVersion] = &[
+        GameVersion::Destiny2Lightfall,
+        GameVersion::Destiny2BeyondLight,
+    ];
+
+    pub fn package_version(self) -> PackageVersion {
+        match self {
+            GameVersion::Destiny2PreBeyondLight => PackageVersion::Destiny2PreBeyondLight,
+            GameVersion::Destiny2BeyondLight => PackageVersion::Destiny2BeyondLight,
+            GameVersion::Destiny2Lightfall => PackageVersion::Destiny2Lightfall,
+        }
+    }
+
+    /// Finds the first supported version that can open the package at `pkg_path`
+    pub fn detect(pkg_path: &str) -> anyhow::Result<GameVersion> {
+        let _span = debug_span!("Detecting package version", pkg_path).entered();
+        for v in Self::ALL {
+            match v.package_version().open(pkg_path) {
+                Ok(_) => return Ok(*v),
+                Err(e) => debug!("Package is not {v}: {e:?}"),
+            }
+        }
+
+        for v in Self::UNSUPPORTED {
+            if v.package_version().open(pkg_path).is_ok() {
+                anyhow::bail!("Package {pkg_path} is from {v}, which is not supported yet");
+            }
+        }
+
+        anyhow::bail!("Could not detect the version of package {pkg_path}")
+    }
+
+    /// Returns the struct layouts registered for this version
+    pub fn layouts(self) -> Option<&'static StructLayouts> {
+        LAYOUTS.iter().find(|(v, _)| *v == self).map(|(_, l)| l)
+    }
+}
+
+impl Display for GameVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GameVersion::Destiny2PreBeyondLight => "Destiny 2 (pre-Beyond Light)",
+            GameVersion::Destiny2BeyondLight => "Destiny 2 (Beyond Light)",
+            GameVersion::Destiny2Lightfall => "Destiny 2 (Lightfall)",
+        })
+    }
+}
+
+/// Layouts of every struct that differs between versions. Structs read their layout through [layouts] while parsing
+pub struct StructLayouts {
+    pub material: MaterialLayout,
+    pub texture_header: TextureHeaderLayout,
+}
+
+/// Registered struct layouts, one for every version in [GameVersion::ALL]
+const LAYOUTS: &[(GameVersion, StructLayouts)] = &[(
+    GameVersion::Destiny2PreBeyondLight,
+    StructLayouts {
+        material: MaterialLayout {
+            vertex_shader: 0x48,
+            pixel_shader: 0x2c8,
+        },
+        texture_header: TextureHeaderLayout { large_buffer: 0x24 },
+    },
+)];
+
+lazy_static! {
+    static ref GAME_VERSION: RwLock<GameVersion> = RwLock::new(GameVersion::Destiny2PreBeyondLight);
+}
+
+pub fn current() -> GameVersion {
+    *GAME_VERSION.read()
+}
+
+pub fn set_current(version: GameVersion) {
+    *GAME_VERSION.write() = version;
+}
+
+/// Returns the struct layouts for the current version, for use in `#[br(try_calc(...))]`
+pub fn layouts() -> binrw::BinResult<&'static StructLayouts> {
+    let version = current();
+    version.layouts().ok_or_else(|| binrw::Error::AssertFail {
+        pos: 0,
+        message: format!("No struct layouts are registered for {version}"),
+    })
+}
+
+/// Detects the version of the package at `pkg_path` and makes it the current version. There's
+/// nothing to choose between until a second version has layouts registered
+pub fn select(pkg_path: &str) -> anyhow::Result<GameVersion> {
+    let version = GameVersion::detect(pkg_path)?;
+    anyhow::ensure!(
+        version.layouts().is_some(),
+        "{version} is not supported yet, no struct layouts are registered for it"
+    );
+
+    info!("Using package version {version}");
+    set_current(version);
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    #[test]
+    fn supported_versions_have_layouts() {
+        for v in GameVersion::ALL {
+            assert!(v.layouts().is_some(), "{v} has no struct layouts");
+        }
+
+        for v in GameVersion::UNSUPPORTED {
+            assert!(
+                !GameVersion::ALL.contains(v),
+                "{v} is listed as both supported and unsupported"
+            );
+        }
+    }
+
+    #[test]
+    fn every_version_is_listed() {
+        for v in GameVersion::iter() {
+            assert!(
+                GameVersion::ALL.contains(&v) || GameVersion::UNSUPPORTED.contains(&v),
+                "{v} is missing from GameVersion::ALL and GameVersion::UNSUPPORTED"
+            );
+        }
+    }
+}
@@ -0,0 +1,624 @@
+//! The map viewer. Renders through Direct3D 11, so this is only built on Windows
+
+use std::cell::RefCell;
+
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use destiny_pkg::TagHash;
+use glam::{Mat4, Quat, Vec2, Vec3};
+
+use strum::EnumCount;
+
+use windows::Win32::Foundation::DXGI_STATUS_OCCLUDED;
+use windows::Win32::Graphics::Direct3D::*;
+use windows::Win32::Graphics::Direct3D11::*;
+use windows::Win32::Graphics::Dxgi::{Common::*, DXGI_PRESENT_TEST, DXGI_SWAP_EFFECT_SEQUENTIAL};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, VirtualKeyCode};
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+};
+
+use crate::camera::FpsCamera;
+use crate::config::{self, WindowConfig};
+
+use crate::input::InputState;
+use crate::map::MapDataList;
+use crate::map_loader::{MapLoadRequest, MapLoader};
+use crate::map_resources::MapResource;
+use crate::overlays::camera_settings::{CameraPositionOverlay, CurrentCubemap};
+use crate::overlays::console::ConsoleOverlay;
+use crate::overlays::fps_display::FpsDisplayOverlay;
+use crate::overlays::gui::GuiManager;
+use crate::overlays::hex_viewer::HexViewerOverlay;
+use crate::overlays::inspector::InspectorOverlay;
+use crate::overlays::load_indicator::LoadIndicatorOverlay;
+use crate::overlays::map_browser::MapBrowserOverlay;
+use crate::overlays::memory::{MemoryOverlay, MemoryStats};
+use crate::overlays::references::ReferencesOverlay;
+use crate::overlays::render_settings::RenderSettingsOverlay;
+use crate::overlays::resource_nametags::ResourceTypeOverlay;
+use crate::overlays::search::SearchOverlay;
+use crate::overlays::tag_dump::TagDumper;
+use crate::packages;
+use crate::picking::{PickingScene, Ray, SelectedObject};
+use crate::render::debug::DebugShapes;
+use crate::render::error::ErrorRenderer;
+use crate::render::renderer::{Renderer, ScopeOverrides};
+use crate::render::resource_mt::{BUFFER_QUEUE, TEXTURE_QUEUE};
+use crate::render::DeviceContextSwapchain;
+use crate::resources::Resources;
+use crate::text::load_global_strings;
+use crate::version;
+
+/// Opens the package at `pkg_path` and runs the viewer until its window is closed. The package
+/// version has to be selected beforehand
+pub fn run(pkg_path: String) -> anyhow::Result<()> {
+    let package = info_span!("Opening package").in_scope(|| {
+        version::current()
+            .package_version()
+            .open(&pkg_path)
+            .with_context(|| format!("Failed to open package {pkg_path}"))
+    })?;
+    packages::initialize(&pkg_path)?;
+
+    let stringmap = load_global_strings()?;
+
+    // for (t, _) in package_manager().get_all_by_reference(0x80806cb1) {
+    //     let unk: Unk80806cb1 = package_manager().read_tag_struct(t)?;
+
+    //     for m in &unk.unk20 {
+    //         if !m.unkc.is_valid() {
+    //             warn!("Pipeline '{}' doesn't have a material", m.name.to_string());
+    //             continue;
+    //         }
+    //         let material: Unk808071e8 = package_manager().read_tag_struct(m.unkc)?;
+
+    //         println!(
+    //             "Extracting '{}' (vs={}, ps={}, {} textures)",
+    //             *m.name,
+    //             material.vertex_shader.is_valid(),
+    //             material.pixel_shader.is_valid(),
+    //             // material.compute_shader.is_valid(),
+    //             material.vs_textures.len() + material.ps_textures.len() // + material.cs_textures.len()
+    //         );
+
+    //         let pipeline_dir = PathBuf::from_str("./pipelines/")
+    //             .unwrap()
+    //             .join(m.name.to_string());
+    //         std::fs::create_dir_all(&pipeline_dir)?;
+
+    //         if material.vertex_shader.is_valid() {
+    //             let header_entry = package_manager().get_entry(material.vertex_shader)?;
+    //             let data = package_manager().read_tag(TagHash(header_entry.reference))?;
+    //             File::create(&pipeline_dir.join("vertex.cso"))?.write_all(&data)?;
+    //         }
+
+    //         if material.pixel_shader.is_valid() {
+    //             let header_entry = package_manager().get_entry(material.pixel_shader)?;
+    //             let data = package_manager().read_tag(TagHash(header_entry.reference))?;
+    //             File::create(&pipeline_dir.join("pixel.cso"))?.write_all(&data)?;
+    //         }
+
+    //         // if material.compute_shader.is_valid() {
+    //         //     let header_entry = package_manager.get_entry_by_tag(material.compute_shader)?;
+    //         //     let data = package_manager.read_tag(TagHash(header_entry.reference))?;
+    //         //     File::create(&pipeline_dir.join("compute.cso"))?.write_all(&data)?;
+    //         // }
+
+    //         let mut out = File::create(pipeline_dir.join("material.txt"))?;
+    //         write!(&mut out, "{material:#x?}")?;
+
+    //         let mut out = File::create(pipeline_dir.join("material.bin"))?;
+    //         let data = package_manager().read_tag(m.unkc)?;
+    //         out.write_all(&data)?;
+    //     }
+
+    //     // println!("{unk:#?}");
+    // }
+
+    info!("Loaded {} global strings", stringmap.len());
+
+    let event_loop = EventLoop::new();
+    let window = winit::window::WindowBuilder::new()
+        .with_title("Alkahest")
+        .with_inner_size(config::with(|c| {
+            PhysicalSize::new(c.window.width, c.window.height)
+        }))
+        .with_position(config::with(|c| {
+            PhysicalPosition::new(c.window.pos_x, c.window.pos_y)
+        }))
+        .with_maximized(config!().window.maximised)
+        .build(&event_loop)?;
+
+    let dcs = Arc::new(DeviceContextSwapchain::create(&window)?);
+
+    // TODO(cohae): resources should be added to renderdata directly
+    let mut renderer = Renderer::create(&window, dcs.clone())?;
+
+    let stringmap = Arc::new(stringmap);
+    let mut map_loader = MapLoader::new(dcs.clone(), stringmap.clone());
+    let mut maps = MapDataList {
+        current_map: 0,
+        maps: package
+            .get_all_by_reference(0x80807dae)
+            .into_iter()
+            .map(|(index, _)| map_loader.map_entry(TagHash::new(package.pkg_id(), index as _)))
+            .collect::<anyhow::Result<_>>()?,
+    };
+
+    // Only the current map is loaded, the others are loaded when switching to them
+    map_loader.update(&renderer, &mut maps);
+    anyhow::ensure!(
+        maps.current_map().is_some(),
+        "No maps could be loaded from package {pkg_path}"
+    );
+
+    let rasterizer_state = unsafe {
+        dcs.device
+            .CreateRasterizerState(&D3D11_RASTERIZER_DESC {
+                FillMode: D3D11_FILL_SOLID,
+                CullMode: D3D11_CULL_BACK,
+                FrontCounterClockwise: true.into(),
+                DepthBias: 0,
+                DepthBiasClamp: 0.0,
+                SlopeScaledDepthBias: 0.0,
+                DepthClipEnable: true.into(),
+                ScissorEnable: Default::default(),
+                MultisampleEnable: Default::default(),
+                AntialiasedLineEnable: Default::default(),
+            })
+            .context("Failed to create Rasterizer State")?
+    };
+
+    let mut picking_scene = PickingScene::default();
+    if let Some(map) = maps.current_map() {
+        picking_scene.prefetch(map);
+    }
+
+    let mut resources: Resources = Resources::default();
+    resources.insert(FpsCamera::default());
+    resources.insert(InputState::default());
+    resources.insert(maps);
+    resources.insert(picking_scene);
+    // TODO(cohae): This is fucking terrible, just move it to the debug GUI when we can
+    resources.insert(CurrentCubemap(None, None));
+    resources.insert(ErrorRenderer::load(dcs.clone()));
+    resources.insert(ScopeOverrides::default());
+    resources.insert(DebugShapes::default());
+    resources.insert(SelectedObject::default());
+    resources.insert(MapLoadRequest::default());
+    resources.insert(MemoryStats::default());
+
+    let _blend_state = unsafe {
+        dcs.device.CreateBlendState(&D3D11_BLEND_DESC {
+            RenderTarget: [D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: false.into(),
+                SrcBlend: D3D11_BLEND_ONE,
+                DestBlend: D3D11_BLEND_ZERO,
+                BlendOp: D3D11_BLEND_OP_ADD,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_ZERO,
+                BlendOpAlpha: D3D11_BLEND_OP_ADD,
+                RenderTargetWriteMask: (D3D11_COLOR_WRITE_ENABLE_RED.0
+                    | D3D11_COLOR_WRITE_ENABLE_BLUE.0
+                    | D3D11_COLOR_WRITE_ENABLE_GREEN.0)
+                    as u8,
+            }; 8],
+            ..Default::default()
+        })?
+    };
+
+    let gui_fps = Rc::new(RefCell::new(FpsDisplayOverlay::default()));
+    let gui_rendersettings = Rc::new(RefCell::new(RenderSettingsOverlay));
+    let gui_debug = Rc::new(RefCell::new(CameraPositionOverlay {
+        map_resource_filter: {
+            let mut f = [false; MapResource::COUNT];
+            f[0] = true;
+            f
+        },
+    }));
+
+    let gui_resources = Rc::new(RefCell::new(ResourceTypeOverlay {
+        debug_overlay: gui_debug.clone(),
+    }));
+
+    let gui_dump = Rc::new(RefCell::new(TagDumper::new()));
+    let gui_loading = Rc::new(RefCell::new(LoadIndicatorOverlay::default()));
+    let gui_inspector = Rc::new(RefCell::new(InspectorOverlay::default()));
+    let gui_map_browser = Rc::new(RefCell::new(MapBrowserOverlay::new(stringmap.clone())));
+    let gui_hex_viewer = Rc::new(RefCell::new(HexViewerOverlay::new(stringmap.clone())));
+    let gui_search = Rc::new(RefCell::new(SearchOverlay::new(
+        stringmap,
+        gui_hex_viewer.clone(),
+    )));
+    let gui_memory = Rc::new(RefCell::new(MemoryOverlay));
+    let gui_references = Rc::new(RefCell::new(ReferencesOverlay::default()));
+
+    let mut gui = GuiManager::create(&window, &dcs.device);
+    let gui_console = Rc::new(RefCell::new(ConsoleOverlay::default()));
+    gui.add_overlay(gui_debug.clone());
+    gui.add_overlay(gui_rendersettings);
+    gui.add_overlay(gui_resources);
+    gui.add_overlay(gui_console);
+    gui.add_overlay(gui_dump);
+    gui.add_overlay(gui_inspector);
+    gui.add_overlay(gui_map_browser);
+    gui.add_overlay(gui_memory);
+    gui.add_overlay(gui_references);
+    gui.add_overlay(gui_hex_viewer);
+    gui.add_overlay(gui_search);
+    gui.add_overlay(gui_loading);
+    gui.add_overlay(gui_fps);
+
+    let _start_time = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut last_load_prioritization = Instant::now();
+    let mut last_cursor_pos: Option<PhysicalPosition<f64>> = None;
+    // Cursor position at the time the left mouse button was pressed, used to tell clicks apart from camera drags
+    let mut click_start_pos: Option<PhysicalPosition<f64>> = None;
+    let mut present_parameters = 0;
+
+    event_loop.run(move |event, _, control_flow| {
+        gui.handle_event(&event, &window);
+        resources
+            .get_mut::<InputState>()
+            .unwrap()
+            .handle_event(&event);
+
+        match &event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Resized(new_dims) => unsafe {
+                    *dcs.swapchain_target.write() = None;
+                    dcs.swap_chain
+                        .ResizeBuffers(
+                            1,
+                            new_dims.width,
+                            new_dims.height,
+                            DXGI_FORMAT_B8G8R8A8_UNORM,
+                            0,
+                        )
+                        .expect("Failed to resize swapchain");
+
+                    let bb: ID3D11Texture2D = dcs.swap_chain.GetBuffer(0).unwrap();
+
+                    let new_rtv = dcs.device.CreateRenderTargetView(&bb, None).unwrap();
+
+                    dcs.context()
+                        .OMSetRenderTargets(Some(&[Some(new_rtv.clone())]), None);
+
+                    *dcs.swapchain_target.write() = Some(new_rtv);
+
+                    renderer
+                        .resize((new_dims.width, new_dims.height))
+                        .expect("Failed to resize GBuffers");
+                },
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    if let Some(ref mut p) = last_cursor_pos {
+                        let delta = (position.x - p.x, position.y - p.y);
+                        let input = resources.get::<InputState>().unwrap();
+                        if input.mouse_left() && !gui.imgui.io().want_capture_mouse {
+                            let mut camera = resources.get_mut::<FpsCamera>().unwrap();
+                            camera.update_mouse((delta.0 as f32, delta.1 as f32).into());
+                        }
+
+                        last_cursor_pos = Some(*position);
+                    } else {
+                        last_cursor_pos = Some(*position);
+                    }
+                }
+                WindowEvent::MouseInput {
+                    state,
+                    button: winit::event::MouseButton::Left,
+                    ..
+                } => {
+                    if gui.imgui.io().want_capture_mouse {
+                        click_start_pos = None;
+                    } else if *state == ElementState::Pressed {
+                        click_start_pos = last_cursor_pos;
+                    } else if let (Some(start), Some(cursor)) =
+                        (click_start_pos.take(), last_cursor_pos)
+                    {
+                        if (start.x - cursor.x).abs() < 4.0 && (start.y - cursor.y).abs() < 4.0 {
+                            let window_dims = window.inner_size();
+                            let cursor = Vec2::new(cursor.x as f32, cursor.y as f32);
+                            let screen_size =
+                                Vec2::new(window_dims.width as f32, window_dims.height as f32);
+
+                            let maps = resources.get::<MapDataList>().unwrap();
+                            if let Some(map) = maps.current_map() {
+                                let mut camera = resources.get_mut::<FpsCamera>().unwrap();
+                                let camera_position = camera.position;
+                                let cvars = cvars::get();
+                                let debug = gui_debug.borrow();
+                                let mut hit = if cvars.show_map_resources {
+                                    picking::pick_resource_point(
+                                        map,
+                                        &mut camera,
+                                        cursor,
+                                        screen_size,
+                                        |rp| {
+                                            debug.map_resource_filter[rp.resource.index() as usize]
+                                                && rp
+                                                    .translation
+                                                    .truncate()
+                                                    .distance(camera_position)
+                                                    <= cvars.map_resource_distance
+                                        },
+                                    )
+                                } else {
+                                    None
+                                };
+
+                                if hit.is_none() {
+                                    let ray = Ray::from_cursor(&mut camera, cursor, screen_size);
+                                    hit = resources
+                                        .get_mut::<PickingScene>()
+                                        .unwrap()
+                                        .pick_geometry(map, &ray);
+                                }
+
+                                if let Some(hit) = &hit {
+                                    info!(
+                                        "Picked {:?} {} (instance {}) at {}",
+                                        hit.kind, hit.tag, hit.instance_index, hit.position
+                                    );
+                                }
+                                resources.get_mut::<SelectedObject>().unwrap().0 = hit;
+                            }
+                        }
+                    }
+                }
+                // TODO(cohae): Should this even be in here at this point?
+                WindowEvent::KeyboardInput { .. } => {
+                    let input = resources.get::<InputState>().unwrap();
+                    if input.ctrl() && input.is_key_down(VirtualKeyCode::Q) {
+                        *control_flow = ControlFlow::Exit
+                    }
+                }
+
+                _ => (),
+            },
+            Event::RedrawRequested(..) => {
+                {
+                    let request = resources.get_mut::<MapLoadRequest>().unwrap().0.take();
+                    let mut maps = resources.get_mut::<MapDataList>().unwrap();
+                    if let Some(hash) = request {
+                        if let Some(index) = maps.index_of(hash) {
+                            maps.current_map = index;
+                        } else {
+                            match map_loader.map_entry(hash) {
+                                Ok(entry) => {
+                                    maps.maps.push(entry);
+                                    maps.current_map = maps.maps.len() - 1;
+                                }
+                                Err(e) => error!("Failed to read map {hash}: {e}"),
+                            }
+                        }
+                    }
+
+                    if map_loader.update(&renderer, &mut maps) {
+                        info!("Switched to map '{}'", maps.maps[maps.current_map].name);
+                        resources.get_mut::<SelectedObject>().unwrap().0 = None;
+                        if let Some(map) = maps.current_map() {
+                            resources.get_mut::<PickingScene>().unwrap().prefetch(map);
+                        }
+                    }
+
+                    // Load the resources closest to the camera first
+                    if last_load_prioritization.elapsed() > Duration::from_millis(250)
+                        && !(TEXTURE_QUEUE.stats().is_idle() && BUFFER_QUEUE.stats().is_idle())
+                    {
+                        if let Some(map) = maps.current_map() {
+                            let camera_position = resources.get::<FpsCamera>().unwrap().position;
+                            map_loader.prioritize_loads(&renderer, map, camera_position);
+                        }
+                        last_load_prioritization = Instant::now();
+                    }
+                }
+
+                if !gui.imgui.io().want_capture_keyboard {
+                    let mut camera = resources.get_mut::<FpsCamera>().unwrap();
+                    let input_state = resources.get::<InputState>().unwrap();
+                    camera.update(&input_state, last_frame.elapsed().as_secs_f32());
+                }
+                last_frame = Instant::now();
+
+                let window_dims = window.inner_size();
+
+                unsafe {
+                    renderer.clear_render_targets();
+
+                    dcs.context().RSSetViewports(Some(&[D3D11_VIEWPORT {
+                        TopLeftX: 0.0,
+                        TopLeftY: 0.0,
+                        Width: window_dims.width as f32,
+                        Height: window_dims.height as f32,
+                        MinDepth: 0.0,
+                        MaxDepth: 1.0,
+                    }]));
+
+                    dcs.context().RSSetState(&rasterizer_state);
+
+                    renderer.begin_frame();
+
+                    let maps = resources.get::<MapDataList>().unwrap();
+                    let map = maps.current_map();
+                    let cvars = cvars::get();
+
+                    // The map can fail to load, in which case only the GUI is drawn
+                    if let Some(map) = map {
+                        let mut error_renderer = resources.get_mut::<ErrorRenderer>().unwrap();
+                        for ptag in &map.placement_groups {
+                            let (_placements, instance_renderers) =
+                                &map_loader.placement_groups[&ptag.tag().0];
+                            for instance in instance_renderers.iter() {
+                                if cvars.renderlayer_statics {
+                                    if let Err(e) = instance.draw(&mut renderer, false) {
+                                        error!("Failed to draw statics of {}: {e:#}", ptag.tag());
+                                    }
+                                }
+
+                                if cvars.renderlayer_statics_transparent {
+                                    if let Err(e) = instance.draw(&mut renderer, true) {
+                                        error!("Failed to draw statics of {}: {e:#}", ptag.tag());
+                                    }
+                                }
+                            }
+
+                            if cvars.renderlayer_statics {
+                                if let Some(transforms) =
+                                    map_loader.error_instances.get(&ptag.tag().0)
+                                {
+                                    for transform in transforms {
+                                        error_renderer.push(*transform);
+                                    }
+                                }
+                            }
+                        }
+
+                        if cvars.renderlayer_terrain {
+                            for th in &map.terrains {
+                                if let Some(t) = map_loader.terrain_renderers.get(&th.0) {
+                                    if let Err(e) = t.draw(&mut renderer) {
+                                        error!("Failed to draw terrain {th}: {e:#}");
+                                    }
+                                }
+                            }
+                        }
+
+                        if cvars.renderlayer_entities {
+                            for (rp, cb) in &map.resource_points {
+                                let error_transform = Mat4::from_rotation_translation(
+                                    rp.rotation,
+                                    rp.translation.truncate(),
+                                );
+                                if let Some(ent) = map_loader.entity_renderers.get(&rp.entity) {
+                                    if ent.draw(&mut renderer, cb.buffer().clone()).is_err() {
+                                        error_renderer.push(error_transform);
+                                    }
+                                } else if rp.resource.is_entity() {
+                                    error_renderer.push(error_transform);
+                                }
+                            }
+                        }
+                    }
+
+                    if let Some(selected) = &resources.get::<SelectedObject>().unwrap().0 {
+                        resources.get_mut::<DebugShapes>().unwrap().cube_extents(
+                            selected.position,
+                            Vec3::splat(0.1),
+                            Quat::IDENTITY,
+                            [1.0, 0.5, 0.0],
+                            true,
+                        );
+                    }
+
+                    renderer.submit_frame(
+                        &resources,
+                        cvars.render_lights,
+                        cvars.alpha_blending,
+                        cvars.composition_mode,
+                        cvars.blend_override,
+                        map.map(|m| (m.cb_composite_lights.buffer().clone(), m.point_lights.len())),
+                        cvars.evaluate_bytecode,
+                    );
+
+                    {
+                        let mut data = renderer.render_data.data_mut();
+                        if cvars.memory_budget_mb > 0 {
+                            data.evict(cvars.memory_budget_mb * 1024 * 1024);
+                        }
+
+                        *resources.get_mut::<MemoryStats>().unwrap() = MemoryStats {
+                            usage: data.memory_usage(),
+                            evicted: data.evicted,
+                            pending_reloads: data.pending_reloads.len(),
+                        };
+                    }
+
+                    let camera = resources.get::<FpsCamera>().unwrap();
+                    if let Some(MapResource::CubemapVolume(c, _)) = map
+                        .and_then(|map| {
+                            map.resource_points.iter().find(|(r, _)| {
+                                if let MapResource::CubemapVolume(_, aabb) = &r.resource {
+                                    aabb.contains_point(camera.position)
+                                } else {
+                                    false
+                                }
+                            })
+                        })
+                        .map(|(r, _)| &r.resource)
+                    {
+                        if let Some(mut cr) = resources.get_mut::<CurrentCubemap>() {
+                            cr.0 = Some(c.cubemap_name.to_string());
+                        }
+                        renderer
+                            .render_data
+                            .data()
+                            .textures
+                            .get(&c.cubemap_texture)
+                            .map(|t| t.view.clone())
+                    } else {
+                        if let Some(mut cr) = resources.get_mut::<CurrentCubemap>() {
+                            cr.0 = None;
+                        }
+                        None
+                    };
+
+                    drop(camera);
+                    drop(maps);
+                    gui.draw_frame(&window, last_frame.elapsed(), &mut resources);
+
+                    dcs.context().OMSetDepthStencilState(None, 0);
+
+                    if dcs
+                        .swap_chain
+                        .Present(DXGI_SWAP_EFFECT_SEQUENTIAL.0 as _, present_parameters)
+                        == DXGI_STATUS_OCCLUDED
+                    {
+                        present_parameters = DXGI_PRESENT_TEST;
+                        std::thread::sleep(Duration::from_millis(50));
+                    } else {
+                        present_parameters = 0;
+                    }
+
+                    if let Some(c) = tracy_client::Client::running() {
+                        c.frame_mark()
+                    }
+                };
+            }
+            Event::MainEventsCleared => {
+                let io = gui.imgui.io_mut();
+                gui.platform
+                    .prepare_frame(io, &window)
+                    .expect("Failed to start frame");
+                window.request_redraw();
+            }
+            Event::LoopDestroyed => {
+                config::with_mut(|c| {
+                    let size = window.inner_size();
+                    let pos = window
+                        .outer_position()
+                        .unwrap_or(PhysicalPosition::default());
+                    c.window = WindowConfig {
+                        width: size.width,
+                        height: size.height,
+                        pos_x: pos.x,
+                        pos_y: pos.y,
+                        maximised: window.is_maximized(),
+                    };
+                });
+                config::persist();
+            }
+            _ => (),
+        }
+    });
+}